@@ -20,7 +20,7 @@ fn main() {
                              });
 
     // Create a client.
-    let client = KintoClient::new(server_url.to_owned(), auth.into());
+    let client = KintoClient::new(server_url.to_owned(), auth.into()).unwrap();
 
     // Pick a new record using the default bucket
     let ref mut new_record = client.bucket("default").collection("notes").new_record();
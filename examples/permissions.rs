@@ -6,7 +6,8 @@ extern crate serde_json;
 
 
 use hyper::header::{Authorization, Basic};
-use kinto_http::{KintoClient, Resource};
+use kinto_http::{KintoClient, Resource, Permissions, Principal};
+use kinto_http::permissions::BucketPermission;
 
 
 fn main() {
@@ -20,12 +21,12 @@ fn main() {
                              });
 
     // Create a client.
-    let client = KintoClient::new(server_url.to_owned(), auth.into());
+    let client = KintoClient::new(server_url.to_owned(), auth.into()).unwrap();
 
     // Pick a new record using the default bucket
     let mut new_bucket = client.new_bucket();
 
-    new_bucket.permissions.read = Some(vec!["system.Everyone".to_owned()]);
+    new_bucket.permissions.grant(BucketPermission::Read, Principal::Everyone);
     new_bucket.data = Some(json!({"title": "Hello World"}));
 
     // Save the record on the server or panic if fails
@@ -34,7 +35,7 @@ fn main() {
     println!("{:?}", new_bucket.permissions);
 
     // Create an unautheticated client.
-    let pub_client = KintoClient::new(server_url.to_owned(), None);
+    let pub_client = KintoClient::new(server_url.to_owned(), None).unwrap();
 
 
     // Get the created record by id
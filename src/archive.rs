@@ -0,0 +1,754 @@
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+
+use serde_json;
+use serde_json::Value;
+use hyper::header::IfNoneMatch;
+
+use KintoClient;
+use error::KintoError;
+use batch::send_batches;
+use request::{KintoRequest, PayloadedEndpoint, PluralEndpoint, UpdateRecord};
+use resource::Resource;
+use bucket::Bucket;
+use collection::Collection;
+use conflict::without_timestamp;
+use utils::unwrap_collection_records;
+
+
+/// Version of the archive format.
+pub const ARCHIVE_VERSION: u64 = 1;
+
+
+/// Serialization of an archive.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveFormat {
+    /// A single JSON document.
+    Json,
+    /// One JSON object per line (bucket, groups, collections then records),
+    /// written while the objects are fetched.
+    Ndjson,
+}
+
+
+/// Object of an archive, with its permissions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveObject {
+    pub data: Value,
+    #[serde(default)]
+    pub permissions: Value,
+}
+
+
+/// Collection of an archive, with its records.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ArchiveCollection {
+    pub data: Value,
+    #[serde(default)]
+    pub permissions: Value,
+    #[serde(default)]
+    pub records: Vec<ArchiveObject>,
+}
+
+
+/// Content of a bucket: metadata, groups, collections and records.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BucketArchive {
+    pub version: u64,
+    pub bucket: ArchiveObject,
+    #[serde(default)]
+    pub groups: Vec<ArchiveObject>,
+    #[serde(default)]
+    pub collections: Vec<ArchiveCollection>,
+}
+
+
+/// What to do with the objects that already exist on import.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnExisting {
+    Skip,
+    Overwrite,
+}
+
+
+/// Options of an archive import.
+#[derive(Debug, Clone)]
+pub struct ImportOptions {
+    /// Id of the restored bucket, the archived one if not set.
+    pub bucket_id: Option<String>,
+    /// Principals replaced in permissions and groups members.
+    pub principals: HashMap<String, String>,
+    pub on_existing: OnExisting,
+}
+
+
+impl Default for ImportOptions {
+    fn default() -> ImportOptions {
+        ImportOptions {
+            bucket_id: None,
+            principals: HashMap::new(),
+            on_existing: OnExisting::Skip,
+        }
+    }
+}
+
+
+impl ImportOptions {
+    pub fn new() -> ImportOptions {
+        ImportOptions::default()
+    }
+
+    /// Restore the archive under another bucket id.
+    pub fn bucket_id(&mut self, id: &str) -> &mut Self {
+        self.bucket_id = Some(id.to_owned());
+        self
+    }
+
+    /// Replace a principal (e.g. the user id on the other server).
+    pub fn remap(&mut self, from: &str, to: &str) -> &mut Self {
+        self.principals.insert(from.to_owned(), to.to_owned());
+        self
+    }
+
+    pub fn on_existing(&mut self, on_existing: OnExisting) -> &mut Self {
+        self.on_existing = on_existing;
+        self
+    }
+}
+
+
+/// Object rejected by the server on import.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportError {
+    pub uri: String,
+    pub status: u16,
+    pub details: Value,
+}
+
+
+/// Report of an archive import.
+#[derive(Debug, Clone, Default)]
+pub struct ImportResult {
+    /// Number of objects created or overwritten.
+    pub imported: usize,
+    /// Number of existing objects left untouched.
+    pub skipped: usize,
+    pub errors: Vec<ImportError>,
+}
+
+
+impl ImportResult {
+    /// Whether all the objects were imported or skipped.
+    pub fn ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+
+/// Entry of an archive, in the order of a NDJSON archive.
+enum ArchiveEntry {
+    Bucket(ArchiveObject),
+    Group(ArchiveObject),
+    Collection(ArchiveObject),
+    Record(String, ArchiveObject),
+}
+
+
+impl ArchiveEntry {
+    fn to_line(&self) -> Value {
+        let (kind, object) = match *self {
+            ArchiveEntry::Bucket(ref object) => ("bucket", object),
+            ArchiveEntry::Group(ref object) => ("group", object),
+            ArchiveEntry::Collection(ref object) => ("collection", object),
+            ArchiveEntry::Record(_, ref object) => ("record", object),
+        };
+        let mut line = json!({
+            "type": kind,
+            "data": object.data,
+            "permissions": object.permissions,
+        });
+        match *self {
+            ArchiveEntry::Bucket(_) => line["version"] = ARCHIVE_VERSION.into(),
+            ArchiveEntry::Record(ref collection_id, _) => {
+                line["collection"] = collection_id.as_str().into()
+            }
+            _ => (),
+        };
+        line
+    }
+
+    fn from_line(line: Value) -> Result<ArchiveEntry, KintoError> {
+        let object = ArchiveObject {
+            data: line["data"].to_owned(),
+            permissions: line["permissions"].to_owned(),
+        };
+        match line["type"].as_str() {
+            Some("bucket") => Ok(ArchiveEntry::Bucket(object)),
+            Some("group") => Ok(ArchiveEntry::Group(object)),
+            Some("collection") => Ok(ArchiveEntry::Collection(object)),
+            Some("record") => {
+                match line["collection"].as_str() {
+                    Some(id) => Ok(ArchiveEntry::Record(id.to_owned(), object)),
+                    None => Err(KintoError::JsonError),
+                }
+            }
+            _ => Err(KintoError::JsonError),
+        }
+    }
+}
+
+
+impl BucketArchive {
+    /// Fetch all the objects of a bucket.
+    pub fn fetch(bucket: &Bucket) -> Result<BucketArchive, KintoError> {
+        let mut archive = BucketArchive::default();
+        try!(walk(bucket, |entry| archive.push(entry)));
+        Ok(archive)
+    }
+
+    /// Read a JSON or NDJSON archive. NDJSON archives are parsed line by line.
+    pub fn read<R>(reader: R) -> Result<BucketArchive, KintoError>
+        where R: Read
+    {
+        let mut reader = BufReader::new(reader);
+        let mut first = String::new();
+        while first.trim().is_empty() {
+            if try!(reader.read_line(&mut first)) == 0 {
+                return Err(KintoError::JsonError);
+            }
+        }
+
+        // A NDJSON archive starts with the bucket entry, a JSON archive is a
+        // single document without entry type (possibly on several lines)
+        let line = serde_json::from_str::<Value>(&first).unwrap_or(Value::Null);
+        if line["type"].is_null() {
+            let archive: BucketArchive =
+                try!(serde_json::from_reader(first.as_bytes().chain(reader)));
+            try!(check_version(&archive.version.into()));
+            return Ok(archive);
+        }
+
+        try!(check_version(&line["version"]));
+        let mut archive = BucketArchive::default();
+        try!(archive.push(try!(ArchiveEntry::from_line(line))));
+        for line in reader.lines() {
+            let line = try!(line);
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = try!(ArchiveEntry::from_line(try!(serde_json::from_str(&line))));
+            try!(archive.push(entry));
+        }
+        Ok(archive)
+    }
+
+    /// Write the archive as a single JSON document.
+    pub fn write<W>(&self, writer: &mut W) -> Result<(), KintoError>
+        where W: Write
+    {
+        Ok(try!(serde_json::to_writer(writer, self)))
+    }
+
+    /// Id of the archived bucket.
+    pub fn bucket_id(&self) -> Option<&str> {
+        self.bucket.data["id"].as_str()
+    }
+
+    /// Create the archived objects on the server, in batches.
+    pub fn restore(&self,
+                   client: &KintoClient,
+                   options: &ImportOptions)
+                   -> Result<ImportResult, KintoError> {
+        let source_id = try!(self.bucket_id().ok_or(KintoError::UndefinedIdError));
+        let bucket_id = options.bucket_id.clone().unwrap_or_else(|| source_id.to_owned());
+        let remapper = Remapper {
+            options: options,
+            groups: Some((format!("/buckets/{}/groups/", source_id),
+                          format!("/buckets/{}/groups/", bucket_id))),
+        };
+        let bucket = client.bucket(&bucket_id);
+        let mut requests = ImportRequests::default();
+
+        let mut data = self.bucket.data.clone();
+        data["id"] = bucket_id.as_str().into();
+        let object = ArchiveObject {
+            data: data,
+            permissions: self.bucket.permissions.clone(),
+        };
+        try!(requests.add(&bucket, &remapper, &object));
+
+        for object in &self.groups {
+            let mut object = object.clone();
+            match object.data["members"].as_array_mut() {
+                Some(members) => {
+                    for member in members.iter_mut() {
+                        let principal = remapper.principal(member.as_str().unwrap_or(""));
+                        *member = principal.into();
+                    }
+                }
+                None => (),
+            };
+            let group = bucket.group(try!(object_id(&object)));
+            try!(requests.add(&group, &remapper, &object));
+        }
+
+        for archived in &self.collections {
+            let object = archived.object();
+            let collection = bucket.clone().collection(try!(object_id(&object)));
+            try!(archived.add_requests(&mut requests, &collection, &remapper));
+        }
+        requests.send(client, options)
+    }
+
+    fn push(&mut self, entry: ArchiveEntry) -> Result<(), KintoError> {
+        match entry {
+            ArchiveEntry::Bucket(object) => {
+                self.version = ARCHIVE_VERSION;
+                self.bucket = object;
+            }
+            ArchiveEntry::Group(object) => self.groups.push(object),
+            ArchiveEntry::Collection(object) => {
+                self.collections.push(ArchiveCollection {
+                                          data: object.data,
+                                          permissions: object.permissions,
+                                          records: vec![],
+                                      })
+            }
+            ArchiveEntry::Record(collection_id, object) => {
+                let collection = self.collections
+                    .iter_mut()
+                    .find(|collection| collection.data["id"] == collection_id.as_str());
+                match collection {
+                    Some(collection) => collection.records.push(object),
+                    None => return Err(KintoError::UndefinedIdError),
+                };
+            }
+        };
+        Ok(())
+    }
+}
+
+
+impl ArchiveCollection {
+    /// Fetch a collection with its records.
+    pub fn fetch(collection: &Collection) -> Result<ArchiveCollection, KintoError> {
+        let mut collection = collection.clone();
+        try!(collection.load());
+        Ok(ArchiveCollection {
+               data: collection.data.clone().unwrap_or_default(),
+               permissions: collection.get_permissions().unwrap_or_default(),
+               records: try!(load_children(collection.new_record())),
+           })
+    }
+
+    /// Create the archived collection and its records in a bucket, under
+    /// the given id.
+    pub fn restore(&self,
+                   bucket: &Bucket,
+                   id: &str,
+                   options: &ImportOptions)
+                   -> Result<ImportResult, KintoError> {
+        let remapper = Remapper {
+            options: options,
+            groups: None,
+        };
+
+        let mut archived = self.clone();
+        archived.data["id"] = id.into();
+        let collection = bucket.clone().collection(id);
+        let mut requests = ImportRequests::default();
+        try!(archived.add_requests(&mut requests, &collection, &remapper));
+        requests.send(&bucket.get_client(), options)
+    }
+
+    /// Collection object, without its records.
+    fn object(&self) -> ArchiveObject {
+        ArchiveObject {
+            data: self.data.clone(),
+            permissions: self.permissions.clone(),
+        }
+    }
+
+    fn add_requests(&self,
+                    requests: &mut ImportRequests,
+                    collection: &Collection,
+                    remapper: &Remapper)
+                    -> Result<(), KintoError> {
+        try!(requests.add(collection, remapper, &self.object()));
+        for object in &self.records {
+            let record = collection.record(try!(object_id(object)));
+            try!(requests.add(&record, remapper, object));
+        }
+        Ok(())
+    }
+}
+
+
+/// Write all the objects of a bucket.
+pub fn export<W>(bucket: &Bucket,
+                 writer: &mut W,
+                 format: ArchiveFormat)
+                 -> Result<(), KintoError>
+    where W: Write
+{
+    match format {
+        ArchiveFormat::Json => try!(BucketArchive::fetch(bucket)).write(writer),
+        ArchiveFormat::Ndjson => {
+            walk(bucket, |entry| {
+                try!(serde_json::to_writer(&mut *writer, &entry.to_line()));
+                try!(writer.write_all(b"\n"));
+                Ok(())
+            })
+        }
+    }
+}
+
+
+/// Restore a JSON or NDJSON archive.
+pub fn import<R>(client: &KintoClient,
+                 reader: R,
+                 options: &ImportOptions)
+                 -> Result<ImportResult, KintoError>
+    where R: Read
+{
+    try!(BucketArchive::read(reader)).restore(client, options)
+}
+
+
+/// Fetch the objects of a bucket, parents first.
+fn walk<F>(bucket: &Bucket, mut emit: F) -> Result<(), KintoError>
+    where F: FnMut(ArchiveEntry) -> Result<(), KintoError>
+{
+    let mut bucket = bucket.clone();
+    try!(bucket.load());
+    try!(emit(ArchiveEntry::Bucket(ArchiveObject {
+                                        data: bucket.data.clone().unwrap_or_default(),
+                                        permissions: bucket.get_permissions()
+                                            .unwrap_or_default(),
+                                    })));
+
+    for object in try!(load_children(bucket.new_group())) {
+        try!(emit(ArchiveEntry::Group(object)));
+    }
+
+    for object in try!(load_children(bucket.new_collection())) {
+        let collection_id = try!(object_id(&object)).to_owned();
+        let collection = bucket.clone().collection(&collection_id);
+        try!(emit(ArchiveEntry::Collection(object)));
+
+        for object in try!(load_children(collection.new_record())) {
+            try!(emit(ArchiveEntry::Record(collection_id.clone(), object)));
+        }
+    }
+    Ok(())
+}
+
+
+/// Get the children of a plural endpoint as stored on the server, without
+/// transformers. The listing only returns ids since it has no permissions.
+fn load_children<T>(child: T) -> Result<Vec<ArchiveObject>, KintoError>
+    where T: Resource
+{
+    let mut request = try!(child.list_request());
+    request.fields("id");
+    let response = try!(request.follow_subrequests());
+    let client = child.get_client();
+    load_all(&client, &unwrap_collection_records(response, child))
+}
+
+
+/// Get the data and permissions of the objects, in batches.
+fn load_all<T>(client: &KintoClient,
+               objects: &[T])
+               -> Result<Vec<ArchiveObject>, KintoError>
+    where T: Resource
+{
+    let mut requests = vec![];
+    for object in objects {
+        requests.push(try!(object.load_request()));
+    }
+
+    let mut loaded = vec![];
+    for response in try!(send_batches(client, requests)) {
+        if !response.status.is_success() {
+            return Err(KintoError::HttpError(response.status.to_u16(), response.body));
+        }
+        loaded.push(ArchiveObject {
+                        data: response.body["data"].to_owned(),
+                        permissions: response.body["permissions"].to_owned(),
+                    });
+    }
+    Ok(loaded)
+}
+
+
+/// Reject the archives written with another format version.
+fn check_version(version: &Value) -> Result<(), KintoError> {
+    match version.as_u64() {
+        Some(ARCHIVE_VERSION) => Ok(()),
+        Some(version) => Err(KintoError::ArchiveVersionError(version)),
+        None => Err(KintoError::JsonError),
+    }
+}
+
+
+fn object_id(object: &ArchiveObject) -> Result<&str, KintoError> {
+    object.data["id"].as_str().ok_or(KintoError::UndefinedIdError)
+}
+
+
+/// Requests of an import, with the URIs of the imported objects.
+#[derive(Default)]
+struct ImportRequests {
+    uris: Vec<String>,
+    requests: Vec<UpdateRecord>,
+}
+
+
+impl ImportRequests {
+    fn add<T>(&mut self,
+              resource: &T,
+              remapper: &Remapper,
+              object: &ArchiveObject)
+              -> Result<(), KintoError>
+        where T: Resource
+    {
+        self.uris.push(try!(resource.uri()));
+        self.requests.push(try!(remapper.request(resource, object)));
+        Ok(())
+    }
+
+    /// Send the requests in batches.
+    fn send(self,
+            client: &KintoClient,
+            options: &ImportOptions)
+            -> Result<ImportResult, KintoError> {
+        let responses = try!(send_batches(client, self.requests));
+        let mut result = ImportResult::default();
+        for (uri, response) in self.uris.into_iter().zip(responses) {
+            let status = response.status.to_u16();
+            if response.status.is_success() {
+                result.imported += 1;
+            } else if status == 412 && options.on_existing == OnExisting::Skip {
+                result.skipped += 1;
+            } else {
+                result.errors.push(ImportError {
+                                       uri: uri,
+                                       status: status,
+                                       details: response.body,
+                                   });
+            }
+        }
+        Ok(result)
+    }
+}
+
+
+/// Prepare the requests of an import, replacing the principals.
+struct Remapper<'a> {
+    options: &'a ImportOptions,
+    /// Groups URIs prefixes of the archived bucket and of the target one.
+    groups: Option<(String, String)>,
+}
+
+
+impl<'a> Remapper<'a> {
+    fn principal(&self, principal: &str) -> String {
+        match self.options.principals.get(principal) {
+            Some(remapped) => return remapped.to_owned(),
+            None => (),
+        };
+        match self.groups {
+            Some((ref source, ref target)) if principal.starts_with(source.as_str()) => {
+                return principal.replacen(source.as_str(), target.as_str(), 1);
+            }
+            _ => (),
+        };
+        principal.to_owned()
+    }
+
+    fn permissions(&self, permissions: &Value) -> Value {
+        let mut remapped = json!({});
+        match permissions.as_object() {
+            Some(permissions) => {
+                for (permission, principals) in permissions {
+                    let principals: Vec<String> = principals.as_array()
+                        .map(|principals| {
+                                 principals.iter()
+                                     .filter_map(|principal| principal.as_str())
+                                     .map(|principal| self.principal(principal))
+                                     .collect()
+                             })
+                        .unwrap_or_default();
+                    remapped[permission.as_str()] = principals.into();
+                }
+            }
+            None => (),
+        };
+        remapped
+    }
+
+    fn request<T>(&self,
+                  resource: &T,
+                  object: &ArchiveObject)
+                  -> Result<UpdateRecord, KintoError>
+        where T: Resource
+    {
+        let mut request = try!(resource.update_request());
+        let mut body = json!({"data": without_timestamp(&object.data)});
+        let permissions = self.permissions(&object.permissions);
+        if permissions != json!({}) {
+            body["permissions"] = permissions;
+        }
+        request.body(Some(body));
+        if self.options.on_existing == OnExisting::Skip {
+            request.if_none_match(IfNoneMatch::Any);
+        }
+        Ok(request)
+    }
+}
+
+
+#[cfg(test)]
+mod test_archive {
+    use std::io::Cursor;
+
+    use serde_json;
+
+    use error::KintoError;
+    use resource::Resource;
+    use bucket::Bucket;
+    use group::Group;
+    use permissions::{Permissions, RecordPermission};
+    use transformer::AesGcmTransformer;
+    use utils::tests::{setup_bucket, setup_collection};
+    use super::{ArchiveCollection, ArchiveFormat, BucketArchive, ImportOptions,
+                OnExisting, import};
+
+    fn fill_bucket() -> Bucket {
+        let mut bucket = setup_bucket();
+        bucket.data = json!({"title": "Food"}).into();
+        bucket.set().unwrap();
+
+        let mut group: Group = bucket.group("cooks");
+        group.set_members(vec!["account:alice".to_owned()]);
+        group.set().unwrap();
+
+        let mut collection = bucket.clone().collection("meat");
+        collection.set().unwrap();
+        for id in &["entrecote", "onglet"] {
+            let mut record = collection.record(id);
+            record.data = json!({"title": id}).into();
+            record.permissions.grant(RecordPermission::Read, "account:alice");
+            record.set().unwrap();
+        }
+        bucket
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let bucket = fill_bucket();
+        for format in &[ArchiveFormat::Json, ArchiveFormat::Ndjson] {
+            let mut exported = vec![];
+            bucket.export(&mut exported, *format).unwrap();
+            let archive = BucketArchive::read(Cursor::new(exported)).unwrap();
+
+            assert_eq!(archive.bucket_id(), Some("food"));
+            assert_eq!(archive.bucket.data["title"], "Food");
+            assert_eq!(archive.groups[0].data["members"][0], "account:alice");
+            assert_eq!(archive.collections[0].data["id"], "meat");
+            let records = &archive.collections[0].records;
+            assert_eq!(records.len(), 2);
+            assert_eq!(records[0].permissions["read"][0], "account:alice");
+        }
+    }
+
+    #[test]
+    fn test_read_archive_versions() {
+        let mut archive = BucketArchive::fetch(&fill_bucket()).unwrap();
+        let pretty = serde_json::to_vec_pretty(&archive).unwrap();
+        let read = BucketArchive::read(Cursor::new(pretty)).unwrap();
+        assert_eq!(read, archive);
+
+        archive.version = 2;
+        let mut written = vec![];
+        archive.write(&mut written).unwrap();
+        match BucketArchive::read(Cursor::new(written)) {
+            Err(KintoError::ArchiveVersionError(2)) => (),
+            result => panic!("unexpected result {:?}", result),
+        };
+
+        let line = json!({"type": "bucket", "version": 2, "data": {"id": "food"}});
+        match BucketArchive::read(Cursor::new(format!("{}\n", line))) {
+            Err(KintoError::ArchiveVersionError(2)) => (),
+            result => panic!("unexpected result {:?}", result),
+        };
+    }
+
+    #[test]
+    fn test_fetch_keeps_stored_records() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        collection.transformers.push(AesGcmTransformer::new("key", &[1; 32]).unwrap());
+        let mut record = collection.record("note");
+        record.data = json!({"title": "secret"}).into();
+        record.permissions.grant(RecordPermission::Read, "account:alice");
+        record.create().unwrap();
+
+        let archive = ArchiveCollection::fetch(&collection).unwrap();
+        let archived = &archive.records[0];
+        assert!(archived.data["title"].is_null());
+        assert!(archived.data["ciphertext"].is_string());
+        assert_eq!(archived.permissions["read"][0], "account:alice");
+    }
+
+    #[test]
+    fn test_import_under_new_bucket() {
+        let bucket = fill_bucket();
+        let client = bucket.get_client();
+        let mut exported = vec![];
+        bucket.export(&mut exported, ArchiveFormat::Ndjson).unwrap();
+
+        let mut options = ImportOptions::new();
+        options.bucket_id("food-copy").remap("account:alice", "account:bob");
+        let result = import(&client, Cursor::new(exported), &options).unwrap();
+        assert!(result.ok());
+        assert_eq!(result.imported, 5);
+
+        let bucket = client.bucket("food-copy");
+        let mut group = bucket.group("cooks");
+        group.load().unwrap();
+        assert_eq!(group.members(), vec!["account:bob"]);
+
+        let mut record = bucket.collection("meat").record("onglet");
+        record.load().unwrap();
+        assert_eq!(record.data.unwrap()["title"], "onglet");
+        assert!(record.permissions.has(RecordPermission::Read, "account:bob"));
+    }
+
+    #[test]
+    fn test_import_existing_objects() {
+        let bucket = fill_bucket();
+        let client = bucket.get_client();
+        let mut exported = vec![];
+        bucket.export(&mut exported, ArchiveFormat::Json).unwrap();
+
+        let mut record = bucket.collection("meat").record("onglet");
+        record.data = json!({"title": "changed"}).into();
+        record.set().unwrap();
+
+        let options = ImportOptions::new();
+        let result = import(&client, Cursor::new(exported.clone()), &options).unwrap();
+        assert_eq!(result.imported, 0);
+        assert_eq!(result.skipped, 5);
+        record.load().unwrap();
+        assert_eq!(record.data.clone().unwrap()["title"], "changed");
+
+        let mut options = ImportOptions::new();
+        options.on_existing(OnExisting::Overwrite);
+        let result = import(&client, Cursor::new(exported), &options).unwrap();
+        assert_eq!(result.imported, 5);
+        record.load().unwrap();
+        assert_eq!(record.data.unwrap()["title"], "onglet");
+    }
+}
@@ -0,0 +1,274 @@
+use std::io::{Read, Write};
+
+use serde_json;
+use serde_json::Value;
+use hyper::Url;
+use hyper::method::Method;
+use hyper::header::{Headers, ContentType};
+use openssl::hash::{Hasher, MessageDigest};
+
+use KintoClient;
+use error::KintoError;
+use request::{RequestPreparer, KintoRequest};
+
+
+/// Multipart boundary used for the attachment uploads.
+const BOUNDARY: &str = "----KintoHttpAttachmentBoundary7MA4YWxkTrZu0gW";
+
+
+/// Attachment metadata, as stored in the record `attachment` field.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AttachmentInfo {
+    pub location: String,
+    pub filename: String,
+    pub hash: String,
+    pub mimetype: String,
+    pub size: u64,
+}
+
+
+/// Upload request on the record attachment endpoint (kinto-attachment plugin).
+#[derive(Debug, Clone)]
+pub struct UploadAttachment {
+    pub preparer: RequestPreparer,
+}
+
+impl UploadAttachment {
+    pub fn new(client: KintoClient, path: String) -> UploadAttachment {
+        let mut preparer = RequestPreparer::new(client, path);
+        preparer.method = Method::Post;
+        UploadAttachment { preparer: preparer }
+    }
+
+    /// Set the multipart form payload with the file content and the record data.
+    pub fn form(&mut self,
+                content: &[u8],
+                mimetype: &str,
+                filename: &str,
+                data: Option<Value>)
+                -> &mut Self {
+        let mut payload = vec![];
+
+        match data {
+            Some(data) => {
+                payload.extend(format!("--{}\r\n\
+                                        Content-Disposition: form-data; \
+                                        name=\"data\"\r\n\r\n{}\r\n",
+                                       BOUNDARY,
+                                       serde_json::to_string(&data).unwrap())
+                                   .as_bytes());
+            }
+            None => (),
+        };
+
+        payload.extend(format!("--{}\r\n\
+                                Content-Disposition: form-data; name=\"attachment\"; \
+                                filename=\"{}\"\r\n\
+                                Content-Type: {}\r\n\r\n",
+                               BOUNDARY,
+                               filename.replace("\"", "\\\""),
+                               mimetype)
+                           .as_bytes());
+        payload.extend(content);
+        payload.extend(format!("\r\n--{}--\r\n", BOUNDARY).as_bytes());
+
+        let content_type = format!("multipart/form-data; boundary={}", BOUNDARY);
+        self.preparer.headers.set(ContentType(content_type.parse().unwrap()));
+        self.preparer.payload = Some(payload);
+        self
+    }
+}
+
+impl KintoRequest for UploadAttachment {
+    fn preparer(&mut self) -> &mut RequestPreparer {
+        &mut self.preparer
+    }
+}
+
+
+/// Get the attachments base url from the server capabilities.
+pub fn attachments_base_url(client: &KintoClient) -> Result<String, KintoError> {
+    let info = try!(client.server_info());
+    match info["capabilities"]["attachments"]["base_url"].as_str() {
+        Some(base_url) => Ok(base_url.to_owned()),
+        None => Err(KintoError::UnavailableEndpointError),
+    }
+}
+
+
+/// Download an attachment into a writer, checking its size and hash.
+///
+/// The client credentials are only sent if the attachments are served from
+/// the Kinto server origin.
+pub fn download<W>(client: &KintoClient,
+                   info: &AttachmentInfo,
+                   writer: &mut W)
+                   -> Result<(), KintoError>
+    where W: Write
+{
+    let base_url = try!(attachments_base_url(client));
+    let url = format!("{}/{}",
+                      base_url.trim_end_matches("/"),
+                      info.location.trim_start_matches("/"));
+
+    let parsed = try!(Url::parse(url.as_str()).map_err(|_| KintoError::UrlError));
+    let mut headers = Headers::new();
+    match client.auth().cloned() {
+        Some(method) if parsed.origin() == client.server_url().origin() => {
+            headers.set(method)
+        }
+        _ => (),
+    };
+
+    let mut response = match client.http_client()
+              .get(url.as_str())
+              .headers(headers)
+              .send() {
+        Ok(response) => response,
+        Err(_) => return Err(KintoError::HyperError),
+    };
+
+    if !response.status.is_success() {
+        let mut content = String::new();
+        let _ = response.read_to_string(&mut content);
+        let body = serde_json::from_str(&content).unwrap_or(Value::Null);
+        return Err(KintoError::HttpError(response.status.to_u16(), body));
+    }
+
+    let mut hasher = try!(Hasher::new(MessageDigest::sha256())
+                              .map_err(|_| KintoError::IntegrityError));
+    let mut size = 0;
+    let mut buffer = [0; 8192];
+
+    loop {
+        let read = try!(response.read(&mut buffer));
+        if read == 0 {
+            break;
+        }
+        try!(hasher.update(&buffer[..read]).map_err(|_| KintoError::IntegrityError));
+        try!(writer.write_all(&buffer[..read]));
+        size += read as u64;
+    }
+
+    let digest = try!(hasher.finish().map_err(|_| KintoError::IntegrityError));
+    let hash: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+
+    if size != info.size || hash != info.hash {
+        return Err(KintoError::IntegrityError);
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test_attachment {
+    use std::io::{Cursor, Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::thread::JoinHandle;
+
+    use KintoClient;
+    use error::KintoError;
+    use resource::Resource;
+    use utils::tests::{setup_client, setup_record};
+    use super::{AttachmentInfo, download};
+
+    /// Answer one request, returning it.
+    fn serve(response: String) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let read = stream.read(&mut request).unwrap();
+            stream.write_all(response.as_bytes()).unwrap();
+            String::from_utf8_lossy(&request[..read]).into_owned()
+        });
+        (url, server)
+    }
+
+    /// Serve the capabilities of a server whose attachments are on a CDN.
+    fn serve_server(cdn_url: &str) -> (String, JoinHandle<String>) {
+        let info = json!({"capabilities": {"attachments": {"base_url": cdn_url}}});
+        let body = info.to_string();
+        serve(format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                       Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                      body.len(),
+                      body))
+    }
+
+    fn beef() -> AttachmentInfo {
+        AttachmentInfo {
+            location: "food/beef.txt".to_owned(),
+            hash: "aa415c4e8890cf0fec7826aec962ffbcc04534faefd2b3266c54f690d40d6e82"
+                .to_owned(),
+            size: 4,
+            ..AttachmentInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_attach_record() {
+        let mut record = setup_record();
+        record.data = json!({"good": true}).into();
+        record.attach(Cursor::new(b"beef"), "text/plain", "beef.txt").unwrap();
+
+        let info = record.attachment().unwrap();
+        assert_eq!(info.filename, "beef.txt");
+        assert_eq!(info.mimetype, "text/plain");
+        assert_eq!(record.data.unwrap()["good"].as_bool().unwrap(), true);
+    }
+
+    #[test]
+    fn test_download_attachment() {
+        let mut record = setup_record();
+        record.attach(Cursor::new(b"beef"), "text/plain", "beef.txt").unwrap();
+
+        let mut content = vec![];
+        record.download_attachment(&mut content).unwrap();
+        assert_eq!(content, b"beef");
+    }
+
+    #[test]
+    fn test_detach_record() {
+        let mut record = setup_record();
+        record.attach(Cursor::new(b"beef"), "text/plain", "beef.txt").unwrap();
+        record.detach().unwrap();
+        assert!(record.attachment().is_none());
+        record.load().unwrap();
+    }
+
+    #[test]
+    fn test_download_from_other_origin() {
+        let (cdn_url, cdn) = serve("HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\
+                                    Connection: close\r\n\r\nbeef"
+                                       .to_owned());
+        let (server_url, server) = serve_server(&cdn_url);
+        let auth = setup_client().auth().cloned();
+        let client = KintoClient::new(server_url, auth).unwrap();
+
+        let mut content = vec![];
+        download(&client, &beef(), &mut content).unwrap();
+        assert_eq!(content, b"beef");
+        assert!(server.join().unwrap().contains("Authorization"));
+        let request = cdn.join().unwrap();
+        assert!(request.starts_with("GET /food/beef.txt "));
+        assert!(!request.contains("Authorization"));
+    }
+
+    #[test]
+    fn test_download_error() {
+        let (cdn_url, cdn) = serve("HTTP/1.1 404 Not Found\r\nContent-Length: 2\r\n\
+                                    Connection: close\r\n\r\n{}"
+                                       .to_owned());
+        let (server_url, server) = serve_server(&cdn_url);
+        let client = KintoClient::new(server_url, None).unwrap();
+
+        match download(&client, &beef(), &mut vec![]) {
+            Err(KintoError::HttpError(404, _)) => (),
+            result => panic!("{:?}", result),
+        };
+        server.join().unwrap();
+        cdn.join().unwrap();
+    }
+}
@@ -4,11 +4,16 @@ use hyper::status::StatusCode;
 use serde_json::map::Map;
 
 use KintoClient;
+use error::KintoError;
 use paths::Paths;
 use request::{RequestPreparer, KintoRequest};
 use response::ResponseWrapper;
 
 
+/// Maximum number of requests sent in a single batch.
+pub const BATCH_MAX_REQUESTS: usize = 25;
+
+
 #[derive(Debug, Clone)]
 pub struct BatchRequest {
     pub preparer: RequestPreparer,
@@ -44,7 +49,7 @@ impl KintoRequest for BatchRequest {
 
             let mut headers = Map::new();
             for header in req.headers.iter() {
-                headers.insert(header.name().to_owned(), header.to_string().into());
+                headers.insert(header.name().to_owned(), header.value_string().into());
             }
 
             let entry = json!({
@@ -91,13 +96,8 @@ impl From<ResponseWrapper> for BatchResponseWrapper {
                 // XXX: Unwrap headers
                 headers: Headers::new(),
                 body: resp.get("body").unwrap().clone(),
-                // XXX: Avoid version hardcodes
-                path: resp.get("path")
-                    .unwrap()
-                    .as_str()
-                    .unwrap()
-                    .replace("/v1/", "/")
-                    .to_owned(),
+                path: batch_wrapper.client
+                    .endpoint_path(resp.get("path").unwrap().as_str().unwrap()),
                 status: StatusCode::Unregistered(resp.get("status")
                                                      .unwrap()
                                                      .as_u64()
@@ -117,13 +117,34 @@ impl From<ResponseWrapper> for BatchResponseWrapper {
 }
 
 
+/// Send the requests in as many batches as needed.
+///
+/// The responses are returned in the order of the requests.
+pub fn send_batches<T>(client: &KintoClient,
+                       requests: Vec<T>)
+                       -> Result<Vec<ResponseWrapper>, KintoError>
+    where T: KintoRequest
+{
+    let mut responses = vec![];
+    for chunk in requests.chunks(BATCH_MAX_REQUESTS) {
+        let mut batch = BatchRequest::new(client.clone());
+        for request in chunk {
+            batch.add_request(request.clone());
+        }
+        let wrapper: BatchResponseWrapper = try!(batch.send()).into();
+        responses.extend(wrapper.responses);
+    }
+    Ok(responses)
+}
+
+
 #[cfg(test)]
 mod test_record {
 
     use hyper::status::StatusCode;
     use hyper::method::Method;
 
-    use batch::{BatchRequest, BatchResponseWrapper};
+    use batch::{BatchRequest, BatchResponseWrapper, BATCH_MAX_REQUESTS, send_batches};
     use request::KintoRequest;
     use resource::Resource;
     use utils::tests::{setup_client, setup_bucket};
@@ -154,4 +175,17 @@ mod test_record {
         assert_eq!(result.responses[0].status, StatusCode::Created);
         assert_eq!(result.responses[1].status, StatusCode::Ok);
     }
+
+    #[test]
+    fn test_send_batches_in_chunks() {
+        let client = setup_client();
+        let requests: Vec<_> = (0..BATCH_MAX_REQUESTS + 5)
+            .map(|i| client.bucket(&format!("b{}", i)).update_request().unwrap())
+            .collect();
+        let responses = send_batches(&client, requests).unwrap();
+        assert_eq!(responses.len(), BATCH_MAX_REQUESTS + 5);
+        assert_eq!(responses[BATCH_MAX_REQUESTS].path,
+                   format!("/buckets/b{}", BATCH_MAX_REQUESTS));
+        assert_eq!(client.list_buckets().unwrap().len(), BATCH_MAX_REQUESTS + 5);
+    }
 }
@@ -0,0 +1,124 @@
+use std::collections::HashMap;
+
+
+/// Options without value.
+const FLAGS: [&str; 2] = ["help", "overwrite"];
+
+
+/// Command line arguments: positionals and `--name value` options.
+#[derive(Debug, Clone, Default)]
+pub struct Args {
+    pub positionals: Vec<String>,
+    options: HashMap<String, Vec<String>>,
+}
+
+
+impl Args {
+    pub fn parse<I>(args: I) -> Result<Args, String>
+        where I: IntoIterator<Item = String>
+    {
+        let mut parsed = Args::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") || arg == "--" {
+                parsed.positionals.push(arg);
+                continue;
+            }
+
+            let option = arg[2..].to_owned();
+            let (name, value) = match option.find('=') {
+                Some(index) => {
+                    (option[..index].to_owned(), option[index + 1..].to_owned())
+                }
+                None if FLAGS.contains(&option.as_str()) => (option, String::new()),
+                None => {
+                    match args.next() {
+                        Some(value) => (option, value),
+                        None => return Err(format!("missing value for --{}", option)),
+                    }
+                }
+            };
+            parsed.options.entry(name).or_insert_with(Vec::new).push(value);
+        }
+        Ok(parsed)
+    }
+
+    /// Get a positional argument.
+    pub fn positional(&self, index: usize) -> Option<&str> {
+        self.positionals.get(index).map(String::as_str)
+    }
+
+    /// Whether an option was given.
+    pub fn has(&self, name: &str) -> bool {
+        self.options.contains_key(name)
+    }
+
+    /// Get the last value of an option.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        match self.options.get(name) {
+            Some(values) => values.last().map(String::as_str),
+            None => None,
+        }
+    }
+
+    /// Get the value of a mandatory option.
+    pub fn required(&self, name: &str) -> Result<&str, String> {
+        self.get(name).ok_or_else(|| format!("missing --{}", name))
+    }
+
+    /// Get all the values of a repeated option.
+    pub fn all(&self, name: &str) -> Vec<&str> {
+        match self.options.get(name) {
+            Some(values) => values.iter().map(String::as_str).collect(),
+            None => vec![],
+        }
+    }
+}
+
+
+/// Split a `name=value` argument.
+pub fn split_pair(pair: &str) -> Result<(&str, &str), String> {
+    match pair.find('=') {
+        Some(index) => Ok((&pair[..index], &pair[index + 1..])),
+        None => Err(format!("expected name=value, got {}", pair)),
+    }
+}
+
+
+#[cfg(test)]
+mod test_args {
+    use super::{Args, split_pair};
+
+    fn parse(args: &[&str]) -> Args {
+        Args::parse(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let args = parse(&["records",
+                           "list",
+                           "--bucket",
+                           "food",
+                           "--filter=min_price=2",
+                           "--filter",
+                           "kind=meat",
+                           "--overwrite"]);
+        assert_eq!(args.positionals, vec!["records", "list"]);
+        assert_eq!(args.get("bucket"), Some("food"));
+        assert_eq!(args.all("filter"), vec!["min_price=2", "kind=meat"]);
+        assert!(args.has("overwrite"));
+        assert!(args.required("collection").is_err());
+    }
+
+    #[test]
+    fn test_missing_option_value() {
+        Args::parse(vec!["--bucket".to_owned()]).unwrap_err();
+    }
+
+    #[test]
+    fn test_split_pair() {
+        assert_eq!(split_pair("a=b=c").unwrap(), ("a", "b=c"));
+        split_pair("a").unwrap_err();
+    }
+}
@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use hyper::header::{Authorization, Basic};
+use kinto_http::KintoClient;
+
+use args::Args;
+
+
+/// Server used when none is configured.
+pub const DEFAULT_SERVER: &str = "http://localhost:8888/v1";
+
+
+/// Server and authentication of the client.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub server: String,
+    /// Basic auth credentials (`user:password`).
+    pub auth: Option<String>,
+}
+
+
+impl Config {
+    /// Resolve the configuration from the flags, then the environment,
+    /// then the profile file.
+    pub fn resolve<E>(args: &Args,
+                      env: E,
+                      profiles: Option<&str>)
+                      -> Result<Config, String>
+        where E: Fn(&str) -> Option<String>
+    {
+        let name = args.get("profile")
+            .map(str::to_owned)
+            .or_else(|| env("KINTO_PROFILE"))
+            .unwrap_or_else(|| "default".to_owned());
+        let profiles = parse_profiles(profiles.unwrap_or(""));
+        let profile = match profiles.get(&name) {
+            Some(profile) => profile.clone(),
+            None if args.has("profile") => {
+                return Err(format!("unknown profile {}", name))
+            }
+            None => HashMap::new(),
+        };
+
+        let setting = |flag: &str, var: &str| -> Option<String> {
+            args.get(flag)
+                .map(str::to_owned)
+                .or_else(|| env(var))
+                .or_else(|| profile.get(flag).cloned())
+        };
+
+        Ok(Config {
+               server: setting("server", "KINTO_SERVER")
+                   .unwrap_or_else(|| DEFAULT_SERVER.to_owned()),
+               auth: setting("auth", "KINTO_AUTH"),
+           })
+    }
+
+    pub fn client(&self) -> Result<KintoClient, String> {
+        let auth = self.auth.as_ref().map(|credentials| {
+            let mut parts = credentials.splitn(2, ':');
+            Authorization(Basic {
+                              username: parts.next().unwrap_or("").to_owned(),
+                              password: parts.next().map(str::to_owned),
+                          })
+        });
+        KintoClient::new(self.server.clone(), auth)
+            .map_err(|_| format!("invalid server URL {}", self.server))
+    }
+}
+
+
+/// Parse an INI profiles file:
+///
+/// ```ini
+/// [default]
+/// server = https://kinto.example.com/v1
+/// auth = user:password
+/// ```
+pub fn parse_profiles(content: &str) -> HashMap<String, HashMap<String, String>> {
+    let mut profiles = HashMap::new();
+    let mut current: Option<String> = None;
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = line[1..line.len() - 1].trim().to_owned();
+            profiles.entry(name.clone()).or_insert_with(HashMap::new);
+            current = Some(name);
+            continue;
+        }
+        match (current.as_ref(), line.find('=')) {
+            (Some(name), Some(index)) => {
+                let profile = profiles.entry(name.clone()).or_insert_with(HashMap::new);
+                profile.insert(line[..index].trim().to_owned(),
+                               line[index + 1..].trim().to_owned());
+            }
+            _ => (),
+        };
+    }
+    profiles
+}
+
+
+#[cfg(test)]
+mod test_config {
+    use args::Args;
+    use super::{Config, DEFAULT_SERVER, parse_profiles};
+
+    const PROFILES: &str = "
+        # Kinto servers
+        [default]
+        server = http://localhost:8888/v1
+        auth = alice:secret
+
+        [prod]
+        server = https://kinto.example.com/v1
+    ";
+
+    fn args(args: &[&str]) -> Args {
+        Args::parse(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn test_parse_profiles() {
+        let profiles = parse_profiles(PROFILES);
+        assert_eq!(profiles["default"]["auth"], "alice:secret");
+        assert_eq!(profiles["prod"]["server"], "https://kinto.example.com/v1");
+    }
+
+    #[test]
+    fn test_resolve_precedence() {
+        let no_env = |_: &str| None;
+        let config = Config::resolve(&args(&[]), no_env, None).unwrap();
+        assert_eq!(config.server, DEFAULT_SERVER);
+        assert_eq!(config.auth, None);
+
+        let args_prod = args(&["--profile", "prod"]);
+        let config = Config::resolve(&args_prod, no_env, Some(PROFILES)).unwrap();
+        assert_eq!(config.server, "https://kinto.example.com/v1");
+
+        let env = |var: &str| match var {
+            "KINTO_SERVER" => Some("http://env:8888".to_owned()),
+            _ => None,
+        };
+        let config = Config::resolve(&args(&[]), env, Some(PROFILES)).unwrap();
+        assert_eq!(config.server, "http://env:8888");
+        assert_eq!(config.auth, Some("alice:secret".to_owned()));
+
+        let args_flag = args(&["--server", "http://flag"]);
+        let config = Config::resolve(&args_flag, env, Some(PROFILES)).unwrap();
+        assert_eq!(config.server, "http://flag");
+    }
+
+    #[test]
+    fn test_unknown_profile() {
+        Config::resolve(&args(&["--profile", "staging"]), |_| None, Some(PROFILES))
+            .unwrap_err();
+    }
+}
@@ -0,0 +1,586 @@
+//! Command line client for Kinto servers.
+
+extern crate hyper;
+extern crate kinto_http;
+#[macro_use]
+extern crate serde_json;
+
+mod args;
+mod config;
+mod output;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process;
+use std::str::FromStr;
+
+use hyper::method::Method;
+use serde_json::Value;
+
+use kinto_http::{KintoClient, KintoError, Resource};
+use kinto_http::archive::{ArchiveFormat, ImportOptions, OnExisting};
+use kinto_http::batch::send_batches;
+use kinto_http::request::{KintoRequest, PayloadedEndpoint, PluralEndpoint,
+                          RequestPreparer, GetRecord, PatchRecord};
+
+use args::{Args, split_pair};
+use config::Config;
+use output::{Format, render};
+
+
+const USAGE: &str = "Usage: kinto [OPTIONS] COMMAND [ARGS]
+
+Commands:
+    buckets     list|get|put|patch|delete [ID]
+    collections list|get|put|patch|delete [ID] --bucket BUCKET
+    groups      list|get|put|patch|delete [ID] --bucket BUCKET
+    records     list|get|put|patch|delete [ID] --bucket BUCKET --collection COLLECTION
+    permissions get|grant|revoke URI [PERMISSION PRINCIPAL]
+    batch       FILE                  Send the requests of a JSON file
+    export      --bucket BUCKET       Export a bucket archive
+    import                            Import a bucket archive
+    info                              Show the server information
+
+Options:
+    --server URL         Server URL (env KINTO_SERVER)
+    --auth USER:PASS     Basic auth credentials (env KINTO_AUTH)
+    --profile NAME       Profile of the configuration file (env KINTO_PROFILE)
+    --config FILE        Profiles file (env KINTO_CONFIG, default ~/.kinto.ini)
+    --output FORMAT      json, ndjson or table (default json)
+    --data JSON          Data of put and patch
+    --permissions JSON   Permissions of put and patch
+    --filter FIELD=VALUE Filter listed objects (repeatable)
+    --sort FIELDS        Sort listed objects (e.g. -last_modified)
+    --since TIMESTAMP    Only list objects changed since the timestamp
+    --limit N            Number of objects per page
+    --file FILE          Archive file of export and import (default stdin/stdout)
+    --format FORMAT      Archive format of export: json or ndjson
+    --bucket-id ID       Import the archive under another bucket id
+    --remap FROM=TO      Replace a principal on import (repeatable)
+    --overwrite          Overwrite the existing objects on import";
+
+
+fn main() {
+    let args = match Args::parse(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(error) => exit(error),
+    };
+    if args.has("help") || args.positionals.is_empty() {
+        println!("{}", USAGE);
+        return;
+    }
+    match run(&args) {
+        Ok(()) => (),
+        Err(error) => exit(error),
+    };
+}
+
+
+fn exit(error: String) -> ! {
+    let _ = writeln!(io::stderr(), "kinto: {}", error);
+    process::exit(1);
+}
+
+
+fn run(args: &Args) -> Result<(), String> {
+    let profiles = try!(read_profiles(args));
+    let env = |var: &str| env::var(var).ok();
+    let config = try!(Config::resolve(args, env, profiles.as_deref()));
+    let client = try!(config.client());
+    let format = try!(Format::parse(args.get("output").unwrap_or("json")));
+
+    let command = args.positional(0).unwrap_or("");
+    let result = match command {
+        "buckets" | "collections" | "groups" | "records" => try!(objects(&client, args)),
+        "permissions" => try!(permissions(&client, args)),
+        "batch" => try!(batch(&client, args)),
+        "export" => return export(&client, args),
+        "import" => try!(import(&client, args)),
+        "info" => try!(client.server_info().map_err(error)),
+        _ => return Err(format!("unknown command {}\n\n{}", command, USAGE)),
+    };
+    println!("{}", render(&result, format));
+    Ok(())
+}
+
+
+/// Describe a failed request, with the HTTP status when the server answered.
+fn error(error: KintoError) -> String {
+    match error {
+        KintoError::HttpError(status, body) => {
+            let reason = body["error"].as_str().unwrap_or("error");
+            match body["message"].as_str() {
+                Some(message) => {
+                    format!("request failed: HTTP {} {}: {}", status, reason, message)
+                }
+                None => format!("request failed: HTTP {} {}", status, reason),
+            }
+        }
+        KintoError::NotModified => "request failed: HTTP 304 Not Modified".to_owned(),
+        KintoError::PreconditionError => {
+            "request failed: HTTP 412 Precondition Failed: the object exists or changed"
+                .to_owned()
+        }
+        KintoError::QuotaExceeded => {
+            "request failed: HTTP 507 Insufficient Storage: quota exceeded".to_owned()
+        }
+        KintoError::ValidationError(errors) => {
+            let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            format!("invalid data: {}", errors.join(", "))
+        }
+        KintoError::ArchiveVersionError(version) => {
+            format!("unsupported archive version {}", version)
+        }
+        KintoError::InvalidIdError(id) => format!("invalid id {}", id),
+        KintoError::InvalidUriError(uri) => format!("invalid URI {}", uri),
+        KintoError::UndefinedIdError => "missing id".to_owned(),
+        KintoError::UrlError => "invalid server URL".to_owned(),
+        KintoError::UnavailableEndpointError => {
+            "the server does not support this command".to_owned()
+        }
+        KintoError::HyperError => "cannot reach the server".to_owned(),
+        KintoError::JsonError => "invalid JSON response".to_owned(),
+        KintoError::IOError => "cannot read or write the data".to_owned(),
+        error => format!("request failed: {:?}", error),
+    }
+}
+
+
+/// Read the profiles file, if any.
+fn read_profiles(args: &Args) -> Result<Option<String>, String> {
+    let path = args.get("config")
+        .map(str::to_owned)
+        .or_else(|| env::var("KINTO_CONFIG").ok());
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            match env::var("HOME") {
+                Ok(home) => PathBuf::from(home).join(".kinto.ini"),
+                Err(_) => return Ok(None),
+            }
+        }
+    };
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut content = String::new();
+    try!(File::open(&path)
+             .and_then(|mut file| file.read_to_string(&mut content))
+             .map_err(|e| format!("cannot read {}: {}", path.display(), e)));
+    Ok(Some(content))
+}
+
+
+/// Parse a JSON option.
+fn json_option(args: &Args, name: &str) -> Result<Option<Value>, String> {
+    match args.get(name) {
+        Some(json) => {
+            serde_json::from_str(json)
+                .map(Some)
+                .map_err(|_| format!("invalid JSON in --{}", name))
+        }
+        None => Ok(None),
+    }
+}
+
+
+/// Run an action on buckets, collections, groups or records.
+fn objects(client: &KintoClient, args: &Args) -> Result<Value, String> {
+    let kind = args.positional(0).unwrap_or("");
+    let action = args.positional(1).unwrap_or("list");
+    let id = args.positional(2);
+
+    match kind {
+        "buckets" => {
+            match id {
+                Some(id) => object_action(client.bucket(id), action, true, args),
+                None => object_action(client.new_bucket(), action, false, args),
+            }
+        }
+        "collections" | "groups" => {
+            let bucket = client.bucket(try!(args.required("bucket")));
+            match (kind, id) {
+                ("collections", Some(id)) => {
+                    object_action(bucket.collection(id), action, true, args)
+                }
+                ("collections", None) => {
+                    object_action(bucket.new_collection(), action, false, args)
+                }
+                (_, Some(id)) => object_action(bucket.group(id), action, true, args),
+                (_, None) => object_action(bucket.new_group(), action, false, args),
+            }
+        }
+        _ => {
+            let bucket = client.bucket(try!(args.required("bucket")));
+            let collection = bucket.collection(try!(args.required("collection")));
+            match id {
+                Some(id) => object_action(collection.record(id), action, true, args),
+                None => object_action(collection.new_record(), action, false, args),
+            }
+        }
+    }
+}
+
+
+fn object_action<T>(object: T,
+                    action: &str,
+                    has_id: bool,
+                    args: &Args)
+                    -> Result<Value, String>
+    where T: Resource
+{
+    let mut body = json!({});
+    match try!(json_option(args, "data")) {
+        Some(data) => body["data"] = data,
+        None => (),
+    };
+    match try!(json_option(args, "permissions")) {
+        Some(permissions) => body["permissions"] = permissions,
+        None => (),
+    };
+
+    let response = match (action, has_id) {
+        ("list", true) => return Err("list takes no id".to_owned()),
+        ("list", false) => {
+            let mut request = try!(object.list_request().map_err(error));
+            for filter in args.all("filter") {
+                let (field, value) = try!(split_pair(filter));
+                request.filter(field, value);
+            }
+            match args.get("sort") {
+                Some(sort) => {
+                    request.sort(sort);
+                }
+                None => (),
+            };
+            match args.get("since") {
+                Some(since) => {
+                    let since = try!(since.parse()
+                                      .map_err(|_| "invalid --since".to_owned()));
+                    request.since(since);
+                }
+                None => (),
+            };
+            match args.get("limit") {
+                Some(limit) => {
+                    let limit = try!(limit.parse()
+                                      .map_err(|_| "invalid --limit".to_owned()));
+                    request.limit(limit);
+                }
+                None => (),
+            };
+            let response = try!(request.follow_subrequests().map_err(error));
+            return Ok(response.body["data"].to_owned());
+        }
+        ("get", true) => try!(object.load_request().map_err(error)).send(),
+        ("put", true) => {
+            try!(object.update_request().map_err(error))
+                .body(Some(body))
+                .send()
+        }
+        ("put", false) => {
+            try!(object.create_request().map_err(error))
+                .body(Some(body))
+                .send()
+        }
+        ("patch", true) => {
+            try!(object.patch_request().map_err(error))
+                .body(Some(body))
+                .send()
+        }
+        ("delete", true) => try!(object.delete_request().map_err(error)).send(),
+        ("get", false) | ("patch", false) | ("delete", false) => {
+            return Err(format!("{} needs an id", action))
+        }
+        _ => return Err(format!("unknown action {}", action)),
+    };
+    Ok(try!(response.map_err(error)).body)
+}
+
+
+/// Show or edit the permissions of an object.
+fn permissions(client: &KintoClient, args: &Args) -> Result<Value, String> {
+    let action = args.positional(1).unwrap_or("get");
+    let uri = try!(args.positional(2).ok_or("missing URI".to_owned()));
+    let uri = try!(client.resolve(uri).and_then(|object| object.uri()).map_err(error));
+
+    let response = try!(GetRecord::new(client.clone(), uri.clone())
+                            .send()
+                            .map_err(error));
+    let permissions = response.body["permissions"].to_owned();
+    if action == "get" {
+        return Ok(permissions);
+    }
+
+    let permission = try!(args.positional(3).ok_or("missing permission".to_owned()));
+    let principal = try!(args.positional(4).ok_or("missing principal".to_owned()));
+    let mut principals: Vec<String> =
+        serde_json::from_value(permissions[permission].to_owned()).unwrap_or_default();
+    match action {
+        "grant" => {
+            if !principals.iter().any(|p| p == principal) {
+                principals.push(principal.to_owned());
+            }
+        }
+        "revoke" => principals.retain(|p| p != principal),
+        _ => return Err(format!("unknown action {}", action)),
+    };
+
+    let mut body = json!({"permissions": {}});
+    body["permissions"][permission] = principals.into();
+    let response = try!(PatchRecord::new(client.clone(), uri)
+                            .body(Some(body))
+                            .send()
+                            .map_err(error));
+    Ok(response.body["permissions"].to_owned())
+}
+
+
+/// Send the requests of a file (`{"requests": [...]}` or a list of requests).
+fn batch(client: &KintoClient, args: &Args) -> Result<Value, String> {
+    let path = try!(args.positional(1).ok_or("missing batch file".to_owned()));
+    let file = try!(File::open(path).map_err(|e| format!("cannot read {}: {}", path, e)));
+    let content: Value = try!(serde_json::from_reader(file)
+                                  .map_err(|_| format!("invalid JSON in {}", path)));
+    let entries = match content.get("requests") {
+        Some(requests) => requests.as_array().cloned().unwrap_or_default(),
+        None => content.as_array().cloned().unwrap_or_default(),
+    };
+
+    let mut requests = vec![];
+    for entry in entries {
+        let path = try!(entry["path"].as_str().ok_or("missing request path".to_owned()));
+        let path = client.endpoint_path(path);
+        let mut request = RequestPreparer::new(client.clone(), path);
+        request.method = try!(Method::from_str(entry["method"].as_str().unwrap_or("GET"))
+                                  .map_err(|_| "invalid request method".to_owned()));
+        match entry["headers"].as_object() {
+            Some(headers) => {
+                for (name, value) in headers {
+                    let value = value.as_str().unwrap_or("").as_bytes().to_vec();
+                    request.headers.set_raw(name.to_owned(), vec![value]);
+                }
+            }
+            None => (),
+        };
+        if !entry["body"].is_null() {
+            request.body = Some(entry["body"].to_owned());
+        }
+        requests.push(request);
+    }
+
+    let responses = try!(send_batches(client, requests).map_err(error));
+    Ok(responses.into_iter()
+           .map(|response| {
+                    json!({
+                "status": response.status.to_u16(),
+                "path": response.path,
+                "body": response.body,
+            })
+                })
+           .collect::<Vec<_>>()
+           .into())
+}
+
+
+fn export(client: &KintoClient, args: &Args) -> Result<(), String> {
+    let bucket = client.bucket(try!(args.required("bucket")));
+    let format = match args.get("format").unwrap_or("json") {
+        "json" => ArchiveFormat::Json,
+        "ndjson" => ArchiveFormat::Ndjson,
+        format => return Err(format!("unknown archive format {}", format)),
+    };
+    let result = match args.get("file") {
+        Some(path) => {
+            let created = File::create(path);
+            let mut file =
+                try!(created.map_err(|e| format!("cannot write {}: {}", path, e)));
+            bucket.export(&mut file, format)
+        }
+        None => bucket.export(&mut io::stdout(), format),
+    };
+    result.map_err(error)
+}
+
+
+fn import(client: &KintoClient, args: &Args) -> Result<Value, String> {
+    let mut options = ImportOptions::new();
+    match args.get("bucket-id") {
+        Some(id) => {
+            options.bucket_id(id);
+        }
+        None => (),
+    };
+    for remap in args.all("remap") {
+        let (from, to) = try!(split_pair(remap));
+        options.remap(from, to);
+    }
+    if args.has("overwrite") {
+        options.on_existing(OnExisting::Overwrite);
+    }
+
+    let result = match args.get("file") {
+        Some(path) => {
+            let file = try!(File::open(path)
+                                .map_err(|e| format!("cannot read {}: {}", path, e)));
+            client.import(file, &options)
+        }
+        None => client.import(io::stdin(), &options),
+    };
+    let result = try!(result.map_err(error));
+    let errors: Vec<Value> = result.errors
+        .iter()
+        .map(|e| json!({"uri": e.uri, "status": e.status, "details": e.details}))
+        .collect();
+    Ok(json!({
+        "imported": result.imported,
+        "skipped": result.skipped,
+        "errors": errors,
+    }))
+}
+
+
+#[cfg(test)]
+mod test_main {
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+
+    use hyper::header::{Authorization, Basic};
+    use kinto_http::{KintoClient, KintoError};
+    use kinto_http::utils::uuid4;
+
+    use args::Args;
+    use super::{batch, error, export, import, objects, permissions};
+
+    fn setup_client() -> KintoClient {
+        let auth = Authorization(Basic {
+                                     username: "a".to_owned(),
+                                     password: Some("a".to_owned()),
+                                 });
+        let client = KintoClient::new("http://localhost:8888/v1".to_owned(), auth.into())
+            .unwrap();
+        client.flush().unwrap();
+        client
+    }
+
+    fn parse(args: &[&str]) -> Args {
+        Args::parse(args.iter().map(|arg| arg.to_string())).unwrap()
+    }
+
+    #[test]
+    fn test_error_messages() {
+        let body = json!({"code": 404, "error": "Not Found", "message": "bucket missing"});
+        assert_eq!(error(KintoError::HttpError(404, body)),
+                   "request failed: HTTP 404 Not Found: bucket missing");
+        assert_eq!(error(KintoError::HttpError(503, json!(null))),
+                   "request failed: HTTP 503 error");
+        assert!(error(KintoError::PreconditionError).contains("HTTP 412"));
+    }
+
+    #[test]
+    fn test_objects() {
+        let client = setup_client();
+        objects(&client, &parse(&["buckets", "put", "food"])).unwrap();
+        objects(&client,
+                &parse(&["collections", "put", "meat", "--bucket", "food"]))
+            .unwrap();
+        for (id, kind) in &[("entrecote", "beef"), ("gigot", "lamb")] {
+            let data = format!(r#"{{"kind": "{}"}}"#, kind);
+            objects(&client,
+                    &parse(&["records", "put", id, "--bucket", "food", "--collection",
+                             "meat", "--data", &data]))
+                .unwrap();
+        }
+
+        let listed = objects(&client,
+                             &parse(&["records", "list", "--bucket", "food",
+                                      "--collection", "meat", "--filter", "kind=lamb"]))
+            .unwrap();
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+        assert_eq!(listed[0]["id"], "gigot");
+
+        let patched = objects(&client,
+                              &parse(&["records", "patch", "gigot", "--bucket", "food",
+                                       "--collection", "meat", "--data",
+                                       r#"{"cut": "leg"}"#]))
+            .unwrap();
+        assert_eq!(patched["data"]["cut"], "leg");
+
+        objects(&client,
+                &parse(&["records", "delete", "gigot", "--bucket", "food",
+                         "--collection", "meat"]))
+            .unwrap();
+        let missing = objects(&client,
+                              &parse(&["records", "get", "gigot", "--bucket", "food",
+                                       "--collection", "meat"]))
+            .unwrap_err();
+        assert!(missing.starts_with("request failed: HTTP 404"), "{}", missing);
+    }
+
+    #[test]
+    fn test_list_takes_no_id() {
+        let client = setup_client();
+        let args = parse(&["records", "list", "entrecote", "--bucket", "food",
+                           "--collection", "meat"]);
+        assert_eq!(objects(&client, &args).unwrap_err(), "list takes no id");
+    }
+
+    #[test]
+    fn test_permissions() {
+        let client = setup_client();
+        objects(&client, &parse(&["buckets", "put", "food"])).unwrap();
+
+        let granted = permissions(&client,
+                                  &parse(&["permissions", "grant", "/buckets/food", "read",
+                                           "system.Everyone"]))
+            .unwrap();
+        assert_eq!(granted["read"], json!(["system.Everyone"]));
+        let revoked = permissions(&client,
+                                  &parse(&["permissions", "revoke", "/buckets/food",
+                                           "read", "system.Everyone"]))
+            .unwrap();
+        assert_eq!(revoked["read"].as_array().map(Vec::len).unwrap_or(0), 0);
+    }
+
+    #[test]
+    fn test_batch() {
+        let client = setup_client();
+        let path = env::temp_dir().join(format!("kinto-{}.json", uuid4()));
+        let requests = json!({"requests": [
+            {"method": "PUT", "path": "/buckets/food"},
+            {"method": "GET", "path": "/buckets/food/collections/missing"}
+        ]});
+        write!(fs::File::create(&path).unwrap(), "{}", requests).unwrap();
+
+        let responses = batch(&client, &parse(&["batch", path.to_str().unwrap()])).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(responses[0]["path"], "/buckets/food");
+        assert!(responses[0]["status"].as_u64().unwrap() < 300);
+        assert_eq!(responses[1]["status"], 404);
+    }
+
+    #[test]
+    fn test_export_and_import() {
+        let client = setup_client();
+        objects(&client, &parse(&["buckets", "put", "food"])).unwrap();
+        objects(&client,
+                &parse(&["collections", "put", "meat", "--bucket", "food"]))
+            .unwrap();
+
+        let path = env::temp_dir().join(format!("kinto-{}.json", uuid4()));
+        let path = path.to_str().unwrap();
+        export(&client, &parse(&["export", "--bucket", "food", "--file", path])).unwrap();
+        let result = import(&client,
+                            &parse(&["import", "--bucket-id", "groceries", "--file",
+                                     path]))
+            .unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(result["errors"], json!([]));
+        objects(&client,
+                &parse(&["collections", "get", "meat", "--bucket", "groceries"]))
+            .unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+use serde_json;
+use serde_json::Value;
+
+
+/// Longest cell of a table.
+const MAX_CELL_WIDTH: usize = 40;
+
+
+/// Rendering of the command results.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Ndjson,
+    Table,
+}
+
+
+impl Format {
+    pub fn parse(format: &str) -> Result<Format, String> {
+        match format {
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            "table" => Ok(Format::Table),
+            _ => Err(format!("unknown output format {}", format)),
+        }
+    }
+}
+
+
+pub fn render(value: &Value, format: Format) -> String {
+    match format {
+        Format::Json => serde_json::to_string_pretty(value).unwrap_or_default(),
+        Format::Ndjson => {
+            match value.as_array() {
+                Some(items) => {
+                    items.iter()
+                        .map(|item| serde_json::to_string(item).unwrap_or_default())
+                        .collect::<Vec<_>>()
+                        .join("\n")
+                }
+                None => serde_json::to_string(value).unwrap_or_default(),
+            }
+        }
+        Format::Table => {
+            match *value {
+                Value::Array(ref rows) => table(rows),
+                // Single objects are shown as a field / value table
+                Value::Object(ref map) => {
+                    let object = map.get("data")
+                        .and_then(Value::as_object)
+                        .unwrap_or(map);
+                    let rows: Vec<Value> = object.iter()
+                        .map(|(field, value)| json!({"field": field, "value": value}))
+                        .collect();
+                    table(&rows)
+                }
+                _ => cell(value),
+            }
+        }
+    }
+}
+
+
+/// Render objects as an aligned table, with the `id` column first.
+fn table(rows: &[Value]) -> String {
+    let mut columns: Vec<String> = vec![];
+    for row in rows {
+        match row.as_object() {
+            Some(object) => {
+                for field in object.keys() {
+                    if !columns.contains(field) {
+                        columns.push(field.to_owned());
+                    }
+                }
+            }
+            None => (),
+        };
+    }
+    match columns.iter().position(|column| column == "id") {
+        Some(index) => {
+            let id = columns.remove(index);
+            columns.insert(0, id);
+        }
+        None => (),
+    };
+
+    let cells: Vec<Vec<String>> = rows.iter()
+        .map(|row| columns.iter().map(|column| cell(&row[column.as_str()])).collect())
+        .collect();
+    let widths: Vec<usize> = columns.iter()
+        .enumerate()
+        .map(|(index, column)| {
+                 cells.iter()
+                     .map(|row| row[index].chars().count())
+                     .chain(Some(column.len()))
+                     .max()
+                     .unwrap_or(0)
+             })
+        .collect();
+
+    let line = |row: &[String]| -> String {
+        row.iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:1$}", cell, width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_owned()
+    };
+
+    let mut lines = vec![line(&columns)];
+    for row in &cells {
+        lines.push(line(row));
+    }
+    lines.join("\n")
+}
+
+
+fn cell(value: &Value) -> String {
+    let text = match *value {
+        Value::Null => String::new(),
+        Value::String(ref text) => text.to_owned(),
+        _ => serde_json::to_string(value).unwrap_or_default(),
+    };
+    if text.chars().count() > MAX_CELL_WIDTH {
+        let truncated: String = text.chars().take(MAX_CELL_WIDTH - 3).collect();
+        format!("{}...", truncated)
+    } else {
+        text
+    }
+}
+
+
+#[cfg(test)]
+mod test_output {
+    use super::{Format, render};
+
+    #[test]
+    fn test_render_ndjson() {
+        let value = json!([{"id": "a"}, {"id": "b"}]);
+        assert_eq!(render(&value, Format::Ndjson), "{\"id\":\"a\"}\n{\"id\":\"b\"}");
+    }
+
+    #[test]
+    fn test_render_table() {
+        let value = json!([{"title": "Onglet", "id": "onglet"},
+                           {"id": "entrecote", "tags": ["beef"]}]);
+        assert_eq!(render(&value, Format::Table),
+                   "id         title   tags\n\
+                    onglet     Onglet\n\
+                    entrecote          [\"beef\"]");
+    }
+
+    #[test]
+    fn test_render_object_table() {
+        let value = json!({"data": {"id": "food", "last_modified": 42},
+                           "permissions": {}});
+        assert_eq!(render(&value, Format::Table),
+                   "field          value\n\
+                    id             food\n\
+                    last_modified  42");
+    }
+}
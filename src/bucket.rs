@@ -1,3 +1,5 @@
+use std::io::Write;
+
 use serde_json;
 use serde_json::Value;
 
@@ -7,10 +9,22 @@ use request::KintoRequest;
 use response::ResponseWrapper;
 use resource::Resource;
 use collection::Collection;
+use group::Group;
+use history::{GetHistory, HistoryEntry};
+use paths::Paths;
+use permissions::{Permissions, BucketPermission};
+use metadata::BucketMetadata;
+use archive::{ArchiveFormat, ImportResult, export};
+use copy::{copy_bucket, rename_bucket};
+use quota::{QuotaInfo, fetch_quota};
 
 use utils::unwrap_collection_records;
 
 
+/// Alias of the personal bucket of the current user (default_bucket plugin).
+pub const DEFAULT_BUCKET: &str = "default";
+
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct BucketPermissions {
     #[serde(skip_serializing_if="Option::is_none")]
@@ -24,6 +38,29 @@ pub struct BucketPermissions {
 }
 
 
+impl Permissions for BucketPermissions {
+    type Permission = BucketPermission;
+
+    fn principals(&self, perm: BucketPermission) -> &Option<Vec<String>> {
+        match perm {
+            BucketPermission::Read => &self.read,
+            BucketPermission::Write => &self.write,
+            BucketPermission::CreateCollection => &self.create_collection,
+            BucketPermission::CreateGroup => &self.create_group,
+        }
+    }
+
+    fn principals_mut(&mut self, perm: BucketPermission) -> &mut Option<Vec<String>> {
+        match perm {
+            BucketPermission::Read => &mut self.read,
+            BucketPermission::Write => &mut self.write,
+            BucketPermission::CreateCollection => &mut self.create_collection,
+            BucketPermission::CreateGroup => &mut self.create_group,
+        }
+    }
+}
+
+
 #[derive(Debug, Clone, Default)]
 pub struct Bucket {
     pub data: Option<Value>,
@@ -53,6 +90,23 @@ impl Bucket {
         }
     }
 
+    /// Whether the bucket is the `default` alias of the user bucket.
+    pub fn is_default(&self) -> bool {
+        self.id.as_deref() == Some(DEFAULT_BUCKET)
+    }
+
+    /// Replace the `default` alias by the real id of the user bucket.
+    ///
+    /// Requests work with both ids, but the server always answers with the
+    /// real one. Only the alias creates the user bucket and its collections
+    /// implicitly, so children of a bucket keep the id they were given.
+    pub fn resolve_id(&mut self) -> Result<(), KintoError> {
+        if self.is_default() {
+            self.id = try!(self.client.default_bucket()).id;
+        }
+        Ok(())
+    }
+
     /// Get a collection by id.
     pub fn collection<'a>(self, id: &'a str) -> Collection {
         return Collection::new_by_id(self, id);
@@ -70,11 +124,87 @@ impl Bucket {
         return Ok(unwrap_collection_records(response, self.new_collection()));
     }
 
-    /// Delete all available collections.
+    /// Delete all available collections, see `DeletePlan` for a preview first.
     pub fn delete_collections(&self) -> Result<(), KintoError> {
         try!(try!(self.new_collection().delete_all_request()).follow_subrequests());
         Ok(())
     }
+
+    /// Get a group by id.
+    pub fn group(&self, id: &str) -> Group {
+        Group::new_by_id(self.clone(), id)
+    }
+
+    /// Get an empty group.
+    pub fn new_group(&self) -> Group {
+        Group::new(self.clone())
+    }
+
+    /// List all available groups.
+    pub fn list_groups(&self) -> Result<Vec<Group>, KintoError> {
+        let response = try!(try!(self.new_group().list_request()).follow_subrequests());
+        Ok(unwrap_collection_records(response, self.new_group()))
+    }
+
+    /// Create a custom request on the bucket history (history plugin).
+    pub fn history_request(&self) -> Result<GetHistory, KintoError> {
+        let id = try!(self.get_id().ok_or(KintoError::UndefinedIdError));
+        Ok(GetHistory::new(self.get_client(), Paths::History(id).into()))
+    }
+
+    /// Get the usage of the bucket (kinto-quotas plugin).
+    pub fn quota(&self) -> Result<QuotaInfo, KintoError> {
+        let id = try!(self.get_id().ok_or(KintoError::UndefinedIdError));
+        fetch_quota(&self.client, Paths::BucketQuota(id).into())
+    }
+
+    /// List all history entries of the bucket.
+    pub fn history(&self) -> Result<Vec<HistoryEntry>, KintoError> {
+        try!(self.history_request()).entries()
+    }
+
+    /// Export the bucket with its groups, collections and records.
+    pub fn export<W>(&self,
+                     writer: &mut W,
+                     format: ArchiveFormat)
+                     -> Result<(), KintoError>
+        where W: Write
+    {
+        export(self, writer, format)
+    }
+
+    /// Copy the bucket with its groups, collections and records.
+    pub fn copy_to(&self, id: &str) -> Result<ImportResult, KintoError> {
+        copy_bucket(self, id)
+    }
+
+    /// Copy the bucket to a new id, check the copy and delete this one.
+    pub fn rename(&self, id: &str) -> Result<Bucket, KintoError> {
+        rename_bucket(self, id)
+    }
+
+    /// Get the typed metadata of the (loaded) bucket.
+    pub fn metadata(&self) -> Result<BucketMetadata, KintoError> {
+        BucketMetadata::from_value(self.data.clone().unwrap_or(json!({})))
+    }
+
+    /// Replace the bucket data with the given metadata, saved by `set()`.
+    pub fn set_metadata(&mut self, metadata: BucketMetadata) -> Result<(), KintoError> {
+        self.data = Some(try!(metadata.to_value()));
+        Ok(())
+    }
+
+    /// Load the bucket and get its typed metadata.
+    pub fn load_metadata(&mut self) -> Result<BucketMetadata, KintoError> {
+        try!(self.load());
+        self.metadata()
+    }
+
+    /// Save the bucket with the given metadata.
+    pub fn save_metadata(&mut self, metadata: BucketMetadata) -> Result<(), KintoError> {
+        try!(self.set_metadata(metadata));
+        self.set()
+    }
 }
 
 
@@ -83,11 +213,12 @@ impl Resource for Bucket {
         Ok(format!("/buckets"))
     }
 
-    fn unwrap_response(&mut self, wrapper: ResponseWrapper) {
+    fn unwrap_response(&mut self, wrapper: ResponseWrapper) -> Result<(), KintoError> {
         self.data = Some(wrapper.body["data"].to_owned());
         self.permissions = serde_json::from_value(wrapper.body["permissions"].to_owned())
-            .unwrap();
+            .unwrap_or_default();
         self.id = Some(wrapper.body["data"]["id"].as_str().unwrap().to_owned());
+        Ok(())
     }
 
     fn get_client(&self) -> KintoClient {
@@ -141,6 +272,8 @@ mod test_bucket_resource {
     use utils::tests::{setup_client, setup_bucket};
     use resource::Resource;
     use bucket::BucketPermissions;
+    use error::KintoError;
+    use permissions::{Permissions, Principal, BucketPermission};
 
     #[test]
     fn test_set_bucket() {
@@ -159,6 +292,21 @@ mod test_bucket_resource {
         assert!(data["id"].as_str() != None);
     }
 
+    #[test]
+    fn test_invalid_bucket_id() {
+        let mut bucket = setup_client().bucket("food/meat");
+        match bucket.create() {
+            Err(KintoError::InvalidIdError(id)) => assert_eq!(id, "food/meat"),
+            _ => panic!("invalid id should be rejected"),
+        }
+
+        // Existing objects may have ids from a custom generator
+        match bucket.load() {
+            Err(KintoError::HttpError(..)) => (),
+            result => panic!("unexpected result {:?}", result),
+        }
+    }
+
     #[test]
     fn test_create_bucket() {
         let mut bucket = setup_bucket();
@@ -222,6 +370,19 @@ mod test_bucket_resource {
         assert_eq!(permissions.write.unwrap().len(), 2);
     }
 
+    #[test]
+    fn test_set_bucket_permissions_only() {
+        let mut bucket = setup_bucket();
+        bucket.data = json!({"good": true}).into();
+        bucket.set().unwrap();
+
+        bucket.permissions.grant(BucketPermission::Read, Principal::Everyone);
+        bucket.set_permissions().unwrap();
+
+        assert!(bucket.permissions.has(BucketPermission::Read, Principal::Everyone));
+        assert_eq!(bucket.data.unwrap()["good"].as_bool().unwrap(), true);
+    }
+
     #[test]
     fn test_load_bucket_fails_without_id() {
         let mut bucket = setup_bucket();
@@ -271,7 +432,7 @@ mod test_bucket_resource {
 
 #[cfg(test)]
 mod test_bucket_class {
-    use utils::tests::setup_bucket;
+    use utils::tests::{setup_client, setup_bucket};
     use resource::Resource;
 
     #[test]
@@ -289,4 +450,37 @@ mod test_bucket_class {
         assert_eq!(collection.data, None);
         assert_eq!(collection.get_id(), None);
     }
+
+    #[test]
+    fn test_resolve_default_bucket() {
+        let client = setup_client();
+        let mut bucket = client.bucket("default");
+        assert!(bucket.is_default());
+
+        let mut collection = bucket.clone().collection("notes");
+        collection.set().unwrap();
+
+        bucket.resolve_id().unwrap();
+        assert!(!bucket.is_default());
+        let collections = bucket.list_collections().unwrap();
+        assert_eq!(collections[0].get_id().unwrap(), "notes");
+    }
+
+    #[test]
+    fn test_default_bucket_children() {
+        let client = setup_client();
+        let real_id = client.default_bucket().unwrap().id;
+
+        // The alias creates the collection implicitly
+        let mut record = client.bucket("default").collection("notes").new_record();
+        record.set().unwrap();
+        assert_eq!(record.collection.bucket.get_id(), Some("default"));
+
+        let mut bucket = client.bucket("default");
+        bucket.resolve_id().unwrap();
+        assert_eq!(bucket.id, real_id);
+        let mut collection = bucket.collection("notes");
+        collection.load().unwrap();
+        assert_eq!(collection.list_records().unwrap().len(), 1);
+    }
 }
@@ -0,0 +1,122 @@
+use serde_json;
+use hyper::method::Method;
+
+use KintoClient;
+use error::KintoError;
+use request::{RequestPreparer, KintoRequest, PluralEndpoint};
+use response::ResponseWrapper;
+use collection::Collection;
+
+
+/// Entry of the monitor changes endpoint (kinto-changes plugin).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollectionChange {
+    pub id: String,
+    pub bucket: String,
+    pub collection: String,
+    pub last_modified: u64,
+    #[serde(default)]
+    pub host: String,
+}
+
+
+impl CollectionChange {
+    /// Get a handle on the changed collection.
+    pub fn collection(&self, client: &KintoClient) -> Collection {
+        client.bucket(self.bucket.as_str()).collection(self.collection.as_str())
+    }
+}
+
+
+/// Get request on the monitor changes endpoint.
+#[derive(Debug, Clone)]
+pub struct GetChanges {
+    pub preparer: RequestPreparer,
+}
+
+impl GetChanges {
+    pub fn new(client: KintoClient, path: String) -> GetChanges {
+        let mut preparer = RequestPreparer::new(client, path);
+        preparer.method = Method::Get;
+        GetChanges { preparer: preparer }
+    }
+
+    /// Set the expected timestamp, to bust intermediary caches.
+    pub fn expected(&mut self, timestamp: u64) -> &mut Self {
+        self.preparer.query = format!("{}&_expected={}", self.preparer.query, timestamp);
+        self
+    }
+
+    /// Fetch all pages of the matching entries.
+    pub fn entries(&mut self) -> Result<Vec<CollectionChange>, KintoError> {
+        let response = try!(self.follow_subrequests());
+        unwrap_changes(response)
+    }
+}
+
+impl KintoRequest for GetChanges {
+    fn preparer(&mut self) -> &mut RequestPreparer {
+        &mut self.preparer
+    }
+}
+
+impl PluralEndpoint for GetChanges {}
+
+
+/// Get the collection changes from a response.
+pub fn unwrap_changes(wrapper: ResponseWrapper)
+                      -> Result<Vec<CollectionChange>, KintoError> {
+    Ok(try!(serde_json::from_value(wrapper.body["data"].to_owned())))
+}
+
+
+#[cfg(test)]
+mod test_changes {
+    use serde_json;
+
+    use KintoClient;
+    use resource::Resource;
+    use super::CollectionChange;
+    use utils::tests::setup_collection;
+
+    #[test]
+    fn test_parse_change() {
+        let change: CollectionChange = serde_json::from_value(json!({
+            "id": "a3a8f2b0-7d61-4ab5-a8b9-e84d1d22a1ec",
+            "bucket": "food",
+            "collection": "meat",
+            "last_modified": 42,
+            "host": "localhost:8888"
+        }))
+                .unwrap();
+        assert_eq!(change.last_modified, 42);
+        assert_eq!(change.host, "localhost:8888");
+    }
+
+    #[test]
+    fn test_change_collection() {
+        let change = CollectionChange {
+            bucket: "food".to_owned(),
+            collection: "meat".to_owned(),
+            ..CollectionChange::default()
+        };
+        let collection = change.collection(&KintoClient::default());
+        assert_eq!(collection.get_id().unwrap(), "meat");
+        assert_eq!(collection.bucket.get_id().unwrap(), "food");
+    }
+
+    #[test]
+    fn test_collection_changes() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        collection.record("entrecote").create().unwrap();
+
+        let client = collection.get_client();
+        let changes = client.collection_changes(None).unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].collection, "meat");
+
+        let since = changes[0].last_modified;
+        assert_eq!(client.collection_changes(Some(since)).unwrap().len(), 0);
+    }
+}
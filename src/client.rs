@@ -1,40 +1,131 @@
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+use hyper::Url;
 use hyper::client;
+use hyper::client::pool::{Config, Pool};
 use hyper::header::{Headers, Authorization, Basic};
 use hyper::net::HttpsConnector;
 use hyper_native_tls::NativeTlsClient;
+use serde_json::Value;
 
 use error::KintoError;
-use resource::Resource;
-use request::KintoRequest;
-use bucket::Bucket;
+use resource::{AnyResource, Resource};
+use request::{KintoRequest, PluralEndpoint, GetRecord};
+use bucket::{Bucket, DEFAULT_BUCKET};
+use archive::{ImportOptions, ImportResult, import};
+use changes::{GetChanges, CollectionChange};
+use paths::Paths;
+
+use ids::validate_id;
+use utils::{extract_ids_from_path, is_api_version, unwrap_collection_records};
 
-use utils::unwrap_collection_records;
+
+/// Version of the HTTP API used when the server URL has none.
+const DEFAULT_API_VERSION: &str = "v1";
 
 
 /// Client for the Kinto HTTP API.
-#[derive(Debug)]
+///
+/// Clones share the same HTTP connections pool and configuration, so
+/// resources can hold their own client handle cheaply.
+#[derive(Debug, Clone)]
 pub struct KintoClient {
-    pub server_url: String,
-    pub http_client: client::Client,
-    pub auth: Option<Authorization<Basic>>,
+    inner: Arc<ClientInner>,
+}
+
+
+/// State shared by the clones of a client.
+#[derive(Debug)]
+struct ClientInner {
+    server_url: Url,
+    http_client: client::Client,
+    auth: Option<Authorization<Basic>>,
+    /// Real id of the user bucket, once resolved.
+    user_bucket: Mutex<Option<String>>,
+    /// Server capabilities, once fetched.
+    capabilities: Mutex<Option<Value>>,
 }
 
 
 impl KintoClient {
     /// Create a client.
-    pub fn new(server_url: String, auth: Option<Authorization<Basic>>) -> KintoClient {
+    ///
+    /// The API version prefix is added to the server URL if missing, so
+    /// `http://localhost:8888`, `http://localhost:8888/v1` and
+    /// `http://localhost:8888/v1/` are equivalent.
+    pub fn new(server_url: String,
+               auth: Option<Authorization<Basic>>)
+               -> Result<KintoClient, KintoError> {
+        let server_url = try!(parse_server_url(server_url.as_str()));
+        Ok(KintoClient::with_url(server_url, auth))
+    }
+
+    fn with_url(server_url: Url, auth: Option<Authorization<Basic>>) -> KintoClient {
 
         // Build an SSL connector
         let ssl = NativeTlsClient::new().unwrap();
         let connector = HttpsConnector::new(ssl);
 
-        // Build a HTTP Client with TLS support.
-        let client = client::Client::with_connector(connector);
+        // Build a HTTP Client with TLS support, keeping connections alive.
+        let pool = Pool::with_connector(Config::default(), connector);
+        let client = client::Client::with_connector(pool);
 
         KintoClient {
-            server_url: server_url,
-            http_client: client,
-            auth: auth,
+            inner: Arc::new(ClientInner {
+                                server_url: server_url,
+                                http_client: client,
+                                auth: auth,
+                                user_bucket: Mutex::new(None),
+                                capabilities: Mutex::new(None),
+                            }),
+        }
+    }
+
+    /// Get the server URL, including the API version prefix (e.g. `/v1`).
+    pub fn server_url(&self) -> &Url {
+        &self.inner.server_url
+    }
+
+    /// Get the HTTP client, shared by all clones of this client.
+    pub fn http_client(&self) -> &client::Client {
+        &self.inner.http_client
+    }
+
+    /// Get the authentication header sent with the requests.
+    pub fn auth(&self) -> Option<&Authorization<Basic>> {
+        self.inner.auth.as_ref()
+    }
+
+    /// Get the API version prefix of the server URL (e.g. `/v1`).
+    pub fn api_prefix(&self) -> &str {
+        self.server_url().path()
+    }
+
+    /// Get the full URL of an endpoint path (e.g. `/buckets`).
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.server_url().as_str(), path)
+    }
+
+    /// Get the endpoint path of a full URL or of a prefixed path, as
+    /// given by the server (e.g. in `Next-Page` headers or batch responses).
+    pub fn endpoint_path(&self, url: &str) -> String {
+        let (path, query) = match Url::parse(url) {
+            Ok(url) => (url.path().to_owned(), url.query().map(str::to_owned)),
+            Err(_) => {
+                let mut parts = url.splitn(2, '?');
+                (parts.next().unwrap_or("").to_owned(), parts.next().map(str::to_owned))
+            }
+        };
+        let prefix = self.api_prefix();
+        let path = if path == prefix || path.starts_with(&format!("{}/", prefix)) {
+            path[prefix.len()..].to_owned()
+        } else {
+            path
+        };
+        match query {
+            Some(query) => format!("{}?{}", path, query),
+            None => path,
         }
     }
 
@@ -45,6 +136,70 @@ impl KintoClient {
         Bucket::new_by_id(self.clone(), id)
     }
 
+    /// Get the personal bucket of the current user (default_bucket plugin).
+    ///
+    /// Unlike `bucket("default")`, the returned bucket has the real id, as
+    /// found in the server responses. The id is only fetched once per client,
+    /// when the bucket is also created if needed (like any access with the
+    /// alias does).
+    pub fn default_bucket(&self) -> Result<Bucket, KintoError> {
+        let cached = self.inner.user_bucket.lock().unwrap().clone();
+        let id = match cached {
+            Some(id) => id,
+            None => {
+                let info = try!(self.server_info());
+                let id = try!(info["user"]["bucket"]
+                                  .as_str()
+                                  .ok_or(KintoError::UnavailableEndpointError))
+                    .to_owned();
+                let path = Paths::Bucket(DEFAULT_BUCKET).into();
+                try!(GetRecord::new(self.clone(), path).send());
+                *self.inner.user_bucket.lock().unwrap() = Some(id.clone());
+                id
+            }
+        };
+        Ok(self.bucket(&id))
+    }
+
+    /// Get the object designated by a URI (e.g. `/buckets/food/groups/cooks`).
+    ///
+    /// Absolute URLs and version prefixes are accepted, and the paths of
+    /// plugins endpoints resolve to their object (e.g. the `/attachment` of
+    /// a record, or the `/history` of a bucket).
+    pub fn resolve(&self, uri: &str) -> Result<AnyResource, KintoError> {
+        let path = self.endpoint_path(uri);
+        let path = path.split('?').next().unwrap_or("").to_owned();
+        let ids = extract_ids_from_path(path);
+        let id = |name: &str| -> Result<Option<String>, KintoError> {
+            match ids.get(name) {
+                Some(Some(id)) => {
+                    try!(validate_id(id));
+                    Ok(Some(id.to_owned()))
+                }
+                _ => Ok(None),
+            }
+        };
+
+        let bucket = match try!(id("buckets")) {
+            Some(bucket_id) => self.bucket(&bucket_id),
+            None => return Err(KintoError::InvalidUriError(uri.to_owned())),
+        };
+        match (try!(id("collections")), try!(id("groups")), try!(id("records"))) {
+            (Some(collection_id), None, Some(record_id)) => {
+                let collection = bucket.collection(&collection_id);
+                Ok(AnyResource::Record(collection.record(&record_id)))
+            }
+            (Some(collection_id), None, None) => {
+                Ok(AnyResource::Collection(bucket.collection(&collection_id)))
+            }
+            (None, Some(group_id), None) => {
+                Ok(AnyResource::Group(bucket.group(&group_id)))
+            }
+            (None, None, None) => Ok(AnyResource::Bucket(bucket)),
+            _ => Err(KintoError::InvalidUriError(uri.to_owned())),
+        }
+    }
+
     /// Create a new empty bucket with a generated id.
     pub fn new_bucket(&self) -> Bucket {
         Bucket::new(self.clone())
@@ -56,24 +211,72 @@ impl KintoClient {
         return Ok(unwrap_collection_records(response, self.new_bucket()));
     }
 
-    /// Delete all available buckets.
+    /// Delete all available buckets, see `DeletePlan` for a preview first.
     pub fn delete_buckets(&self) -> Result<(), KintoError> {
         try!(try!(self.new_bucket().delete_all_request()).follow_subrequests());
         Ok(())
     }
 
+    /// Restore a bucket archive (see `Bucket::export()`).
+    pub fn import<R>(&self,
+                     reader: R,
+                     options: &ImportOptions)
+                     -> Result<ImportResult, KintoError>
+        where R: Read
+    {
+        import(self, reader, options)
+    }
+
+    /// Create a custom request on the monitor changes endpoint.
+    pub fn changes_request(&self) -> GetChanges {
+        GetChanges::new(self.clone(), Paths::Changes.into())
+    }
+
+    /// List the collections timestamps, changed since the given timestamp if any.
+    pub fn collection_changes(&self,
+                              since: Option<u64>)
+                              -> Result<Vec<CollectionChange>, KintoError> {
+        let mut request = self.changes_request();
+        match since {
+            Some(since) => {
+                request.since(since);
+            }
+            None => (),
+        };
+        request.entries()
+    }
+
+    /// Get the server information and capabilities.
+    pub fn server_info(&self) -> Result<Value, KintoError> {
+        let response = try!(GetRecord::new(self.clone(), "/".to_owned()).send());
+        Ok(response.body)
+    }
+
+    /// Get the server capabilities, only fetched once per client.
+    pub fn capabilities(&self) -> Result<Value, KintoError> {
+        let cached = self.inner.capabilities.lock().unwrap().clone();
+        match cached {
+            Some(capabilities) => Ok(capabilities),
+            None => {
+                let capabilities = try!(self.server_info())["capabilities"].clone();
+                *self.inner.capabilities.lock().unwrap() = Some(capabilities.clone());
+                Ok(capabilities)
+            }
+        }
+    }
+
     /// Flush the server (if the flush endpoint is enabled).
     pub fn flush(&self) -> Result<(), KintoError> {
-        let path = format!("{}/__flush__", self.server_url);
+        let path = self.url("/__flush__");
 
         // Set authentication headers
         let mut headers = Headers::new();
-        match self.auth.to_owned() {
+        match self.auth().cloned() {
             Some(method) => headers.set(method),
             None => (),
         };
 
-        try!(self.http_client
+        try!(self.http_client()
                  .post(path.as_str())
                  .headers(headers)
                  .send());
@@ -82,26 +285,47 @@ impl KintoClient {
 }
 
 
-impl Clone for KintoClient {
-    fn clone(&self) -> KintoClient {
-        let new_client = KintoClient::new(self.server_url.to_owned(),
-                                          self.auth.to_owned());
+impl Default for KintoClient {
+    fn default() -> KintoClient {
+        let server_url = Url::parse("http://localhost:8888/v1").unwrap();
+        let new_client = KintoClient::with_url(server_url, None);
         return new_client;
     }
 }
 
 
-impl Default for KintoClient {
-    fn default() -> KintoClient {
-        let new_client = KintoClient::new("".to_owned(), None);
-        return new_client;
+/// Parse the server URL and make sure it ends with the API version prefix.
+fn parse_server_url(server_url: &str) -> Result<Url, KintoError> {
+    let mut url = try!(Url::parse(server_url).map_err(|_| KintoError::UrlError));
+    if url.cannot_be_a_base() || url.query().is_some() {
+        return Err(KintoError::UrlError);
     }
+
+    let mut segments: Vec<String> = url.path()
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_owned)
+        .collect();
+    let versioned = match segments.last() {
+        Some(segment) => is_api_version(segment),
+        None => false,
+    };
+    if !versioned {
+        segments.push(DEFAULT_API_VERSION.to_owned());
+    }
+    url.set_path(&format!("/{}", segments.join("/")));
+    url.set_fragment(None);
+    Ok(url)
 }
 
 
 #[cfg(test)]
 mod test_client {
-    use resource::Resource;
+    use std::sync::Arc;
+    use std::thread;
+
+    use {KintoClient, Bucket, Collection, Group, Record};
+    use resource::{AnyResource, Resource};
     use utils::tests::setup_client;
 
     #[test]
@@ -112,6 +336,151 @@ mod test_client {
         assert_eq!(bucket.get_id().unwrap(), "food");
     }
 
+    #[test]
+    fn test_default_bucket() {
+        let client = setup_client();
+        let bucket = client.default_bucket().unwrap();
+        assert_ne!(bucket.get_id().unwrap(), "default");
+
+        let mut alias = client.bucket("default");
+        alias.load().unwrap();
+        assert_eq!(alias.get_id(), bucket.get_id());
+    }
+
+    #[test]
+    fn test_default_bucket_anonymous() {
+        let server_url = setup_client().server_url().to_string();
+        let client = KintoClient::new(server_url, None).unwrap();
+        client.default_bucket().unwrap_err();
+    }
+
+    #[test]
+    fn test_server_url_version_prefix() {
+        for url in &["http://localhost:8888",
+                     "http://localhost:8888/",
+                     "http://localhost:8888/v1",
+                     "http://localhost:8888/v1/"] {
+            let client = KintoClient::new(url.to_string(), None).unwrap();
+            assert_eq!(client.server_url().as_str(), "http://localhost:8888/v1");
+            assert_eq!(client.url("/buckets"), "http://localhost:8888/v1/buckets");
+        }
+
+        let client = KintoClient::new("https://example.com/kinto/v2/".to_owned(), None)
+            .unwrap();
+        assert_eq!(client.api_prefix(), "/kinto/v2");
+    }
+
+    #[test]
+    fn test_invalid_server_url() {
+        KintoClient::new("localhost".to_owned(), None).unwrap_err();
+        KintoClient::new("mailto:kinto@example.com".to_owned(), None).unwrap_err();
+    }
+
+    #[test]
+    fn test_endpoint_path() {
+        let client = setup_client();
+        assert_eq!(client.endpoint_path("http://localhost:8888/v1/buckets?_token=a"),
+                   "/buckets?_token=a");
+        assert_eq!(client.endpoint_path("/v1/buckets/food"), "/buckets/food");
+        assert_eq!(client.endpoint_path("/buckets/food"), "/buckets/food");
+    }
+
+    #[test]
+    fn test_resolve_uri() {
+        let client = setup_client();
+        let uris = [("/buckets/food", "bucket"),
+                    ("/buckets/food/collections/meat", "collection"),
+                    ("/buckets/food/groups/cooks", "group"),
+                    ("/buckets/food/collections/meat/records/entrecote", "record")];
+        for &(uri, name) in uris.iter() {
+            let resource = client.resolve(uri).unwrap();
+            assert_eq!(resource.resource_name(), name);
+            assert_eq!(resource.uri().unwrap(), uri);
+        }
+    }
+
+    #[test]
+    fn test_resolve_urls_and_plugin_paths() {
+        let client = setup_client();
+        let uris = [("http://localhost:8888/v1/buckets/food/collections/meat?_limit=2",
+                     "/buckets/food/collections/meat"),
+                    ("/v1/buckets/food/history", "/buckets/food"),
+                    ("/buckets/food/collections/meat/records",
+                     "/buckets/food/collections/meat"),
+                    ("/buckets/food/collections/meat/records/entrecote/attachment",
+                     "/buckets/food/collections/meat/records/entrecote")];
+        for &(uri, expected) in uris.iter() {
+            assert_eq!(client.resolve(uri).unwrap().uri().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_resolve_invalid_uri() {
+        let client = setup_client();
+        for uri in &["/",
+                     "/buckets",
+                     "/collections/meat",
+                     "/buckets/food/groups/a/records/b",
+                     "/buckets/fo%20od"] {
+            client.resolve(uri).unwrap_err();
+        }
+    }
+
+    #[test]
+    fn test_resolve_history_entries() {
+        let client = setup_client();
+        let mut bucket = client.bucket("food");
+        bucket.set().unwrap();
+        let mut collection = bucket.clone().collection("meat");
+        collection.set().unwrap();
+
+        let entries = bucket.history().unwrap();
+        let mut resource = client.resolve(&entries[0].uri).unwrap();
+        match resource {
+            AnyResource::Collection(ref mut collection) => collection.load().unwrap(),
+            _ => panic!("history entry should resolve to the collection"),
+        };
+    }
+
+    #[test]
+    fn test_clones_share_state() {
+        let client = setup_client();
+        let collection = client.bucket("food").collection("meat");
+        let record = collection.record("entrecote");
+        assert!(Arc::ptr_eq(&client.inner, &record.get_client().inner));
+    }
+
+    #[test]
+    fn test_handles_are_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<KintoClient>();
+        assert_send_sync::<Bucket>();
+        assert_send_sync::<Collection>();
+        assert_send_sync::<Group>();
+        assert_send_sync::<Record>();
+    }
+
+    #[test]
+    fn test_shared_client_across_threads() {
+        let client = setup_client();
+        let mut bucket = client.bucket("food");
+        bucket.set().unwrap();
+
+        let threads: Vec<_> = (0..4)
+            .map(|i| {
+                let bucket = bucket.clone();
+                thread::spawn(move || {
+                    let mut collection = bucket.collection(&format!("meat{}", i));
+                    collection.set().unwrap();
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(client.bucket("food").list_collections().unwrap().len(), 4);
+    }
+
     #[test]
     fn test_new_bucket() {
         let client = setup_client();
@@ -1,3 +1,5 @@
+use std::sync::Arc;
+
 use serde_json;
 use serde_json::Value;
 
@@ -8,7 +10,21 @@ use response::ResponseWrapper;
 use resource::Resource;
 use bucket::Bucket;
 use record::Record;
-use utils::unwrap_collection_records;
+use signer::Signer;
+use conflict::ConflictStrategy;
+use transformer::TransformerPipeline;
+use ids::{IdGenerator, UuidGenerator};
+use schema::Validator;
+use metadata::CollectionMetadata;
+use archive::ImportResult;
+use copy::{CopyOptions, copy_collection, rename_collection};
+use quota::{QuotaInfo, fetch_quota};
+use paths::Paths;
+use sync::{LocalStore, SyncResult, sync, sync_with};
+use permissions::{Permissions, CollectionPermission};
+use content_signature::{CertificateFetcher, HttpCertificateFetcher, TrustAnchor,
+                        unwrap_signature, verify};
+use utils::{unwrap_collection_records, etag_to_timestamp};
 
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -22,12 +38,42 @@ pub struct CollectionPermissions {
 }
 
 
+impl Permissions for CollectionPermissions {
+    type Permission = CollectionPermission;
+
+    fn principals(&self, perm: CollectionPermission) -> &Option<Vec<String>> {
+        match perm {
+            CollectionPermission::Read => &self.read,
+            CollectionPermission::Write => &self.write,
+            CollectionPermission::CreateRecord => &self.create_record,
+        }
+    }
+
+    fn principals_mut(&mut self,
+                      perm: CollectionPermission)
+                      -> &mut Option<Vec<String>> {
+        match perm {
+            CollectionPermission::Read => &mut self.read,
+            CollectionPermission::Write => &mut self.write,
+            CollectionPermission::CreateRecord => &mut self.create_record,
+        }
+    }
+}
+
+
 #[derive(Debug, Clone, Default)]
 pub struct Collection {
     pub data: Option<Value>,
     pub permissions: CollectionPermissions,
     pub bucket: Bucket,
     pub id: Option<String>,
+    /// Transformers applied to the records data, see `TransformerPipeline`
+    /// for the operations they cover.
+    pub transformers: TransformerPipeline,
+    /// Generator of the new records ids, UUIDs if not set.
+    pub id_generator: Option<Arc<dyn IdGenerator>>,
+    /// Validator of the records data, see `validator()`.
+    pub record_validator: Option<Validator>,
 }
 
 
@@ -39,6 +85,9 @@ impl Collection {
             id: None,
             data: None,
             permissions: CollectionPermissions::default(),
+            transformers: TransformerPipeline::default(),
+            id_generator: None,
+            record_validator: None,
         }
     }
 
@@ -49,6 +98,9 @@ impl Collection {
             id: Some(id.to_owned()),
             data: None,
             permissions: CollectionPermissions::default(),
+            transformers: TransformerPipeline::default(),
+            id_generator: None,
+            record_validator: None,
         }
     }
 
@@ -56,23 +108,171 @@ impl Collection {
         return Record::new_by_id(self.clone(), id);
     }
 
-    /// Create a new empty record with a generated id.
+    /// Create a new empty record, its id is generated when created.
     pub fn new_record(&self) -> Record {
         return Record::new(self.clone());
     }
 
+    /// Use a custom generator for the new records ids.
+    pub fn set_id_generator<G>(&mut self, generator: G) -> &mut Self
+        where G: IdGenerator + 'static
+    {
+        self.id_generator = Some(Arc::new(generator));
+        self
+    }
+
+    /// Get the typed metadata of the (loaded) collection.
+    pub fn metadata(&self) -> Result<CollectionMetadata, KintoError> {
+        CollectionMetadata::from_value(self.data.clone().unwrap_or(json!({})))
+    }
+
+    /// Replace the collection data with the given metadata, saved by `set()`.
+    pub fn set_metadata(&mut self,
+                        metadata: CollectionMetadata)
+                        -> Result<(), KintoError> {
+        self.data = Some(try!(metadata.to_value()));
+        Ok(())
+    }
+
+    /// Load the collection and get its typed metadata.
+    pub fn load_metadata(&mut self) -> Result<CollectionMetadata, KintoError> {
+        try!(self.load());
+        self.metadata()
+    }
+
+    /// Save the collection with the given metadata.
+    pub fn save_metadata(&mut self,
+                         metadata: CollectionMetadata)
+                         -> Result<(), KintoError> {
+        try!(self.set_metadata(metadata));
+        self.set()
+    }
+
+    /// Load the records schema of the collection.
+    ///
+    /// The records of this collection are then validated before being sent.
+    pub fn validator(&mut self) -> Result<Validator, KintoError> {
+        try!(self.load());
+        // Other metadata fields may not have the expected types
+        let schema = self.data
+            .as_ref()
+            .map(|data| data["schema"].clone())
+            .unwrap_or_default();
+        let validator = Validator::new(schema);
+        self.record_validator = Some(validator.clone());
+        Ok(validator)
+    }
+
+    /// Generate an id for a new record.
+    pub fn generate_id(&self) -> String {
+        match self.id_generator.as_ref() {
+            Some(generator) => generator.generate(),
+            None => UuidGenerator.generate(),
+        }
+    }
+
     /// List the names of all available records.
     pub fn list_records(&self) -> Result<Vec<Record>, KintoError> {
         let response = try!(try!(self.new_record().list_request()).follow_subrequests());
-        return Ok(unwrap_collection_records(response, self.new_record()));
+        let mut records = unwrap_collection_records(response, self.new_record());
+        for record in &mut records {
+            let uri = try!(record.uri());
+            record.data = match record.data.take() {
+                Some(data) => Some(try!(self.transformers.decode(&uri, data))),
+                None => None,
+            };
+        }
+        return Ok(records);
+    }
+
+    /// Save again all records with the current transformers (e.g. after a
+    /// key rotation).
+    pub fn rewrite_records(&self) -> Result<(), KintoError> {
+        for mut record in try!(self.list_records()) {
+            try!(record.update());
+        }
+        Ok(())
     }
 
-    /// Delete all available records.
+    /// Delete all available records, see `DeletePlan` for a preview first.
     pub fn delete_records(&self) -> Result<(), KintoError> {
         let resource = Record::new(self.clone());
         try!(try!(resource.delete_all_request()).follow_subrequests());
         Ok(())
     }
+
+    /// Copy the collection with its records into a bucket, under a new id.
+    pub fn copy_to(&self,
+                   bucket: &Bucket,
+                   id: &str,
+                   options: &CopyOptions)
+                   -> Result<ImportResult, KintoError> {
+        copy_collection(self, bucket, id, options)
+    }
+
+    /// Copy the collection to a new id, check the copy and delete this one.
+    pub fn rename(&self, id: &str) -> Result<Collection, KintoError> {
+        rename_collection(self, id)
+    }
+
+    /// Get the usage of the collection (kinto-quotas plugin).
+    pub fn quota(&self) -> Result<QuotaInfo, KintoError> {
+        let bucket_id = try!(self.bucket.get_id().ok_or(KintoError::UndefinedIdError));
+        let id = try!(self.get_id().ok_or(KintoError::UndefinedIdError));
+        fetch_quota(&self.get_client(), Paths::CollectionQuota(bucket_id, id).into())
+    }
+
+    /// Get the review workflow of this source collection (kinto-signer plugin).
+    pub fn signer(&self) -> Result<Signer, KintoError> {
+        Signer::new(self.clone())
+    }
+
+    /// Verify the content signature of this (destination) collection, by a
+    /// certificate issued by the trusted root.
+    pub fn verify_signature(&mut self, anchor: &TrustAnchor) -> Result<(), KintoError> {
+        let fetcher = HttpCertificateFetcher::new(self.get_client());
+        self.verify_signature_with(anchor, &fetcher)
+    }
+
+    /// Verify the content signature, fetching the certificates with `fetcher`.
+    pub fn verify_signature_with<F>(&mut self,
+                                    anchor: &TrustAnchor,
+                                    fetcher: &F)
+                                    -> Result<(), KintoError>
+        where F: CertificateFetcher
+    {
+        try!(self.load());
+        let signature = try!(unwrap_signature(&self.data.clone().unwrap_or_default()));
+
+        let response = try!(try!(self.new_record().list_request()).follow_subrequests());
+        let timestamp = try!(etag_to_timestamp(&response.headers)
+                                 .ok_or(KintoError::SignatureError));
+        let records = match response.body["data"].as_array() {
+            Some(records) => records.clone(),
+            None => return Err(KintoError::SignatureError),
+        };
+
+        verify(records.as_slice(), timestamp, &signature, anchor, fetcher)
+    }
+
+    /// Synchronize a local copy of this collection.
+    pub fn sync<S>(&self, store: &mut S) -> Result<SyncResult, KintoError>
+        where S: LocalStore
+    {
+        sync(self, store)
+    }
+
+    /// Synchronize a local copy of this collection, resolving the conflicts
+    /// with `strategy`.
+    pub fn sync_with<S, C>(&self,
+                           store: &mut S,
+                           strategy: &C)
+                           -> Result<SyncResult, KintoError>
+        where S: LocalStore,
+              C: ConflictStrategy
+    {
+        sync_with(self, store, strategy)
+    }
 }
 
 
@@ -81,11 +281,12 @@ impl Resource for Collection {
         Ok(format!("{}/collections", try!(self.bucket.record_path())))
     }
 
-    fn unwrap_response(&mut self, wrapper: ResponseWrapper) {
+    fn unwrap_response(&mut self, wrapper: ResponseWrapper) -> Result<(), KintoError> {
         self.data = Some(wrapper.body["data"].to_owned());
         self.permissions = serde_json::from_value(wrapper.body["permissions"].to_owned())
-            .unwrap();
+            .unwrap_or_default();
         self.id = Some(wrapper.body["data"]["id"].as_str().unwrap().to_owned());
+        Ok(())
     }
 
     fn get_client(&self) -> KintoClient {
@@ -200,6 +401,19 @@ mod test_collection {
         assert!(create_data["last_modified"] != update_data["last_modified"]);
     }
 
+    #[test]
+    fn test_patch_collection() {
+        let mut collection = setup_collection();
+        collection.data = json!({"good": true}).into();
+        collection.create().unwrap();
+
+        collection.patch(json!({"status": "to-review"})).unwrap();
+        let data = collection.data.unwrap();
+
+        assert_eq!(data["good"].as_bool().unwrap(), true);
+        assert_eq!(data["status"], "to-review");
+    }
+
     #[test]
     fn test_update_collection_fails_on_not_existing() {
         let client = setup_bucket();
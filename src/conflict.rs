@@ -0,0 +1,281 @@
+use std::collections::BTreeSet;
+
+use serde_json::Value;
+use serde_json::map::Map;
+
+
+/// Concurrent changes of a same object.
+///
+/// A `Null` version means the object does not exist (never created or
+/// deleted) on that side.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// Last version known by both sides, if any.
+    pub base: Option<Value>,
+    pub local: Value,
+    pub remote: Value,
+}
+
+
+/// Field that was changed differently on both sides.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldConflict {
+    /// JSON pointer of the field (e.g. `/author/name`), empty for the whole object.
+    pub path: String,
+    pub base: Option<Value>,
+    pub local: Option<Value>,
+    pub remote: Option<Value>,
+}
+
+
+/// Outcome of a conflict resolution.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    /// Data to keep, `Null` to keep the object deleted.
+    Resolved(Value),
+    Unresolved(Vec<FieldConflict>),
+}
+
+
+/// Resolve concurrent changes of an object.
+pub trait ConflictStrategy {
+    fn resolve(&self, conflict: &Conflict) -> Resolution;
+}
+
+
+/// Always keep the server version.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ServerWins;
+
+
+impl ConflictStrategy for ServerWins {
+    fn resolve(&self, conflict: &Conflict) -> Resolution {
+        Resolution::Resolved(conflict.remote.clone())
+    }
+}
+
+
+/// Always keep the local version.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClientWins;
+
+
+impl ConflictStrategy for ClientWins {
+    fn resolve(&self, conflict: &Conflict) -> Resolution {
+        Resolution::Resolved(conflict.local.clone())
+    }
+}
+
+
+/// Merge the fields changed on each side since the base version.
+///
+/// Fields changed differently on both sides are left unresolved.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreeWayMerge;
+
+
+impl ConflictStrategy for ThreeWayMerge {
+    fn resolve(&self, conflict: &Conflict) -> Resolution {
+        let base = conflict.base.as_ref().map(without_timestamp);
+        let local = without_timestamp(&conflict.local);
+        let remote = without_timestamp(&conflict.remote);
+
+        let mut conflicts = vec![];
+        let merged = merge(String::new(),
+                           base.as_ref().and_then(existing),
+                           existing(&local),
+                           existing(&remote),
+                           &mut conflicts);
+
+        if conflicts.is_empty() {
+            Resolution::Resolved(merged.unwrap_or(Value::Null))
+        } else {
+            Resolution::Unresolved(conflicts)
+        }
+    }
+}
+
+
+/// Let a callback resolve the conflicts.
+#[derive(Debug, Clone, Copy)]
+pub struct Manual<F>
+    where F: Fn(&Conflict) -> Resolution
+{
+    callback: F,
+}
+
+
+impl<F> Manual<F>
+    where F: Fn(&Conflict) -> Resolution
+{
+    pub fn new(callback: F) -> Manual<F> {
+        Manual { callback: callback }
+    }
+}
+
+
+impl<F> ConflictStrategy for Manual<F>
+    where F: Fn(&Conflict) -> Resolution
+{
+    fn resolve(&self, conflict: &Conflict) -> Resolution {
+        (self.callback)(conflict)
+    }
+}
+
+
+/// Three-way merge of a value, `None` meaning absent.
+fn merge(path: String,
+         base: Option<&Value>,
+         local: Option<&Value>,
+         remote: Option<&Value>,
+         conflicts: &mut Vec<FieldConflict>)
+         -> Option<Value> {
+    if local == remote || remote == base {
+        return local.cloned();
+    }
+    if local == base {
+        return remote.cloned();
+    }
+
+    match (local, remote) {
+        (Some(&Value::Object(ref local)), Some(&Value::Object(ref remote))) => {
+            let empty = Map::new();
+            let base = match base {
+                Some(&Value::Object(ref base)) => base,
+                _ => &empty,
+            };
+
+            let keys: BTreeSet<&String> =
+                base.keys().chain(local.keys()).chain(remote.keys()).collect();
+
+            let mut merged = Map::new();
+            for key in keys {
+                let field_path = format!("{}/{}", path, escape_pointer(key));
+                let value = merge(field_path,
+                                  base.get(key),
+                                  local.get(key),
+                                  remote.get(key),
+                                  conflicts);
+                match value {
+                    Some(value) => {
+                        merged.insert(key.clone(), value);
+                    }
+                    None => (),
+                };
+            }
+            Some(Value::Object(merged))
+        }
+        _ => {
+            conflicts.push(FieldConflict {
+                               path: path,
+                               base: base.cloned(),
+                               local: local.cloned(),
+                               remote: remote.cloned(),
+                           });
+            local.cloned()
+        }
+    }
+}
+
+
+/// Escape a key to be used in a JSON pointer.
+pub fn escape_pointer(key: &str) -> String {
+    key.replace("~", "~0").replace("/", "~1")
+}
+
+
+fn existing(value: &Value) -> Option<&Value> {
+    match *value {
+        Value::Null => None,
+        ref value => Some(value),
+    }
+}
+
+
+/// Data without its server timestamp.
+pub fn without_timestamp(data: &Value) -> Value {
+    let mut data = data.clone();
+    match data.as_object_mut() {
+        Some(map) => {
+            map.remove("last_modified");
+        }
+        None => (),
+    };
+    data
+}
+
+
+#[cfg(test)]
+mod test_conflict {
+    use super::{Conflict, ConflictStrategy, ClientWins, Manual, Resolution, ServerWins,
+                ThreeWayMerge};
+
+    fn conflict() -> Conflict {
+        Conflict {
+            base: Some(json!({"id": "a", "title": "beef", "tags": {"cut": "rib"},
+                              "last_modified": 1})),
+            local: json!({"id": "a", "title": "lamb", "tags": {"cut": "rib"},
+                          "last_modified": 1}),
+            remote: json!({"id": "a", "title": "beef", "tags": {"cut": "loin"},
+                           "last_modified": 2}),
+        }
+    }
+
+    #[test]
+    fn test_server_and_client_wins() {
+        let conflict = conflict();
+        assert_eq!(ServerWins.resolve(&conflict),
+                   Resolution::Resolved(conflict.remote.clone()));
+        assert_eq!(ClientWins.resolve(&conflict),
+                   Resolution::Resolved(conflict.local.clone()));
+    }
+
+    #[test]
+    fn test_merge_fields() {
+        assert_eq!(ThreeWayMerge.resolve(&conflict()),
+                   Resolution::Resolved(json!({"id": "a", "title": "lamb",
+                                               "tags": {"cut": "loin"}})));
+    }
+
+    #[test]
+    fn test_merge_reports_field_conflicts() {
+        let mut conflict = conflict();
+        conflict.remote["tags"]["cut"] = "loin".into();
+        conflict.local["tags"]["cut"] = "flank".into();
+        conflict.local["tags"]["a/b"] = "new".into();
+
+        match ThreeWayMerge.resolve(&conflict) {
+            Resolution::Unresolved(fields) => {
+                assert_eq!(fields.len(), 1);
+                assert_eq!(fields[0].path, "/tags/cut");
+                assert_eq!(fields[0].base, Some(json!("rib")));
+                assert_eq!(fields[0].local, Some(json!("flank")));
+                assert_eq!(fields[0].remote, Some(json!("loin")));
+            }
+            resolution => panic!("Unexpected resolution {:?}", resolution),
+        };
+    }
+
+    #[test]
+    fn test_merge_deletion() {
+        let mut conflict = conflict();
+        conflict.local = json!(null);
+        conflict.remote = conflict.base.clone().unwrap();
+        assert_eq!(ThreeWayMerge.resolve(&conflict), Resolution::Resolved(json!(null)));
+
+        conflict.remote = json!({"id": "a", "title": "pork"});
+        match ThreeWayMerge.resolve(&conflict) {
+            Resolution::Unresolved(fields) => assert_eq!(fields[0].path, ""),
+            resolution => panic!("Unexpected resolution {:?}", resolution),
+        };
+    }
+
+    #[test]
+    fn test_manual() {
+        let strategy = Manual::new(|conflict: &Conflict| {
+            Resolution::Resolved(conflict.base.clone().unwrap())
+        });
+        assert_eq!(strategy.resolve(&conflict()),
+                   Resolution::Resolved(conflict().base.unwrap()));
+    }
+}
@@ -0,0 +1,469 @@
+use std::io::Read;
+
+use serde_json;
+use serde_json::Value;
+use hyper::header::Headers;
+use openssl::asn1::Asn1Time;
+use openssl::base64;
+use openssl::bn::BigNum;
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::{hash, MessageDigest};
+use openssl::x509::X509;
+
+use KintoClient;
+use error::KintoError;
+use signer::Signature;
+
+
+/// Prefix of the signed messages.
+const SIGNATURE_PREFIX: &[u8] = b"Content-Signature:\x00";
+
+/// Only supported signature mode.
+const SIGNATURE_MODE: &str = "p384ecdsa";
+
+
+/// Certificates trusted to sign the collections.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrustAnchor {
+    /// SHA-256 fingerprint of the root certificate, hex encoded (colons are
+    /// ignored).
+    pub root_hash: String,
+    /// Name expected in the subject alternative names of the signing certificate
+    /// (e.g. `remote-settings.content-signature.mozilla.org`).
+    pub signer_name: String,
+}
+
+
+impl TrustAnchor {
+    pub fn new(root_hash: &str, signer_name: &str) -> TrustAnchor {
+        TrustAnchor {
+            root_hash: root_hash.replace(":", "").to_lowercase(),
+            signer_name: signer_name.to_owned(),
+        }
+    }
+}
+
+
+/// Fetch the certificate chain referenced by a signature `x5u` url.
+pub trait CertificateFetcher {
+    /// Get the PEM encoded certificate chain, starting with the signing certificate.
+    fn fetch(&self, x5u: &str) -> Result<Vec<u8>, KintoError>;
+}
+
+
+/// Fetch the certificate chain using the client HTTP connection, over https only.
+#[derive(Debug, Clone)]
+pub struct HttpCertificateFetcher {
+    pub client: KintoClient,
+}
+
+
+impl HttpCertificateFetcher {
+    pub fn new(client: KintoClient) -> HttpCertificateFetcher {
+        HttpCertificateFetcher { client: client }
+    }
+}
+
+
+impl CertificateFetcher for HttpCertificateFetcher {
+    fn fetch(&self, x5u: &str) -> Result<Vec<u8>, KintoError> {
+        if !x5u.to_lowercase().starts_with("https://") {
+            return Err(KintoError::SignatureError);
+        }
+
+        let mut response = match self.client
+                  .http_client()
+                  .get(x5u)
+                  .headers(Headers::new())
+                  .send() {
+            Ok(response) => response,
+            Err(_) => return Err(KintoError::HyperError),
+        };
+
+        if !response.status.is_success() {
+            return Err(KintoError::HyperError);
+        }
+
+        let mut pem = vec![];
+        try!(response.read_to_end(&mut pem));
+        Ok(pem)
+    }
+}
+
+
+/// Serialize a JSON value the way Kinto does before signing (sorted keys,
+/// no whitespace, non-ASCII characters escaped).
+pub fn canonical_json(value: &Value) -> String {
+    match *value {
+        Value::Null => "null".to_owned(),
+        Value::Bool(b) => format!("{}", b),
+        Value::Number(ref n) => format!("{}", n),
+        Value::String(ref s) => canonical_string(s),
+        Value::Array(ref items) => {
+            let items: Vec<String> = items.iter().map(canonical_json).collect();
+            format!("[{}]", items.join(","))
+        }
+        Value::Object(ref map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            let items: Vec<String> = keys.iter()
+                .map(|key| {
+                         let value = canonical_json(&map[*key]);
+                         format!("{}:{}", canonical_string(key), value)
+                     })
+                .collect();
+            format!("{{{}}}", items.join(","))
+        }
+    }
+}
+
+
+fn canonical_string(s: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{08}' => escaped.push_str("\\b"),
+            '\u{0c}' => escaped.push_str("\\f"),
+            c if (c as u32) < 0x20 || (c as u32) > 0x7e => {
+                let mut units = [0; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    escaped.push_str(&format!("\\u{:04x}", unit));
+                }
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+
+/// Build the signed payload of a collection: its records sorted by id and
+/// its timestamp.
+pub fn serialize_records(records: &[Value], timestamp: u64) -> String {
+    let mut records: Vec<&Value> = records.iter()
+        .filter(|record| !record["deleted"].as_bool().unwrap_or(false))
+        .collect();
+    records.sort_by_key(|record| record["id"].as_str().unwrap_or("").to_owned());
+
+    let payload = json!({
+        "data": records,
+        "last_modified": format!("{}", timestamp),
+    });
+    canonical_json(&payload)
+}
+
+
+/// Verify the signature of a list of records with the given timestamp, by a
+/// certificate chaining up to the trusted root.
+pub fn verify<F>(records: &[Value],
+                 timestamp: u64,
+                 signature: &Signature,
+                 anchor: &TrustAnchor,
+                 fetcher: &F)
+                 -> Result<(), KintoError>
+    where F: CertificateFetcher
+{
+    if signature.mode != SIGNATURE_MODE {
+        return Err(KintoError::SignatureError);
+    }
+
+    let pem = try!(fetcher.fetch(signature.x5u.as_str()));
+    let chain = try!(X509::stack_from_pem(pem.as_slice())
+                         .map_err(|_| KintoError::SignatureError));
+    try!(verify_chain(&chain, anchor));
+
+    let mut message = SIGNATURE_PREFIX.to_vec();
+    message.extend(serialize_records(records, timestamp).as_bytes());
+    let digest = try!(hash(MessageDigest::sha384(), message.as_slice())
+                          .map_err(|_| KintoError::SignatureError));
+
+    let sig = try!(decode_signature(signature.signature.as_str()));
+    let key = try!(chain[0]
+                       .public_key()
+                       .and_then(|key| key.ec_key())
+                       .map_err(|_| KintoError::SignatureError));
+
+    match sig.verify(&digest, &key) {
+        Ok(true) => Ok(()),
+        _ => Err(KintoError::SignatureError),
+    }
+}
+
+
+/// Check the validity dates of the certificates, that each one is signed by
+/// the next one in the chain, that the chain ends with the trusted root and
+/// that the signing certificate was issued to the expected signer.
+fn verify_chain(chain: &[X509], anchor: &TrustAnchor) -> Result<(), KintoError> {
+    let (leaf, root) = match (chain.first(), chain.last()) {
+        (Some(leaf), Some(root)) => (leaf, root),
+        _ => return Err(KintoError::SignatureError),
+    };
+
+    let fingerprint = try!(root.digest(MessageDigest::sha256())
+                               .map_err(|_| KintoError::SignatureError));
+    let fingerprint: Vec<String> =
+        fingerprint.iter().map(|byte| format!("{:02x}", byte)).collect();
+    if fingerprint.concat() != anchor.root_hash.replace(":", "").to_lowercase() {
+        return Err(KintoError::SignatureError);
+    }
+    let root_key = try!(root.public_key().map_err(|_| KintoError::SignatureError));
+    match root.verify(&root_key) {
+        Ok(true) => (),
+        _ => return Err(KintoError::SignatureError),
+    };
+
+    let names = try!(leaf.subject_alt_names().ok_or(KintoError::SignatureError));
+    if !names.iter().any(|name| name.dnsname() == Some(anchor.signer_name.as_str())) {
+        return Err(KintoError::SignatureError);
+    }
+
+    let now = try!(Asn1Time::days_from_now(0).map_err(|_| KintoError::SignatureError));
+    for cert in chain {
+        if cert.not_before() > now || cert.not_after() < now {
+            return Err(KintoError::SignatureError);
+        }
+    }
+
+    for pair in chain.windows(2) {
+        let issuer_key = try!(pair[1]
+                                  .public_key()
+                                  .map_err(|_| KintoError::SignatureError));
+        match pair[0].verify(&issuer_key) {
+            Ok(true) => (),
+            _ => return Err(KintoError::SignatureError),
+        };
+    }
+    Ok(())
+}
+
+
+/// Decode a base64 (url safe) encoded raw `r || s` P-384 signature.
+fn decode_signature(signature: &str) -> Result<EcdsaSig, KintoError> {
+    let mut encoded = signature.trim().replace("-", "+").replace("_", "/");
+    while encoded.len() % 4 != 0 {
+        encoded.push('=');
+    }
+
+    let raw = try!(base64::decode_block(encoded.as_str())
+                       .map_err(|_| KintoError::SignatureError));
+    if raw.len() != 96 {
+        return Err(KintoError::SignatureError);
+    }
+
+    let r = try!(BigNum::from_slice(&raw[..48]).map_err(|_| KintoError::SignatureError));
+    let s = try!(BigNum::from_slice(&raw[48..]).map_err(|_| KintoError::SignatureError));
+    EcdsaSig::from_private_components(r, s).map_err(|_| KintoError::SignatureError)
+}
+
+
+/// Parse the signature metadata of a collection.
+pub fn unwrap_signature(data: &Value) -> Result<Signature, KintoError> {
+    serde_json::from_value(data["signature"].to_owned())
+        .map_err(|_| KintoError::SignatureError)
+}
+
+
+#[cfg(test)]
+mod test_content_signature {
+    use openssl::asn1::Asn1Time;
+    use openssl::base64;
+    use openssl::bn::BigNum;
+    use openssl::ec::{EcGroup, EcKey};
+    use openssl::ecdsa::EcdsaSig;
+    use openssl::hash::{hash, MessageDigest};
+    use openssl::nid::Nid;
+    use openssl::pkey::PKey;
+    use openssl::pkey::Private;
+    use openssl::x509::{X509, X509NameBuilder, X509Ref};
+    use openssl::x509::extension::SubjectAlternativeName;
+
+    use KintoClient;
+    use error::KintoError;
+    use signer::Signature;
+    use super::{CertificateFetcher, HttpCertificateFetcher, TrustAnchor,
+                canonical_json, serialize_records, verify, SIGNATURE_PREFIX};
+
+    const SIGNER_NAME: &str = "signer.kinto-http.rs";
+
+    /// Serve a locally generated certificate.
+    struct LocalFetcher {
+        pem: Vec<u8>,
+    }
+
+    impl CertificateFetcher for LocalFetcher {
+        fn fetch(&self, _: &str) -> Result<Vec<u8>, KintoError> {
+            Ok(self.pem.clone())
+        }
+    }
+
+    fn generate_key() -> (EcKey<Private>, PKey<Private>) {
+        let group = EcGroup::from_curve_name(Nid::SECP384R1).unwrap();
+        let key = EcKey::generate(&group).unwrap();
+        let pkey = PKey::from_ec_key(key.clone()).unwrap();
+        (key, pkey)
+    }
+
+    /// Build a certificate for `pkey`, signed by the issuer or self-signed.
+    fn certificate(common_name: &str,
+                   pkey: &PKey<Private>,
+                   issuer: Option<(&X509, &PKey<Private>)>)
+                   -> X509 {
+        let mut name = X509NameBuilder::new().unwrap();
+        name.append_entry_by_text("CN", common_name).unwrap();
+        let name = name.build();
+
+        let mut builder = X509::builder().unwrap();
+        builder.set_version(2).unwrap();
+        let serial = BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap();
+        builder.set_serial_number(&serial).unwrap();
+        builder.set_subject_name(&name).unwrap();
+        builder.set_pubkey(pkey).unwrap();
+        builder.set_not_before(&Asn1Time::days_from_now(0).unwrap()).unwrap();
+        builder.set_not_after(&Asn1Time::days_from_now(1).unwrap()).unwrap();
+        let san = SubjectAlternativeName::new()
+            .dns(common_name)
+            .build(&builder.x509v3_context(issuer.map(|(cert, _)| cert as &X509Ref),
+                                           None))
+            .unwrap();
+        builder.append_extension(san).unwrap();
+        match issuer {
+            Some((cert, key)) => {
+                builder.set_issuer_name(cert.subject_name()).unwrap();
+                builder.sign(key, MessageDigest::sha384()).unwrap();
+            }
+            None => {
+                builder.set_issuer_name(&name).unwrap();
+                builder.sign(pkey, MessageDigest::sha384()).unwrap();
+            }
+        };
+        builder.build()
+    }
+
+    fn anchor(root: &X509) -> TrustAnchor {
+        let fingerprint: Vec<String> = root.digest(MessageDigest::sha256())
+            .unwrap()
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect();
+        TrustAnchor::new(&fingerprint.join(":"), SIGNER_NAME)
+    }
+
+    /// Sign a payload with a certificate issued by a new root, returned with
+    /// the chain.
+    fn sign(payload: &str) -> (LocalFetcher, String, X509) {
+        let (_, root_pkey) = generate_key();
+        let root = certificate("root.kinto-http.rs", &root_pkey, None);
+        let (key, pkey) = generate_key();
+        let cert = certificate(SIGNER_NAME, &pkey, Some((&root, &root_pkey)));
+        let mut pem = cert.to_pem().unwrap();
+        pem.extend(root.to_pem().unwrap());
+
+        (LocalFetcher { pem: pem }, sign_with(payload, &key), root)
+    }
+
+    fn sign_with(payload: &str, key: &EcKey<Private>) -> String {
+
+        let mut message = SIGNATURE_PREFIX.to_vec();
+        message.extend(payload.as_bytes());
+        let digest = hash(MessageDigest::sha384(), message.as_slice()).unwrap();
+        let sig = EcdsaSig::sign(&digest, key).unwrap();
+
+        let mut raw = sig.r().to_vec_padded(48).unwrap();
+        raw.extend(sig.s().to_vec_padded(48).unwrap());
+        base64::encode_block(raw.as_slice())
+            .replace("+", "-")
+            .replace("/", "_")
+    }
+
+    fn signature(signature: String) -> Signature {
+        Signature {
+            x5u: "https://localhost/chain.pem".to_owned(),
+            signature: signature,
+            mode: "p384ecdsa".to_owned(),
+            reference: None,
+        }
+    }
+
+    #[test]
+    fn test_canonical_json() {
+        let value = json!({"b": [1, true, null], "a": "é\n\"/"});
+        assert_eq!(canonical_json(&value), r#"{"a":"\u00e9\n\"/","b":[1,true,null]}"#);
+    }
+
+    #[test]
+    fn test_serialize_records() {
+        let records = vec![json!({"id": "b", "last_modified": 2}),
+                           json!({"id": "a", "last_modified": 1}),
+                           json!({"id": "c", "deleted": true})];
+        assert_eq!(serialize_records(&records, 3),
+                   concat!(r#"{"data":[{"id":"a","last_modified":1},"#,
+                           r#"{"id":"b","last_modified":2}],"last_modified":"3"}"#));
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let records = vec![json!({"id": "b", "title": "beef"}),
+                           json!({"id": "a", "title": "lamb"})];
+        let (fetcher, encoded, root) = sign(&serialize_records(&records, 42));
+        verify(&records, 42, &signature(encoded), &anchor(&root), &fetcher).unwrap();
+    }
+
+    #[test]
+    fn test_verify_signature_fails_on_tampered_records() {
+        let records = vec![json!({"id": "a", "title": "lamb"})];
+        let (fetcher, encoded, root) = sign(&serialize_records(&records, 42));
+        let anchor = anchor(&root);
+        let tampered = vec![json!({"id": "a", "title": "pork"})];
+        verify(&tampered, 42, &signature(encoded.clone()), &anchor, &fetcher)
+            .unwrap_err();
+        verify(&records, 43, &signature(encoded), &anchor, &fetcher).unwrap_err();
+    }
+
+    #[test]
+    fn test_verify_signature_fails_on_unknown_mode() {
+        let records = vec![json!({"id": "a"})];
+        let (fetcher, encoded, root) = sign(&serialize_records(&records, 42));
+        let mut signature = signature(encoded);
+        signature.mode = "rsa".to_owned();
+        verify(&records, 42, &signature, &anchor(&root), &fetcher).unwrap_err();
+    }
+
+    #[test]
+    fn test_verify_signature_fails_on_self_signed_chain() {
+        let records = vec![json!({"id": "a"})];
+        let payload = serialize_records(&records, 42);
+        let (_, _, root) = sign(&payload);
+
+        let (key, pkey) = generate_key();
+        let cert = certificate(SIGNER_NAME, &pkey, None);
+        let fetcher = LocalFetcher { pem: cert.to_pem().unwrap() };
+        let encoded = sign_with(&payload, &key);
+        verify(&records, 42, &signature(encoded), &anchor(&root), &fetcher).unwrap_err();
+    }
+
+    #[test]
+    fn test_verify_signature_fails_on_other_signer() {
+        let records = vec![json!({"id": "a"})];
+        let (fetcher, encoded, root) = sign(&serialize_records(&records, 42));
+        let mut anchor = anchor(&root);
+        anchor.signer_name = "other.kinto-http.rs".to_owned();
+        verify(&records, 42, &signature(encoded), &anchor, &fetcher).unwrap_err();
+    }
+
+    #[test]
+    fn test_fetcher_refuses_plain_http() {
+        let client = KintoClient::new("http://localhost:8888/v1".to_owned(), None)
+            .unwrap();
+        let fetcher = HttpCertificateFetcher::new(client);
+        match fetcher.fetch("http://localhost:8888/chain.pem") {
+            Err(KintoError::SignatureError) => (),
+            _ => panic!("the chain was fetched over plain http"),
+        };
+    }
+}
@@ -0,0 +1,433 @@
+use hyper::header::IfMatch;
+
+use error::KintoError;
+use request::{KintoRequest, PluralEndpoint};
+use resource::Resource;
+use bucket::Bucket;
+use collection::Collection;
+use archive::{ArchiveCollection, BucketArchive, ImportOptions, ImportResult, OnExisting};
+use diff::diff;
+use utils::{etag_to_timestamp, timestamp_to_etag};
+
+
+/// Options of a collection copy.
+#[derive(Debug, Clone)]
+pub struct CopyOptions {
+    /// Copy the permissions of the collection and records, else the copies
+    /// only get the ones inherited from the target bucket.
+    pub permissions: bool,
+    pub on_existing: OnExisting,
+}
+
+
+impl Default for CopyOptions {
+    fn default() -> CopyOptions {
+        CopyOptions {
+            permissions: true,
+            on_existing: OnExisting::Skip,
+        }
+    }
+}
+
+
+/// Copy a collection with its records into a bucket, under a new id.
+pub fn copy_collection(source: &Collection,
+                       bucket: &Bucket,
+                       id: &str,
+                       options: &CopyOptions)
+                       -> Result<ImportResult, KintoError> {
+    let mut archived = try!(ArchiveCollection::fetch(source));
+    if !options.permissions {
+        archived.permissions = json!({});
+        for record in &mut archived.records {
+            record.permissions = json!({});
+        }
+    }
+    let mut import = ImportOptions::new();
+    import.on_existing(options.on_existing);
+    archived.restore(bucket, id, &import)
+}
+
+
+/// Copy a bucket with its groups, collections and records under a new id.
+///
+/// Permissions given to the groups of the bucket are given to the copied
+/// groups.
+pub fn copy_bucket(source: &Bucket, id: &str) -> Result<ImportResult, KintoError> {
+    let mut options = ImportOptions::new();
+    options.bucket_id(id);
+    try!(BucketArchive::fetch(source)).restore(&source.get_client(), &options)
+}
+
+
+/// Move a collection to a new id in its bucket: copy it, check the copy
+/// then delete it.
+///
+/// Fails without copying anything if the target already exists, and keeps
+/// the source if it or its records were changed since the copy started. The
+/// target is deleted if the rename fails after its creation.
+pub fn rename_collection(source: &Collection,
+                         id: &str)
+                         -> Result<Collection, KintoError> {
+    try!(check_new_id(source, id));
+    let mut source = source.clone();
+    try!(source.load());
+    let stamps = try!(collection_stamps(&source));
+    let mut target = source.bucket.clone().collection(id);
+    try!(reserve(&mut target));
+    let moved = move_collection(&source, &target, &stamps);
+    release(target, moved)
+}
+
+
+fn move_collection(source: &Collection,
+                   target: &Collection,
+                   stamps: &[Option<u64>])
+                   -> Result<(), KintoError> {
+    let options = CopyOptions {
+        on_existing: OnExisting::Overwrite,
+        ..CopyOptions::default()
+    };
+    let id = try!(target.get_id().ok_or(KintoError::UndefinedIdError));
+    try!(check_result(&try!(copy_collection(source, &target.bucket, id, &options))));
+    try!(verify_copy(source, target));
+    try!(check_unchanged(source, stamps, &try!(collection_stamps(source))));
+    delete_unchanged(source)
+}
+
+
+/// Move a bucket to a new id: copy it, check the copy then delete it.
+///
+/// Fails without copying anything if the target already exists, and keeps
+/// the source if it or its content were changed since the copy started. The
+/// target is deleted if the rename fails after its creation.
+pub fn rename_bucket(source: &Bucket, id: &str) -> Result<Bucket, KintoError> {
+    try!(check_new_id(source, id));
+    let mut source = source.clone();
+    try!(source.load());
+    let stamps = try!(bucket_stamps(&source));
+    let mut target = source.get_client().bucket(id);
+    try!(reserve(&mut target));
+    let moved = move_bucket(&source, &target, &stamps);
+    release(target, moved)
+}
+
+
+fn move_bucket(source: &Bucket,
+               target: &Bucket,
+               stamps: &[Option<u64>])
+               -> Result<(), KintoError> {
+    let mut options = ImportOptions::new();
+    options.bucket_id(try!(target.get_id().ok_or(KintoError::UndefinedIdError)));
+    options.on_existing(OnExisting::Overwrite);
+    let archive = try!(BucketArchive::fetch(source));
+    try!(check_result(&try!(archive.restore(&source.get_client(), &options))));
+
+    let group_ids = |bucket: &Bucket| -> Result<Vec<String>, KintoError> {
+        let groups = try!(bucket.list_groups());
+        let mut ids: Vec<String> = groups.iter()
+            .filter_map(|group| group.get_id().map(str::to_owned))
+            .collect();
+        ids.sort();
+        Ok(ids)
+    };
+    if try!(group_ids(source)) != try!(group_ids(target)) {
+        return Err(KintoError::CopyError(try!(source.uri())));
+    }
+    for collection in try!(source.list_collections()) {
+        let collection_id = try!(collection.get_id().ok_or(KintoError::UndefinedIdError));
+        try!(verify_copy(&collection, &target.clone().collection(collection_id)));
+    }
+
+    try!(check_unchanged(source, stamps, &try!(bucket_stamps(source))));
+    delete_unchanged(source)
+}
+
+
+/// Refuse to rename an object to its own id, which would delete it.
+fn check_new_id<T>(source: &T, id: &str) -> Result<(), KintoError>
+    where T: Resource
+{
+    if source.get_id() == Some(id) {
+        return Err(KintoError::CopyError(try!(source.uri())));
+    }
+    Ok(())
+}
+
+
+/// Create the empty target of a rename, so that nothing is copied into an
+/// existing object.
+fn reserve<T>(target: &mut T) -> Result<(), KintoError>
+    where T: Resource
+{
+    match target.create() {
+        Err(KintoError::PreconditionError) => {
+            Err(KintoError::CopyError(try!(target.uri())))
+        }
+        result => result,
+    }
+}
+
+
+/// Keep the target of a successful rename, else delete it so that the rename
+/// can be retried.
+fn release<T>(mut target: T, moved: Result<(), KintoError>) -> Result<T, KintoError>
+    where T: Resource
+{
+    match moved {
+        Ok(()) => Ok(target),
+        Err(error) => {
+            let _ = target.delete();
+            Err(error)
+        }
+    }
+}
+
+
+/// Timestamp of the objects of a plural endpoint, bumped by any change.
+fn list_stamp<T>(resource: &T) -> Result<Option<u64>, KintoError>
+    where T: Resource
+{
+    let response = try!(try!(resource.list_request()).limit(1).send());
+    Ok(etag_to_timestamp(&response.headers))
+}
+
+
+/// Timestamps that change with the records of a collection.
+///
+/// The timestamp of the collection itself is not bumped by its records.
+fn collection_stamps(collection: &Collection) -> Result<Vec<Option<u64>>, KintoError> {
+    Ok(vec![try!(list_stamp(&collection.new_record()))])
+}
+
+
+/// Timestamps that change with the groups, collections and records of a
+/// bucket.
+fn bucket_stamps(bucket: &Bucket) -> Result<Vec<Option<u64>>, KintoError> {
+    let mut stamps = vec![try!(list_stamp(&bucket.new_group())),
+                          try!(list_stamp(&bucket.new_collection()))];
+    for collection in try!(bucket.list_collections()) {
+        stamps.extend(try!(collection_stamps(&collection)));
+    }
+    Ok(stamps)
+}
+
+
+/// Refuse to delete a source whose content changed since it was copied.
+fn check_unchanged<T>(source: &T,
+                      copied: &[Option<u64>],
+                      current: &[Option<u64>])
+                      -> Result<(), KintoError>
+    where T: Resource
+{
+    if copied != current {
+        return Err(KintoError::CopyError(try!(source.uri())));
+    }
+    Ok(())
+}
+
+
+/// Delete a renamed source, unless it was changed since it was loaded.
+fn delete_unchanged<T>(source: &T) -> Result<(), KintoError>
+    where T: Resource
+{
+    let if_match = match source.get_timestamp() {
+        Some(stamp) => IfMatch::Items(timestamp_to_etag(stamp)),
+        None => IfMatch::Any,
+    };
+    try!(try!(source.delete_request()).if_match(if_match).send());
+    Ok(())
+}
+
+
+fn check_result(result: &ImportResult) -> Result<(), KintoError> {
+    match result.errors.first() {
+        Some(error) => Err(KintoError::CopyError(error.uri.clone())),
+        None => Ok(()),
+    }
+}
+
+
+/// Check that a copy has the same metadata and records as its source.
+///
+/// Permissions are not compared: the server adds the current user to the
+/// writers of the created objects.
+fn verify_copy(source: &Collection, target: &Collection) -> Result<(), KintoError> {
+    let diff = try!(diff(source, target));
+    let same_metadata = diff.metadata.iter().all(|change| change.path == "/id");
+    if !same_metadata || !diff.added.is_empty() || !diff.removed.is_empty() ||
+       !diff.changed.is_empty() {
+        return Err(KintoError::CopyError(try!(source.uri())));
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod test_copy {
+    use resource::Resource;
+    use bucket::Bucket;
+    use group::Group;
+    use permissions::{Permissions, CollectionPermission, RecordPermission};
+    use utils::tests::setup_bucket;
+    use error::KintoError;
+    use super::{CopyOptions, bucket_stamps, check_unchanged, collection_stamps, release,
+                reserve};
+
+    const COOKS: &str = "/buckets/food/groups/cooks";
+
+    fn fill_bucket() -> Bucket {
+        let mut bucket = setup_bucket();
+        bucket.data = json!({"title": "Food"}).into();
+        bucket.set().unwrap();
+
+        let mut group: Group = bucket.group("cooks");
+        group.set_members(vec!["account:alice".to_owned()]);
+        group.set().unwrap();
+
+        let mut collection = bucket.clone().collection("meat");
+        collection.data = json!({"title": "Meat"}).into();
+        collection.permissions.grant(CollectionPermission::Read, COOKS);
+        collection.set().unwrap();
+        for id in &["entrecote", "onglet"] {
+            let mut record = collection.record(id);
+            record.data = json!({"title": id}).into();
+            record.permissions.grant(RecordPermission::Read, "account:alice");
+            record.set().unwrap();
+        }
+        bucket
+    }
+
+    #[test]
+    fn test_copy_collection() {
+        let bucket = fill_bucket();
+        let mut other = bucket.get_client().bucket("archives");
+        other.set().unwrap();
+
+        let source = bucket.clone().collection("meat");
+        let result = source.copy_to(&other, "meat-2017", &CopyOptions::default())
+            .unwrap();
+        assert!(result.ok());
+        assert_eq!(result.imported, 3);
+
+        let mut copy = other.collection("meat-2017");
+        copy.load().unwrap();
+        assert_eq!(copy.data.clone().unwrap()["title"], "Meat");
+        assert!(copy.permissions.has(CollectionPermission::Read, COOKS));
+        let mut record = copy.record("onglet");
+        record.load().unwrap();
+        assert_eq!(record.data.unwrap()["title"], "onglet");
+        assert!(record.permissions.has(RecordPermission::Read, "account:alice"));
+    }
+
+    #[test]
+    fn test_copy_collection_without_permissions() {
+        let bucket = fill_bucket();
+        let source = bucket.clone().collection("meat");
+        let options = CopyOptions { permissions: false, ..CopyOptions::default() };
+        source.copy_to(&bucket, "beef", &options).unwrap();
+
+        let mut record = bucket.clone().collection("beef").record("onglet");
+        record.load().unwrap();
+        assert!(!record.permissions.has(RecordPermission::Read, "account:alice"));
+    }
+
+    #[test]
+    fn test_copy_bucket() {
+        let bucket = fill_bucket();
+        let result = bucket.copy_to("food-copy").unwrap();
+        assert!(result.ok());
+
+        let copy = bucket.get_client().bucket("food-copy");
+        let mut group = copy.group("cooks");
+        group.load().unwrap();
+        assert_eq!(group.members(), vec!["account:alice".to_owned()]);
+        let mut collection = copy.collection("meat");
+        collection.load().unwrap();
+        assert!(collection.permissions
+                    .has(CollectionPermission::Read, "/buckets/food-copy/groups/cooks"));
+        assert_eq!(collection.list_records().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_rename_collection() {
+        let bucket = fill_bucket();
+        let renamed = bucket.clone().collection("meat").rename("beef").unwrap();
+        assert_eq!(renamed.get_id(), Some("beef"));
+        assert_eq!(renamed.list_records().unwrap().len(), 2);
+        bucket.clone().collection("meat").load().unwrap_err();
+    }
+
+    #[test]
+    fn test_rename_bucket() {
+        let bucket = fill_bucket();
+        let renamed = bucket.rename("groceries").unwrap();
+        let collections = renamed.list_collections().unwrap();
+        assert_eq!(collections[0].get_id(), Some("meat"));
+        assert_eq!(collections[0].list_records().unwrap().len(), 2);
+        bucket.clone().load().unwrap_err();
+    }
+
+    #[test]
+    fn test_rename_checks_the_copy() {
+        let bucket = fill_bucket();
+        match bucket.clone().collection("meat").rename("meat") {
+            Err(KintoError::CopyError(uri)) => {
+                assert_eq!(uri, "/buckets/food/collections/meat")
+            }
+            _ => panic!("the collection was renamed to itself"),
+        };
+
+        // An existing different target is not overwritten
+        let mut target = bucket.clone().collection("beef");
+        target.set().unwrap();
+        target.record("bavette").set().unwrap();
+        match bucket.clone().collection("meat").rename("beef") {
+            Err(KintoError::CopyError(_)) => (),
+            _ => panic!("the copy was not checked"),
+        };
+        bucket.clone().collection("meat").load().unwrap();
+        let records = target.list_records().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].get_id(), Some("bavette"));
+
+        let mut other = bucket.get_client().bucket("groceries");
+        other.set().unwrap();
+        match bucket.rename("groceries") {
+            Err(KintoError::CopyError(uri)) => assert_eq!(uri, "/buckets/groceries"),
+            _ => panic!("the bucket was renamed to an existing one"),
+        };
+        assert!(other.list_collections().unwrap().is_empty());
+        bucket.clone().load().unwrap();
+    }
+
+    #[test]
+    fn test_rename_detects_changes() {
+        let bucket = fill_bucket();
+        let source = bucket.clone().collection("meat");
+        let stamps = collection_stamps(&source).unwrap();
+        let bucket_before = bucket_stamps(&bucket).unwrap();
+        source.record("bavette").set().unwrap();
+        check_unchanged(&source, &stamps, &collection_stamps(&source).unwrap())
+            .unwrap_err();
+        check_unchanged(&bucket, &bucket_before, &bucket_stamps(&bucket).unwrap())
+            .unwrap_err();
+
+        let stamps = bucket_stamps(&bucket).unwrap();
+        bucket.group("tasters").set().unwrap();
+        check_unchanged(&bucket, &stamps, &bucket_stamps(&bucket).unwrap()).unwrap_err();
+    }
+
+    #[test]
+    fn test_failed_rename_releases_the_target() {
+        let bucket = fill_bucket();
+        let mut target = bucket.clone().collection("beef");
+        reserve(&mut target).unwrap();
+        let error = KintoError::CopyError("/buckets/food/collections/meat".to_owned());
+        release(target, Err(error)).unwrap_err();
+
+        // The rename can be retried
+        let renamed = bucket.clone().collection("meat").rename("beef").unwrap();
+        assert_eq!(renamed.list_records().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,492 @@
+use std::str;
+
+use serde_json::Value;
+
+use KintoClient;
+use error::KintoError;
+use request::{DeleteRecord, KintoRequest, PluralEndpoint};
+use resource::Resource;
+use batch::send_batches;
+use bucket::Bucket;
+use collection::Collection;
+
+
+/// Objects whose children are deleted.
+#[derive(Debug, Clone)]
+enum Parent {
+    Server(KintoClient),
+    Bucket(Bucket),
+    Collection(Box<Collection>),
+}
+
+
+/// Number of objects removed, at each level.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeleteCounts {
+    pub buckets: usize,
+    pub groups: usize,
+    pub collections: usize,
+    pub records: usize,
+}
+
+
+/// What a plan would delete.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeletePreview {
+    /// Ids of the matching buckets, collections or records.
+    pub ids: Vec<String>,
+    /// Matching objects, with their children.
+    pub counts: DeleteCounts,
+}
+
+
+/// Object the server refused to delete.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeleteError {
+    pub id: String,
+    pub status: u16,
+    pub details: Value,
+}
+
+
+/// What a plan deleted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeleteReport {
+    /// Ids of the deleted buckets, collections or records.
+    pub deleted: Vec<String>,
+    /// Objects that could not be deleted.
+    pub errors: Vec<DeleteError>,
+    /// Matching objects with their children, as counted before the deletion.
+    pub counts: DeleteCounts,
+}
+
+
+impl DeleteReport {
+    /// Whether all the objects were deleted.
+    pub fn ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+
+/// Deletion of the buckets of a server, the collections of a bucket or the
+/// records of a collection, optionally filtered.
+///
+/// Nothing is deleted unless the plan was confirmed, or guarded by the id
+/// of the bucket or collection that is emptied.
+#[derive(Debug, Clone)]
+pub struct DeletePlan {
+    parent: Parent,
+    filters: Vec<(String, String)>,
+    before: Option<u64>,
+    confirmed: bool,
+    expected_id: Option<String>,
+}
+
+
+impl DeletePlan {
+    fn new(parent: Parent) -> DeletePlan {
+        DeletePlan {
+            parent: parent,
+            filters: vec![],
+            before: None,
+            confirmed: false,
+            expected_id: None,
+        }
+    }
+
+    /// Plan the deletion of the buckets of a server.
+    pub fn buckets(client: &KintoClient) -> DeletePlan {
+        DeletePlan::new(Parent::Server(client.clone()))
+    }
+
+    /// Plan the deletion of the collections of a bucket.
+    pub fn collections(bucket: &Bucket) -> DeletePlan {
+        DeletePlan::new(Parent::Bucket(bucket.clone()))
+    }
+
+    /// Plan the deletion of the records of a collection.
+    pub fn records(collection: &Collection) -> DeletePlan {
+        DeletePlan::new(Parent::Collection(Box::new(collection.clone())))
+    }
+
+    /// Only delete the objects with a field value (e.g. `filter("status", "draft")`).
+    pub fn filter(&mut self, field: &str, value: &str) -> &mut Self {
+        self.filters.push((field.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Only delete the objects modified before the given timestamp.
+    pub fn before(&mut self, timestamp: u64) -> &mut Self {
+        self.before = Some(timestamp);
+        self
+    }
+
+    /// Allow the deletion.
+    pub fn confirm(&mut self) -> &mut Self {
+        self.confirmed = true;
+        self
+    }
+
+    /// Allow the deletion if the emptied bucket or collection has this id.
+    ///
+    /// When deleting buckets, the plan must match this single bucket.
+    pub fn expect_id(&mut self, id: &str) -> &mut Self {
+        self.expected_id = Some(id.to_owned());
+        self
+    }
+
+    /// List and count what would be deleted, without deleting anything.
+    pub fn preview(&self) -> Result<DeletePreview, KintoError> {
+        let mut counts = DeleteCounts::default();
+        let ids = match self.parent {
+            Parent::Server(ref client) => {
+                let ids = try!(self.matching_ids(&client.new_bucket()));
+                counts.buckets = ids.len();
+                for id in &ids {
+                    try!(count_bucket(&client.bucket(id), &mut counts));
+                }
+                ids
+            }
+            Parent::Bucket(ref bucket) => {
+                let ids = try!(self.matching_ids(&bucket.new_collection()));
+                counts.collections = ids.len();
+                for id in &ids {
+                    let collection = bucket.clone().collection(id);
+                    counts.records += try!(count(&collection.new_record()));
+                }
+                ids
+            }
+            Parent::Collection(ref collection) => {
+                let ids = try!(self.matching_ids(&collection.new_record()));
+                counts.records = ids.len();
+                ids
+            }
+        };
+        Ok(DeletePreview {
+               ids: ids,
+               counts: counts,
+           })
+    }
+
+    /// Delete the matching objects, if the plan was confirmed or its guard
+    /// matches.
+    pub fn execute(&self) -> Result<DeleteReport, KintoError> {
+        self.execute_preview(&try!(self.preview()))
+    }
+
+    /// Delete the objects of a preview of this plan, in batches.
+    ///
+    /// Objects created since the preview are kept, and the ones already
+    /// deleted are not reported. The objects the server refuses to delete
+    /// are reported with the error, the others are still deleted.
+    pub fn execute_preview(&self,
+                           preview: &DeletePreview)
+                           -> Result<DeleteReport, KintoError> {
+        if !self.allowed(preview) {
+            return Err(KintoError::DeletionNotConfirmed);
+        }
+
+        let mut requests = vec![];
+        for id in &preview.ids {
+            requests.push(try!(self.delete_request(id)));
+        }
+        let responses = try!(send_batches(&self.client(), requests));
+        let mut report = DeleteReport {
+            counts: preview.counts.clone(),
+            ..DeleteReport::default()
+        };
+        for (id, response) in preview.ids.iter().zip(responses) {
+            let status = response.status.to_u16();
+            if response.status.is_success() {
+                report.deleted.push(id.to_owned());
+            } else if status != 404 {
+                report.errors.push(DeleteError {
+                                       id: id.to_owned(),
+                                       status: status,
+                                       details: response.body,
+                                   });
+            }
+        }
+        Ok(report)
+    }
+
+    fn client(&self) -> KintoClient {
+        match self.parent {
+            Parent::Server(ref client) => client.clone(),
+            Parent::Bucket(ref bucket) => bucket.get_client(),
+            Parent::Collection(ref collection) => collection.get_client(),
+        }
+    }
+
+    fn delete_request(&self, id: &str) -> Result<DeleteRecord, KintoError> {
+        match self.parent {
+            Parent::Server(ref client) => client.bucket(id).delete_request(),
+            Parent::Bucket(ref bucket) => bucket.clone().collection(id).delete_request(),
+            Parent::Collection(ref collection) => collection.record(id).delete_request(),
+        }
+    }
+
+    fn allowed(&self, preview: &DeletePreview) -> bool {
+        if self.confirmed {
+            return true;
+        }
+        match self.expected_id {
+            Some(ref id) => {
+                match self.parent {
+                    Parent::Server(_) => preview.ids == vec![id.to_owned()],
+                    Parent::Bucket(ref bucket) => bucket.get_id() == Some(id),
+                    Parent::Collection(ref collection) => collection.get_id() == Some(id),
+                }
+            }
+            None => false,
+        }
+    }
+
+    fn apply_filters<R>(&self, request: &mut R)
+        where R: PluralEndpoint
+    {
+        for (field, value) in &self.filters {
+            request.filter(field, value);
+        }
+        match self.before {
+            Some(timestamp) => {
+                request.before(timestamp);
+            }
+            None => (),
+        };
+    }
+
+    fn matching_ids<T>(&self, resource: &T) -> Result<Vec<String>, KintoError>
+        where T: Resource
+    {
+        let mut request = try!(resource.list_request());
+        self.apply_filters(&mut request);
+        Ok(ids(&try!(request.follow_subrequests()).body["data"]))
+    }
+}
+
+
+/// Count the groups, collections and records of a bucket.
+fn count_bucket(bucket: &Bucket, counts: &mut DeleteCounts) -> Result<(), KintoError> {
+    counts.groups += try!(count(&bucket.new_group()));
+    let collections = ids(&try!(try!(bucket.new_collection().list_request())
+                                    .follow_subrequests())
+                                   .body["data"]);
+    counts.collections += collections.len();
+    for id in &collections {
+        counts.records += try!(count(&bucket.clone().collection(id).new_record()));
+    }
+    Ok(())
+}
+
+
+/// Count the objects of a plural endpoint, from the total the server sends
+/// with the first one.
+fn count<T>(resource: &T) -> Result<usize, KintoError>
+    where T: Resource
+{
+    let response = try!(try!(resource.list_request()).limit(1).send());
+    let total = response.headers
+        .get_raw("total-records")
+        .and_then(|values| str::from_utf8(values[0].as_slice()).ok())
+        .and_then(|value| value.trim().parse().ok());
+    match total {
+        Some(total) => Ok(total),
+        None => {
+            let response = try!(try!(resource.list_request()).follow_subrequests());
+            Ok(response.body["data"].as_array().map(Vec::len).unwrap_or(0))
+        }
+    }
+}
+
+
+fn ids(objects: &Value) -> Vec<String> {
+    objects.as_array()
+        .map(|objects| {
+                 objects.iter()
+                     .filter_map(|object| object["id"].as_str())
+                     .map(str::to_owned)
+                     .collect()
+             })
+        .unwrap_or_default()
+}
+
+
+#[cfg(test)]
+mod test_deletion {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+
+    use KintoClient;
+    use error::KintoError;
+    use resource::Resource;
+    use collection::Collection;
+    use utils::tests::setup_client;
+    use super::{DeleteCounts, DeleteError, DeletePlan, DeletePreview};
+
+    fn fill_server() -> KintoClient {
+        let client = setup_client();
+        for bucket_id in &["staging", "prod"] {
+            let mut bucket = client.bucket(bucket_id);
+            bucket.set().unwrap();
+            bucket.group("cooks").set().unwrap();
+            let mut collection = bucket.collection("meat");
+            collection.set().unwrap();
+            for id in &["entrecote", "onglet", "bavette"] {
+                let kind = if *id == "onglet" { "beef" } else { "veal" };
+                let mut record = collection.record(id);
+                record.data = json!({"kind": kind}).into();
+                record.set().unwrap();
+            }
+        }
+        client
+    }
+
+    fn count_records(collection: &Collection) -> usize {
+        collection.list_records().unwrap().len()
+    }
+
+    #[test]
+    fn test_preview_counts() {
+        let client = fill_server();
+        let preview = DeletePlan::buckets(&client).preview().unwrap();
+        assert_eq!(preview.counts,
+                   DeleteCounts {
+                       buckets: 2,
+                       groups: 2,
+                       collections: 2,
+                       records: 6,
+                   });
+
+        let preview = DeletePlan::collections(&client.bucket("prod")).preview().unwrap();
+        assert_eq!(preview.ids, vec!["meat".to_owned()]);
+        assert_eq!((preview.counts.collections, preview.counts.records), (1, 3));
+
+        let collection = client.bucket("prod").collection("meat");
+        let preview = DeletePlan::records(&collection)
+            .filter("kind", "beef")
+            .preview()
+            .unwrap();
+        assert_eq!(preview.ids, vec!["onglet".to_owned()]);
+        assert_eq!(count_records(&collection), 3);
+    }
+
+    #[test]
+    fn test_execute_needs_confirmation() {
+        let client = fill_server();
+        let collection = client.bucket("prod").collection("meat");
+
+        match DeletePlan::records(&collection).execute() {
+            Err(KintoError::DeletionNotConfirmed) => (),
+            _ => panic!("the deletion was not confirmed"),
+        };
+        match DeletePlan::records(&collection).expect_id("staging").execute() {
+            Err(KintoError::DeletionNotConfirmed) => (),
+            _ => panic!("the guard does not match"),
+        };
+        match DeletePlan::buckets(&client).expect_id("staging").execute() {
+            Err(KintoError::DeletionNotConfirmed) => (),
+            _ => panic!("the guard matches a single bucket"),
+        };
+        assert_eq!(count_records(&collection), 3);
+
+        let report = DeletePlan::records(&collection)
+            .expect_id("meat")
+            .execute()
+            .unwrap();
+        assert_eq!(report.deleted.len(), 3);
+        assert_eq!(report.counts.records, 3);
+        assert_eq!(count_records(&collection), 0);
+    }
+
+    #[test]
+    fn test_execute_filtered() {
+        let client = fill_server();
+        let mut plan = DeletePlan::buckets(&client);
+        plan.filter("id", "staging").expect_id("staging");
+        let report = plan.execute().unwrap();
+        assert_eq!(report.deleted, vec!["staging".to_owned()]);
+        assert_eq!(report.counts.records, 3);
+        client.bucket("staging").load().unwrap_err();
+        client.bucket("prod").load().unwrap();
+    }
+
+    #[test]
+    fn test_execute_before() {
+        let client = fill_server();
+        let collection = client.bucket("prod").collection("meat");
+        let mut record = collection.record("bavette");
+        record.load().unwrap();
+
+        let mut plan = DeletePlan::records(&collection);
+        plan.before(record.get_timestamp().unwrap()).confirm();
+        let report = plan.execute().unwrap();
+        assert_eq!(report.deleted.len(), 2);
+        record.load().unwrap();
+    }
+
+    #[test]
+    fn test_execute_preview() {
+        let client = fill_server();
+        let collection = client.bucket("prod").collection("meat");
+        let mut plan = DeletePlan::records(&collection);
+        plan.confirm();
+        let preview = plan.preview().unwrap();
+
+        // Created after the preview
+        collection.record("onglet-2").set().unwrap();
+        let mut gone = collection.record(&preview.ids[0]);
+        gone.delete().unwrap();
+
+        let report = plan.execute_preview(&preview).unwrap();
+        assert_eq!(report.deleted, preview.ids[1..].to_vec());
+        assert_eq!(count_records(&collection), 1);
+        collection.record("onglet-2").load().unwrap();
+    }
+
+    #[test]
+    fn test_execute_reports_errors() {
+        let body = json!({"responses": [{
+            "status": 200,
+            "path": "/v1/buckets/food/collections/meat/records/entrecote",
+            "body": {"data": {"id": "entrecote", "deleted": true}},
+        }, {
+            "status": 403,
+            "path": "/v1/buckets/food/collections/meat/records/onglet",
+            "body": {"code": 403, "message": "forbidden"},
+        }]})
+            .to_string();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request).unwrap();
+            let response = format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+                                    Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                                   body.len(),
+                                   body);
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+
+        let client = KintoClient::new(url, None).unwrap();
+        let collection = client.bucket("food").collection("meat");
+        let preview = DeletePreview {
+            ids: vec!["entrecote".to_owned(), "onglet".to_owned()],
+            counts: DeleteCounts::default(),
+        };
+        let mut plan = DeletePlan::records(&collection);
+        let report = plan.confirm().execute_preview(&preview).unwrap();
+        assert!(!report.ok());
+        assert_eq!(report.deleted, vec!["entrecote".to_owned()]);
+        assert_eq!(report.errors,
+                   vec![DeleteError {
+                            id: "onglet".to_owned(),
+                            status: 403,
+                            details: json!({"code": 403, "message": "forbidden"}),
+                        }]);
+        server.join().unwrap();
+    }
+}
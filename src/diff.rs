@@ -0,0 +1,320 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use serde_json;
+use serde_json::Value;
+
+use error::KintoError;
+use resource::Resource;
+use collection::Collection;
+use conflict::{escape_pointer, without_timestamp};
+
+
+/// State of a collection at some point in time, kept to be compared later.
+///
+/// Timestamps are left out, so that copies on different servers compare
+/// equal.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollectionSnapshot {
+    pub metadata: Value,
+    /// Principals by permission, sorted.
+    pub permissions: BTreeMap<String, Vec<String>>,
+    /// Records data by id.
+    pub records: BTreeMap<String, Value>,
+}
+
+
+impl CollectionSnapshot {
+    /// Fetch the current state of a collection.
+    pub fn fetch(collection: &Collection) -> Result<CollectionSnapshot, KintoError> {
+        let mut collection = collection.clone();
+        try!(collection.load());
+
+        let permissions = try!(serde_json::to_value(&collection.permissions));
+        let mut permissions: BTreeMap<String, Vec<String>> =
+            try!(serde_json::from_value(permissions));
+        for principals in permissions.values_mut() {
+            principals.sort();
+        }
+
+        let mut records = BTreeMap::new();
+        for record in try!(collection.list_records()) {
+            let id = try!(record.get_id().ok_or(KintoError::UndefinedIdError)).to_owned();
+            let data = record.data.unwrap_or(json!({}));
+            records.insert(id, without_timestamp(&data));
+        }
+
+        Ok(CollectionSnapshot {
+               metadata: without_timestamp(&collection.data.unwrap_or(json!({}))),
+               permissions: permissions,
+               records: records,
+           })
+    }
+}
+
+
+/// Difference of a field between two versions of an object.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FieldChange {
+    /// JSON pointer of the field (e.g. `/author/name`), empty for the whole object.
+    pub path: String,
+    /// Previous value, `None` if the field was added.
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub before: Option<Value>,
+    /// New value, `None` if the field was removed.
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub after: Option<Value>,
+}
+
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = if self.path.is_empty() { "/" } else { self.path.as_str() };
+        match (self.before.as_ref(), self.after.as_ref()) {
+            (None, Some(after)) => write!(f, "+ {}: {}", path, after),
+            (Some(before), None) => write!(f, "- {}: {}", path, before),
+            (Some(before), Some(after)) => {
+                write!(f, "~ {}: {} -> {}", path, before, after)
+            }
+            (None, None) => write!(f, "~ {}", path),
+        }
+    }
+}
+
+
+/// Record present on both sides with different data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordChange {
+    pub id: String,
+    pub fields: Vec<FieldChange>,
+}
+
+
+/// Differences between two collections, from the first to the second.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollectionDiff {
+    /// Records only in the second collection.
+    pub added: Vec<Value>,
+    /// Records only in the first collection.
+    pub removed: Vec<Value>,
+    pub changed: Vec<RecordChange>,
+    pub metadata: Vec<FieldChange>,
+    pub permissions: Vec<FieldChange>,
+}
+
+
+impl CollectionDiff {
+    /// Whether both collections are the same.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty() &&
+        self.metadata.is_empty() && self.permissions.is_empty()
+    }
+
+    /// Get the JSON rendering of the differences.
+    pub fn to_value(&self) -> Result<Value, KintoError> {
+        Ok(try!(serde_json::to_value(self)))
+    }
+}
+
+
+impl fmt::Display for CollectionDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+        for change in &self.metadata {
+            try!(writeln!(f, "metadata {}", change));
+        }
+        for change in &self.permissions {
+            try!(writeln!(f, "permissions {}", change));
+        }
+        for record in &self.added {
+            try!(writeln!(f, "+ record {}", record["id"].as_str().unwrap_or("")));
+        }
+        for record in &self.removed {
+            try!(writeln!(f, "- record {}", record["id"].as_str().unwrap_or("")));
+        }
+        for record in &self.changed {
+            try!(writeln!(f, "~ record {}", record.id));
+            for change in &record.fields {
+                try!(writeln!(f, "    {}", change));
+            }
+        }
+        Ok(())
+    }
+}
+
+
+/// Compare two collections, e.g. the same one on two servers.
+pub fn diff(a: &Collection, b: &Collection) -> Result<CollectionDiff, KintoError> {
+    Ok(diff_snapshots(&try!(CollectionSnapshot::fetch(a)),
+                      &try!(CollectionSnapshot::fetch(b))))
+}
+
+
+/// Compare two states of collections.
+pub fn diff_snapshots(a: &CollectionSnapshot, b: &CollectionSnapshot) -> CollectionDiff {
+    let mut diff = CollectionDiff::default();
+
+    field_changes(String::new(),
+                  Some(&a.metadata),
+                  Some(&b.metadata),
+                  &mut diff.metadata);
+
+    let permissions: BTreeSet<&String> =
+        a.permissions.keys().chain(b.permissions.keys()).collect();
+    for permission in permissions {
+        let before = a.permissions.get(permission).map(|principals| json!(principals));
+        let after = b.permissions.get(permission).map(|principals| json!(principals));
+        field_changes(format!("/{}", escape_pointer(permission)),
+                      before.as_ref(),
+                      after.as_ref(),
+                      &mut diff.permissions);
+    }
+
+    for (id, data) in &a.records {
+        match b.records.get(id) {
+            Some(other) => {
+                let mut fields = vec![];
+                field_changes(String::new(), Some(data), Some(other), &mut fields);
+                if !fields.is_empty() {
+                    diff.changed.push(RecordChange {
+                                          id: id.to_owned(),
+                                          fields: fields,
+                                      });
+                }
+            }
+            None => diff.removed.push(data.clone()),
+        };
+    }
+    for (id, data) in &b.records {
+        if !a.records.contains_key(id) {
+            diff.added.push(data.clone());
+        }
+    }
+    diff
+}
+
+
+/// List the changed fields of a value, `None` meaning absent.
+fn field_changes(path: String,
+                 before: Option<&Value>,
+                 after: Option<&Value>,
+                 changes: &mut Vec<FieldChange>) {
+    if before == after {
+        return;
+    }
+    match (before.and_then(Value::as_object), after.and_then(Value::as_object)) {
+        (Some(before), Some(after)) => {
+            let keys: BTreeSet<&String> = before.keys().chain(after.keys()).collect();
+            for key in keys {
+                field_changes(format!("{}/{}", path, escape_pointer(key)),
+                              before.get(key),
+                              after.get(key),
+                              changes);
+            }
+        }
+        _ => {
+            changes.push(FieldChange {
+                             path: path,
+                             before: before.cloned(),
+                             after: after.cloned(),
+                         })
+        }
+    };
+}
+
+
+#[cfg(test)]
+mod test_diff {
+    use resource::Resource;
+    use collection::Collection;
+    use permissions::{Permissions, CollectionPermission};
+    use utils::tests::setup_client;
+    use super::{CollectionSnapshot, FieldChange, diff, diff_snapshots};
+
+    fn setup_collections() -> (Collection, Collection) {
+        let client = setup_client();
+        let mut collections = vec![];
+        for bucket_id in &["staging", "prod"] {
+            let mut bucket = client.bucket(bucket_id);
+            bucket.set().unwrap();
+            let mut collection = bucket.collection("meat");
+            collection.data = json!({"title": "Meat"}).into();
+            collection.set().unwrap();
+            for id in &["entrecote", "onglet"] {
+                let mut record = collection.record(id);
+                record.data = json!({"title": id, "price": {"EUR": 5}}).into();
+                record.set().unwrap();
+            }
+            collections.push(collection);
+        }
+        let prod = collections.pop().unwrap();
+        (collections.pop().unwrap(), prod)
+    }
+
+    #[test]
+    fn test_diff_same_collections() {
+        let (staging, prod) = setup_collections();
+        let diff = diff(&staging, &prod).unwrap();
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "No differences\n");
+    }
+
+    #[test]
+    fn test_diff_collections() {
+        let (mut staging, prod) = setup_collections();
+        staging.data = json!({"title": "Beef"}).into();
+        staging.permissions.grant(CollectionPermission::Read, "system.Everyone");
+        staging.set().unwrap();
+
+        let mut record = staging.record("onglet");
+        record.data = json!({"title": "onglet", "price": {"EUR": 6}, "tags": []}).into();
+        record.set().unwrap();
+        staging.record("entrecote").delete().unwrap();
+        let mut record = staging.record("bavette");
+        record.data = json!({"title": "bavette"}).into();
+        record.set().unwrap();
+
+        let diff = diff(&prod, &staging).unwrap();
+        assert_eq!(diff.added, vec![json!({"id": "bavette", "title": "bavette"})]);
+        assert_eq!(diff.removed[0]["id"], "entrecote");
+        assert_eq!(diff.changed[0].id, "onglet");
+        assert_eq!(diff.changed[0].fields,
+                   vec![FieldChange {
+                            path: "/price/EUR".to_owned(),
+                            before: Some(json!(5)),
+                            after: Some(json!(6)),
+                        },
+                        FieldChange {
+                            path: "/tags".to_owned(),
+                            before: None,
+                            after: Some(json!([])),
+                        }]);
+        assert_eq!(diff.metadata[0].path, "/title");
+        assert_eq!(diff.permissions[0].path, "/read");
+
+        let text = diff.to_string();
+        assert!(text.contains("metadata ~ /title: \"Meat\" -> \"Beef\"\n"));
+        assert!(text.contains("permissions + /read: [\"system.Everyone\"]\n"));
+        assert!(text.contains("+ record bavette\n- record entrecote\n"));
+        assert!(text.contains("~ record onglet\n    ~ /price/EUR: 5 -> 6\n"));
+        assert!(text.contains("    + /tags: []\n"));
+
+        let value = diff.to_value().unwrap();
+        assert_eq!(value["changed"][0]["fields"][1],
+                   json!({"path": "/tags", "after": []}));
+    }
+
+    #[test]
+    fn test_diff_points_in_time() {
+        let (staging, _) = setup_collections();
+        let before = CollectionSnapshot::fetch(&staging).unwrap();
+        staging.record("onglet").delete().unwrap();
+        let after = CollectionSnapshot::fetch(&staging).unwrap();
+
+        let diff = diff_snapshots(&before, &after);
+        assert_eq!(diff.removed.len(), 1);
+        assert!(diff_snapshots(&after, &after).is_empty());
+    }
+}
@@ -4,15 +4,41 @@ extern crate serde;
 use std::io::Error as IOError;
 use std::str::Utf8Error;
 use hyper::Error as HyperError;
+use serde_json::Value;
 use serde_json::error::Error as JsonError;
 
+use schema::SchemaError;
+
 
 #[derive(Debug)]
 pub enum KintoError {
     NotModified,
     PreconditionError,
     UndefinedIdError,
+    /// The id does not follow the Kinto ids rules.
+    InvalidIdError(String),
+    /// The server URL cannot be parsed or used as a base URL.
+    UrlError,
+    /// The URI does not designate a Kinto object.
+    InvalidUriError(String),
     UnavailableEndpointError,
+    UndefinedAttachmentError,
+    IntegrityError,
+    SignatureError,
+    StoreError,
+    TransformError,
+    ValidationError(Vec<SchemaError>),
+    /// The copy of the object (URI) failed or differs from it.
+    CopyError(String),
+    /// A deletion plan was neither confirmed nor guarded by a matching id.
+    DeletionNotConfirmed,
+    /// The server storage quota was reached (kinto-quotas plugin).
+    QuotaExceeded,
+    /// The archive was written with an unknown format version.
+    ArchiveVersionError(u64),
+    /// The server answered with an error status, with the error body (e.g.
+    /// `{"code": 404, "error": "Not Found", "message": ...}`).
+    HttpError(u16, Value),
     HyperError,
     JsonError,
     IOError,
@@ -20,28 +46,28 @@ pub enum KintoError {
 
 
 impl From<IOError> for KintoError {
-    fn from(err: IOError) -> Self {
-        err.into()
+    fn from(_: IOError) -> Self {
+        KintoError::IOError
     }
 }
 
 
 impl From<Utf8Error> for KintoError {
-    fn from(err: Utf8Error) -> Self {
-        err.into()
+    fn from(_: Utf8Error) -> Self {
+        KintoError::JsonError
     }
 }
 
 
 impl From<JsonError> for KintoError {
-    fn from(err: JsonError) -> Self {
-        err.into()
+    fn from(_: JsonError) -> Self {
+        KintoError::JsonError
     }
 }
 
 
 impl From<HyperError> for KintoError {
-    fn from(err: HyperError) -> Self {
-        err.into()
+    fn from(_: HyperError) -> Self {
+        KintoError::HyperError
     }
 }
@@ -0,0 +1,171 @@
+use serde_json;
+use serde_json::Value;
+
+use KintoClient;
+use error::KintoError;
+use response::ResponseWrapper;
+use resource::Resource;
+use bucket::Bucket;
+use permissions::{Permissions, GroupPermission};
+
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GroupPermissions {
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub read: Option<Vec<String>>,
+    #[serde(skip_serializing_if="Option::is_none")]
+    pub write: Option<Vec<String>>,
+}
+
+
+impl Permissions for GroupPermissions {
+    type Permission = GroupPermission;
+
+    fn principals(&self, perm: GroupPermission) -> &Option<Vec<String>> {
+        match perm {
+            GroupPermission::Read => &self.read,
+            GroupPermission::Write => &self.write,
+        }
+    }
+
+    fn principals_mut(&mut self, perm: GroupPermission) -> &mut Option<Vec<String>> {
+        match perm {
+            GroupPermission::Read => &mut self.read,
+            GroupPermission::Write => &mut self.write,
+        }
+    }
+}
+
+
+#[derive(Debug, Clone, Default)]
+pub struct Group {
+    pub data: Option<Value>,
+    pub permissions: GroupPermissions,
+    pub bucket: Bucket,
+    pub id: Option<String>,
+}
+
+
+impl Group {
+    /// Create a new group resource.
+    pub fn new(bucket: Bucket) -> Self {
+        Group {
+            bucket: bucket,
+            id: None,
+            data: None,
+            permissions: GroupPermissions::default(),
+        }
+    }
+
+    /// Create a new group resource.
+    pub fn new_by_id<'a>(bucket: Bucket, id: &'a str) -> Self {
+        Group {
+            bucket: bucket,
+            id: Some(id.to_owned()),
+            data: None,
+            permissions: GroupPermissions::default(),
+        }
+    }
+
+    /// Get the principals of the (loaded) group members.
+    pub fn members(&self) -> Vec<String> {
+        let data = self.data.clone().unwrap_or_default();
+        serde_json::from_value(data["members"].to_owned()).unwrap_or_default()
+    }
+
+    /// Replace the group members, saved by `set()`.
+    pub fn set_members(&mut self, members: Vec<String>) -> &mut Self {
+        let mut data = self.data.clone().unwrap_or(json!({}));
+        data["members"] = members.into();
+        self.data = Some(data);
+        self
+    }
+}
+
+
+impl Resource for Group {
+    fn resource_path(&self) -> Result<String, KintoError> {
+        Ok(format!("{}/groups", try!(self.bucket.record_path())))
+    }
+
+    fn unwrap_response(&mut self, wrapper: ResponseWrapper) -> Result<(), KintoError> {
+        self.data = Some(wrapper.body["data"].to_owned());
+        self.permissions = serde_json::from_value(wrapper.body["permissions"].to_owned())
+            .unwrap_or_default();
+        self.id = Some(wrapper.body["data"]["id"].as_str().unwrap().to_owned());
+        Ok(())
+    }
+
+    fn get_client(&self) -> KintoClient {
+        self.bucket.get_client()
+    }
+
+    fn get_id(&self) -> Option<&str> {
+        match self.id.as_ref() {
+            Some(id) => return Some(id),
+            None => (),
+        };
+
+        match self.data.as_ref() {
+            Some(data) => return data["id"].as_str(),
+            None => (),
+        };
+
+        return None;
+    }
+
+    fn get_timestamp(&self) -> Option<u64> {
+        match self.get_data() {
+            Some(data) => data["last_modified"].as_u64(),
+            None => None,
+        }
+    }
+
+    fn get_data(&self) -> Option<Value> {
+        return self.data.clone();
+    }
+
+    fn set_data(&mut self, data: Value) -> Self {
+        self.data = data.into();
+        return self.clone();
+    }
+
+    fn get_permissions(&self) -> Option<Value> {
+        serde_json::to_value(&(self.permissions)).unwrap_or_default().into()
+    }
+}
+
+
+#[cfg(test)]
+mod test_group {
+    use resource::Resource;
+    use permissions::Principal;
+    use utils::tests::setup_bucket;
+
+    #[test]
+    fn test_set_group_members() {
+        let mut bucket = setup_bucket();
+        bucket.set().unwrap();
+
+        let mut group = bucket.group("cooks");
+        group.set_members(vec!["account:alice".to_owned()]);
+        group.set().unwrap();
+
+        let mut loaded = bucket.group("cooks");
+        loaded.load().unwrap();
+        assert_eq!(loaded.members(), vec!["account:alice"]);
+
+        let groups = bucket.list_groups().unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].get_id().unwrap(), "cooks");
+    }
+
+    #[test]
+    fn test_group_principal() {
+        let bucket = setup_bucket();
+        let group = bucket.group("cooks");
+        let principal: String = Principal::Group("food".to_owned(), "cooks".to_owned())
+            .into();
+        assert_eq!(group.record_path().unwrap(), principal);
+    }
+}
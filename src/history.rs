@@ -0,0 +1,162 @@
+use serde_json;
+use serde_json::Value;
+use hyper::method::Method;
+
+use KintoClient;
+use error::KintoError;
+use request::{RequestPreparer, KintoRequest, PluralEndpoint};
+use response::ResponseWrapper;
+use resource::Resource;
+
+
+/// State of the object after the change.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistoryTarget {
+    #[serde(default)]
+    pub data: Value,
+    #[serde(default)]
+    pub permissions: Value,
+}
+
+
+/// Entry of the history plugin audit trail.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub id: String,
+    pub last_modified: u64,
+    pub action: String,
+    pub resource_name: String,
+    pub uri: String,
+    pub user_id: String,
+    #[serde(default)]
+    pub bucket_id: Option<String>,
+    #[serde(default)]
+    pub collection_id: Option<String>,
+    #[serde(default)]
+    pub group_id: Option<String>,
+    #[serde(default)]
+    pub record_id: Option<String>,
+    #[serde(default)]
+    pub target: HistoryTarget,
+}
+
+
+/// Get request on the bucket history endpoint.
+#[derive(Debug, Clone)]
+pub struct GetHistory {
+    pub preparer: RequestPreparer,
+}
+
+impl GetHistory {
+    pub fn new(client: KintoClient, path: String) -> GetHistory {
+        let mut preparer = RequestPreparer::new(client, path);
+        preparer.method = Method::Get;
+        GetHistory { preparer: preparer }
+    }
+
+    /// Only get the entries of the given object.
+    pub fn history_for<T>(&mut self, resource: &T) -> Result<&mut Self, KintoError>
+        where T: Resource
+    {
+        let uri = try!(resource.record_path());
+        Ok(self.filter("uri", uri.as_str()))
+    }
+
+    /// Only get the entries of the given resource type (e.g. `record`).
+    pub fn resource_name(&mut self, name: &str) -> &mut Self {
+        self.filter("resource_name", name)
+    }
+
+    /// Only get the entries of the given action (e.g. `delete`).
+    pub fn action(&mut self, action: &str) -> &mut Self {
+        self.filter("action", action)
+    }
+
+    /// Only get the entries of the given user id.
+    pub fn user_id(&mut self, user_id: &str) -> &mut Self {
+        self.filter("user_id", user_id)
+    }
+
+    /// Fetch all pages of the matching entries.
+    pub fn entries(&mut self) -> Result<Vec<HistoryEntry>, KintoError> {
+        let response = try!(self.follow_subrequests());
+        unwrap_history_entries(response)
+    }
+}
+
+impl KintoRequest for GetHistory {
+    fn preparer(&mut self) -> &mut RequestPreparer {
+        &mut self.preparer
+    }
+}
+
+impl PluralEndpoint for GetHistory {}
+
+
+/// Get the history entries from a response.
+pub fn unwrap_history_entries(wrapper: ResponseWrapper)
+                              -> Result<Vec<HistoryEntry>, KintoError> {
+    Ok(try!(serde_json::from_value(wrapper.body["data"].to_owned())))
+}
+
+
+#[cfg(test)]
+mod test_history {
+    use KintoClient;
+    use resource::Resource;
+    use request::PluralEndpoint;
+    use utils::tests::setup_collection;
+    use super::GetHistory;
+
+    #[test]
+    fn test_bucket_history() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        let entries = collection.bucket.history().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].resource_name, "collection");
+        assert_eq!(entries[0].action, "create");
+    }
+
+    #[test]
+    fn test_history_for_resource() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        collection.record("entrecote").create().unwrap();
+        let entries = collection.bucket
+            .history_request()
+            .unwrap()
+            .history_for(&collection)
+            .unwrap()
+            .entries()
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].uri, "/buckets/food/collections/meat");
+    }
+
+    #[test]
+    fn test_filters_are_encoded() {
+        let client = KintoClient::new("http://localhost:8888/v1".to_owned(), None)
+            .unwrap();
+        let mut request = GetHistory::new(client, "/buckets/food/history".to_owned());
+        request.filter("target.data.title", "steak & fries #2+1");
+        assert_eq!(request.preparer.query,
+                   "&target.data.title=steak%20%26%20fries%20%232%2B1");
+    }
+
+    #[test]
+    fn test_history_since() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        let timestamp = collection.bucket.history().unwrap()[0].last_modified;
+        collection.record("entrecote").create().unwrap();
+        let entries = collection.bucket
+            .history_request()
+            .unwrap()
+            .since(timestamp)
+            .entries()
+            .unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].resource_name, "record");
+    }
+}
@@ -0,0 +1,72 @@
+use std::fmt;
+
+use error::KintoError;
+use utils::uuid4;
+
+
+/// Check that an id follows the Kinto ids rules (alphanumeric characters,
+/// `-` and `_`, not starting with `-` or `_`).
+pub fn validate_id(id: &str) -> Result<(), KintoError> {
+    let mut chars = id.chars();
+    let valid = match chars.next() {
+        Some(first) => {
+            first.is_ascii_alphanumeric() &&
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        }
+        None => false,
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(KintoError::InvalidIdError(id.to_owned()))
+    }
+}
+
+
+/// Generate the ids of new records on the client side.
+///
+/// Creating records with a known id lets them be sent with `PUT` and
+/// `If-None-Match: *`, so a retried creation cannot duplicate a record.
+pub trait IdGenerator: fmt::Debug + Send + Sync {
+    fn generate(&self) -> String;
+}
+
+
+/// Generate random (version 4) UUIDs, like the server does.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidGenerator;
+
+
+impl IdGenerator for UuidGenerator {
+    fn generate(&self) -> String {
+        uuid4()
+    }
+}
+
+
+#[cfg(test)]
+mod test_ids {
+    use error::KintoError;
+    use super::{IdGenerator, UuidGenerator, validate_id};
+
+    #[test]
+    fn test_validate_id() {
+        validate_id("food").unwrap();
+        validate_id("Food_2-b").unwrap();
+        validate_id(&UuidGenerator.generate()).unwrap();
+        for id in &["", "-food", "_food", "fo od", "food/meat", "é", "../food"] {
+            match validate_id(id) {
+                Err(KintoError::InvalidIdError(invalid)) => assert_eq!(&invalid, id),
+                _ => panic!("{} should be invalid", id),
+            }
+        }
+    }
+
+    #[test]
+    fn test_uuid_generator() {
+        let id = UuidGenerator.generate();
+        assert_eq!(id.len(), 36);
+        assert_eq!(&id[14..15], "4");
+        assert!(UuidGenerator.generate() != id);
+    }
+}
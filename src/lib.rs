@@ -1,5 +1,9 @@
 extern crate hyper;
 extern crate hyper_native_tls;
+extern crate openssl;
+extern crate regex;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
@@ -13,10 +17,32 @@ pub mod request;
 pub mod response;
 pub mod resource;
 pub mod batch;
+pub mod permissions;
+pub mod ids;
+pub mod schema;
+pub mod metadata;
 
 pub mod bucket;
 pub mod collection;
 pub mod record;
+pub mod group;
+pub mod history;
+pub mod attachment;
+pub mod signer;
+pub mod content_signature;
+pub mod changes;
+pub mod watcher;
+pub mod transformer;
+pub mod conflict;
+pub mod sync;
+pub mod archive;
+pub mod replication;
+pub mod diff;
+pub mod copy;
+pub mod deletion;
+pub mod quota;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
 
 pub mod utils;
 
@@ -26,6 +52,8 @@ pub use client::KintoClient;
 pub use bucket::Bucket;
 pub use collection::Collection;
 pub use record::Record;
+pub use group::Group;
 
 pub use request::KintoRequest;
 pub use resource::Resource;
+pub use permissions::{Permissions, Principal};
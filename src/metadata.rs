@@ -0,0 +1,216 @@
+use serde_json;
+use serde_json::Value;
+use serde_json::map::Map;
+
+use error::KintoError;
+
+
+/// Fields of `CollectionMetadata` that are not part of `extra`.
+const COLLECTION_FIELDS: [&str; 9] = ["id",
+                                      "last_modified",
+                                      "schema",
+                                      "uiSchema",
+                                      "cache_expires",
+                                      "displayFields",
+                                      "sort",
+                                      "attachment",
+                                      "id_generator"];
+
+/// Fields of `BucketMetadata` that are not part of `extra`.
+const BUCKET_FIELDS: [&str; 2] = ["id", "last_modified"];
+
+
+/// Attachments settings of a collection (kinto-attachment plugin).
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AttachmentSettings {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub required: bool,
+}
+
+
+/// Well-known metadata of a collection.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CollectionMetadata {
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub last_modified: Option<u64>,
+    /// JSON schema of the records.
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub schema: Option<Value>,
+    /// Form rendering hints of the records (kinto-admin).
+    #[serde(default, rename="uiSchema", skip_serializing_if="Option::is_none")]
+    pub ui_schema: Option<Value>,
+    /// Cache duration of the records, in seconds.
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub cache_expires: Option<u64>,
+    /// Fields shown in the records list (kinto-admin).
+    #[serde(default, rename="displayFields", skip_serializing_if="Option::is_none")]
+    pub display_fields: Option<Vec<String>>,
+    /// Default records order (e.g. `-last_modified`).
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub sort: Option<String>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub attachment: Option<AttachmentSettings>,
+    /// Scheme of the records ids.
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub id_generator: Option<String>,
+    /// Other metadata fields.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub extra: Map<String, Value>,
+}
+
+
+impl CollectionMetadata {
+    /// Parse the metadata from the collection data.
+    pub fn from_value(data: Value) -> Result<CollectionMetadata, KintoError> {
+        let mut metadata: CollectionMetadata = try!(serde_json::from_value(data.clone()));
+        metadata.extra = extra_fields(data, &COLLECTION_FIELDS);
+        Ok(metadata)
+    }
+
+    /// Get the collection data of these metadata.
+    pub fn to_value(&self) -> Result<Value, KintoError> {
+        Ok(with_extra(try!(serde_json::to_value(self)), &self.extra))
+    }
+}
+
+
+/// Metadata of a bucket.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BucketMetadata {
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub id: Option<String>,
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub last_modified: Option<u64>,
+    /// Other metadata fields.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub extra: Map<String, Value>,
+}
+
+
+impl BucketMetadata {
+    /// Parse the metadata from the bucket data.
+    pub fn from_value(data: Value) -> Result<BucketMetadata, KintoError> {
+        let mut metadata: BucketMetadata = try!(serde_json::from_value(data.clone()));
+        metadata.extra = extra_fields(data, &BUCKET_FIELDS);
+        Ok(metadata)
+    }
+
+    /// Get the bucket data of these metadata.
+    pub fn to_value(&self) -> Result<Value, KintoError> {
+        Ok(with_extra(try!(serde_json::to_value(self)), &self.extra))
+    }
+}
+
+
+/// Get the fields of an object that are not in `known`.
+fn extra_fields(data: Value, known: &[&str]) -> Map<String, Value> {
+    match data {
+        Value::Object(map) => {
+            map.into_iter()
+                .filter(|(field, _)| !known.contains(&field.as_str()))
+                .collect()
+        }
+        _ => Map::new(),
+    }
+}
+
+
+fn with_extra(data: Value, extra: &Map<String, Value>) -> Value {
+    let mut data = data;
+    for (field, value) in extra {
+        data[field.as_str()] = value.clone();
+    }
+    data
+}
+
+
+#[cfg(test)]
+mod test_metadata {
+    use resource::Resource;
+    use utils::tests::setup_collection;
+    use super::{AttachmentSettings, BucketMetadata, CollectionMetadata};
+
+    #[test]
+    fn test_collection_metadata_roundtrip() {
+        let data = json!({
+            "id": "meat",
+            "last_modified": 42,
+            "schema": {"type": "object"},
+            "uiSchema": {"ui:order": ["title"]},
+            "cache_expires": 3600,
+            "displayFields": ["title"],
+            "sort": "-last_modified",
+            "attachment": {"enabled": true, "required": false},
+            "status": "signed"
+        });
+        let metadata = CollectionMetadata::from_value(data.clone()).unwrap();
+        assert_eq!(metadata.cache_expires, Some(3600));
+        assert_eq!(metadata.display_fields, Some(vec!["title".to_owned()]));
+        assert_eq!(metadata.attachment,
+                   Some(AttachmentSettings {
+                            enabled: true,
+                            required: false,
+                        }));
+        assert_eq!(metadata.extra["status"], "signed");
+        assert_eq!(metadata.to_value().unwrap(), data);
+    }
+
+    #[test]
+    fn test_invalid_metadata() {
+        CollectionMetadata::from_value(json!({"cache_expires": "soon"})).unwrap_err();
+    }
+
+    #[test]
+    fn test_bucket_metadata_extra() {
+        let metadata = BucketMetadata::from_value(json!({"id": "food", "title": "Food"}))
+            .unwrap();
+        assert_eq!(metadata.id.unwrap(), "food");
+        assert_eq!(metadata.extra["title"], "Food");
+    }
+
+    #[test]
+    fn test_collection_set_metadata() {
+        let mut collection = setup_collection();
+        let mut metadata = CollectionMetadata {
+            cache_expires: Some(60),
+            schema: Some(json!({"type": "object"})),
+            ..CollectionMetadata::default()
+        };
+        metadata.extra.insert("title".to_owned(), "Meat".into());
+        collection.save_metadata(metadata).unwrap();
+
+        let mut loaded = collection.bucket.collection("meat");
+        let metadata = loaded.load_metadata().unwrap();
+        assert_eq!(metadata.cache_expires, Some(60));
+        assert_eq!(metadata.schema.unwrap()["type"], "object");
+        assert_eq!(metadata.extra["title"], "Meat");
+    }
+
+    #[test]
+    fn test_bucket_save_metadata() {
+        let mut bucket = setup_collection().bucket;
+        let mut metadata = BucketMetadata::default();
+        metadata.extra.insert("title".to_owned(), "Food".into());
+        bucket.save_metadata(metadata).unwrap();
+
+        let mut loaded = bucket.get_client().bucket("food");
+        assert_eq!(loaded.load_metadata().unwrap().extra["title"], "Food");
+    }
+
+    #[test]
+    fn test_validator_ignores_invalid_metadata() {
+        let mut collection = setup_collection();
+        collection.data = json!({"cache_expires": "soon",
+                                 "schema": {"type": "object", "required": ["title"]}})
+            .into();
+        collection.create().unwrap();
+        collection.metadata().unwrap_err();
+
+        let validator = collection.validator().unwrap();
+        validator.validate(&json!({})).unwrap_err();
+    }
+}
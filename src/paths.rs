@@ -1,4 +1,5 @@
 use std::convert::Into;
+use std::fmt::Write;
 
 
 /// Known paths in the kinto server.
@@ -12,6 +13,11 @@ pub enum Paths<'a> {
     Collection(&'a str, &'a str),
     Records(&'a str, &'a str),
     Record(&'a str, &'a str, &'a str),
+    History(&'a str),
+    Attachment(&'a str, &'a str, &'a str),
+    Changes,
+    BucketQuota(&'a str),
+    CollectionQuota(&'a str, &'a str),
 }
 
 
@@ -20,42 +26,94 @@ impl<'a> Into<String> for Paths<'a> {
         match self {
             Paths::Batch => format!("/batch"),
             Paths::Buckets => format!("/buckets"),
-            Paths::Bucket(id) => format!("/buckets/{id}", id = id),
+            Paths::Bucket(id) => format!("/buckets/{id}", id = encode_segment(id)),
             Paths::Groups(bucket_id) => {
-                format!("/buckets/{bucket_id}/groups", bucket_id = bucket_id)
+                format!("/buckets/{bucket_id}/groups",
+                        bucket_id = encode_segment(bucket_id))
             }
             Paths::Group(bucket_id, id) => {
                 format!("/buckets/{bucket_id}/groups/{id}",
-                        bucket_id = bucket_id,
-                        id = id)
+                        bucket_id = encode_segment(bucket_id),
+                        id = encode_segment(id))
             }
             Paths::Collections(bucket_id) => {
-                format!("/buckets/{bucket_id}/collections", bucket_id = bucket_id)
+                format!("/buckets/{bucket_id}/collections",
+                        bucket_id = encode_segment(bucket_id))
             }
             Paths::Collection(bucket_id, id) => {
                 format!("/buckets/{bucket_id}/collections/{id}",
-                        bucket_id = bucket_id,
-                        id = id)
+                        bucket_id = encode_segment(bucket_id),
+                        id = encode_segment(id))
             }
             Paths::Records(bucket_id, collection_id) => {
                 format!("/buckets/{bucket_id}/collections/{collection_id}/records",
-                        bucket_id = bucket_id,
-                        collection_id = collection_id)
+                        bucket_id = encode_segment(bucket_id),
+                        collection_id = encode_segment(collection_id))
             }
             Paths::Record(bucket_id, collection_id, id) => {
                 format!("/buckets/{bucket_id}/collections/{collection_id}/records/{id}",
-                        bucket_id = bucket_id,
-                        collection_id = collection_id,
-                        id = id)
+                        bucket_id = encode_segment(bucket_id),
+                        collection_id = encode_segment(collection_id),
+                        id = encode_segment(id))
             }
+            Paths::History(bucket_id) => {
+                format!("/buckets/{bucket_id}/history",
+                        bucket_id = encode_segment(bucket_id))
+            }
+            Paths::Changes => format!("/buckets/monitor/collections/changes/records"),
+            Paths::Attachment(bucket_id, collection_id, id) => {
+                format!("/buckets/{bucket_id}/collections/{collection_id}/records/{id}\
+                         /attachment",
+                        bucket_id = encode_segment(bucket_id),
+                        collection_id = encode_segment(collection_id),
+                        id = encode_segment(id))
+            }
+            Paths::BucketQuota(bucket_id) => {
+                format!("/buckets/{bucket_id}/quota/bucket_info",
+                        bucket_id = encode_segment(bucket_id))
+            }
+            Paths::CollectionQuota(bucket_id, collection_id) => {
+                format!("/buckets/{bucket_id}/collections/{collection_id}/quota\
+                         /collection_info",
+                        bucket_id = encode_segment(bucket_id),
+                        collection_id = encode_segment(collection_id))
+            }
+        }
+    }
+}
+
+
+/// Percent-encode a path segment (e.g. an object id).
+pub fn encode_segment(segment: &str) -> String {
+    let mut encoded = String::with_capacity(segment.len());
+    for byte in segment.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => write!(encoded, "%{:02X}", byte).unwrap(),
         }
     }
+    encoded
 }
 
 
 #[cfg(test)]
 mod test_paths {
-    use super::Paths;
+    use super::{Paths, encode_segment};
+
+    #[test]
+    fn test_encode_segment() {
+        assert_eq!(encode_segment("entrecote"), "entrecote");
+        assert_eq!(encode_segment("a b/c?"), "a%20b%2Fc%3F");
+        assert_eq!(encode_segment("côte"), "c%C3%B4te");
+    }
+
+    #[test]
+    fn test_encoded_record_path() {
+        let path: String = Paths::Record("food", "meat", "côte/2").into();
+        assert_eq!(path, "/buckets/food/collections/meat/records/c%C3%B4te%2F2");
+    }
 
     #[test]
     fn test_batch_path() {
@@ -110,4 +168,35 @@ mod test_paths {
         let path: String = Paths::Record("food", "meat", "entrecote").into();
         assert_eq!(path, "/buckets/food/collections/meat/records/entrecote");
     }
+
+    #[test]
+    fn test_history_path() {
+        let path: String = Paths::History("food").into();
+        assert_eq!(path, "/buckets/food/history");
+    }
+
+    #[test]
+    fn test_changes_path() {
+        let path: String = Paths::Changes.into();
+        assert_eq!(path, "/buckets/monitor/collections/changes/records");
+    }
+
+    #[test]
+    fn test_bucket_quota_path() {
+        let path: String = Paths::BucketQuota("food").into();
+        assert_eq!(path, "/buckets/food/quota/bucket_info");
+    }
+
+    #[test]
+    fn test_collection_quota_path() {
+        let path: String = Paths::CollectionQuota("food", "meat").into();
+        assert_eq!(path, "/buckets/food/collections/meat/quota/collection_info");
+    }
+
+    #[test]
+    fn test_attachment_path() {
+        let path: String = Paths::Attachment("food", "meat", "entrecote").into();
+        assert_eq!(path,
+                   "/buckets/food/collections/meat/records/entrecote/attachment");
+    }
 }
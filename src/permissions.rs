@@ -0,0 +1,206 @@
+use std::fmt;
+use std::convert::Into;
+
+use paths::Paths;
+
+
+/// Principals that can be granted permissions on a Kinto object.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Principal {
+    /// Anyone, authenticated or not (`system.Everyone`).
+    Everyone,
+    /// Any authenticated user (`system.Authenticated`).
+    Authenticated,
+    /// A user of the accounts plugin (`account:<id>`).
+    Account(String),
+    /// Members of a bucket group (`/buckets/<bucket>/groups/<id>`).
+    Group(String, String),
+    /// A raw user id, as returned by the server (e.g. `basicauth:abc...`).
+    User(String),
+}
+
+
+impl<'a> From<&'a str> for Principal {
+    fn from(principal: &'a str) -> Self {
+        match principal {
+            "system.Everyone" => return Principal::Everyone,
+            "system.Authenticated" => return Principal::Authenticated,
+            _ => (),
+        };
+
+        if principal.starts_with("account:") {
+            return Principal::Account(principal["account:".len()..].to_owned());
+        }
+
+        // Group principals are the group uri.
+        let split: Vec<&str> = principal.split("/").collect();
+        if split.len() == 5 && split[0].is_empty() && split[1] == "buckets" &&
+           split[3] == "groups" {
+            return Principal::Group(split[2].to_owned(), split[4].to_owned());
+        }
+
+        Principal::User(principal.to_owned())
+    }
+}
+
+
+impl Into<String> for Principal {
+    fn into(self) -> String {
+        match self {
+            Principal::Everyone => "system.Everyone".to_owned(),
+            Principal::Authenticated => "system.Authenticated".to_owned(),
+            Principal::Account(id) => format!("account:{}", id),
+            Principal::Group(bucket_id, id) => Paths::Group(&bucket_id, &id).into(),
+            Principal::User(id) => id,
+        }
+    }
+}
+
+
+impl fmt::Display for Principal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let principal: String = self.clone().into();
+        write!(f, "{}", principal)
+    }
+}
+
+
+/// Permissions available on buckets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BucketPermission {
+    Read,
+    Write,
+    CreateCollection,
+    CreateGroup,
+}
+
+
+/// Permissions available on collections.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CollectionPermission {
+    Read,
+    Write,
+    CreateRecord,
+}
+
+
+/// Permissions available on groups.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GroupPermission {
+    Read,
+    Write,
+}
+
+
+/// Permissions available on records.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RecordPermission {
+    Read,
+    Write,
+}
+
+
+/// Shared behaviour of the resources permissions objects.
+pub trait Permissions {
+    /// Permissions available for the resource.
+    type Permission: Copy;
+
+    /// Get the principals list for a permission.
+    fn principals(&self, perm: Self::Permission) -> &Option<Vec<String>>;
+
+    /// Get the mutable principals list for a permission.
+    fn principals_mut(&mut self, perm: Self::Permission) -> &mut Option<Vec<String>>;
+
+    /// Grant a permission to a principal.
+    fn grant<P>(&mut self, perm: Self::Permission, principal: P) -> &mut Self
+        where P: Into<Principal>
+    {
+        let principal: String = principal.into().into();
+        let principals = self.principals_mut(perm).get_or_insert(vec![]);
+        if !principals.contains(&principal) {
+            principals.push(principal);
+        }
+        self
+    }
+
+    /// Revoke a permission from a principal.
+    fn revoke<P>(&mut self, perm: Self::Permission, principal: P) -> &mut Self
+        where P: Into<Principal>
+    {
+        let principal: String = principal.into().into();
+        match self.principals_mut(perm).as_mut() {
+            Some(principals) => principals.retain(|p| *p != principal),
+            None => (),
+        };
+        self
+    }
+
+    /// Check if a principal was granted a permission.
+    fn has<P>(&self, perm: Self::Permission, principal: P) -> bool
+        where P: Into<Principal>
+    {
+        let principal: String = principal.into().into();
+        match self.principals(perm).as_ref() {
+            Some(principals) => principals.contains(&principal),
+            None => false,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod test_permissions {
+    use super::{Principal, Permissions, BucketPermission};
+    use bucket::BucketPermissions;
+
+    #[test]
+    fn test_principal_to_string() {
+        let everyone: String = Principal::Everyone.into();
+        let authenticated: String = Principal::Authenticated.into();
+        let account: String = Principal::Account("bob".to_owned()).into();
+        let group: String = Principal::Group("food".to_owned(), "cooks".to_owned())
+            .into();
+        assert_eq!(everyone, "system.Everyone");
+        assert_eq!(authenticated, "system.Authenticated");
+        assert_eq!(account, "account:bob");
+        assert_eq!(group, "/buckets/food/groups/cooks");
+    }
+
+    #[test]
+    fn test_principal_from_str() {
+        assert_eq!(Principal::from("system.Everyone"), Principal::Everyone);
+        assert_eq!(Principal::from("system.Authenticated"),
+                   Principal::Authenticated);
+        assert_eq!(Principal::from("account:bob"),
+                   Principal::Account("bob".to_owned()));
+        assert_eq!(Principal::from("/buckets/food/groups/cooks"),
+                   Principal::Group("food".to_owned(), "cooks".to_owned()));
+        assert_eq!(Principal::from("basicauth:abc"),
+                   Principal::User("basicauth:abc".to_owned()));
+    }
+
+    #[test]
+    fn test_grant_permission() {
+        let mut perms = BucketPermissions::default();
+        perms.grant(BucketPermission::Read, Principal::Everyone);
+        perms.grant(BucketPermission::Read, Principal::Everyone);
+        assert_eq!(perms.read.unwrap(), vec!["system.Everyone"]);
+    }
+
+    #[test]
+    fn test_revoke_permission() {
+        let mut perms = BucketPermissions::default();
+        perms.grant(BucketPermission::CreateGroup, "account:bob");
+        perms.revoke(BucketPermission::CreateGroup, "account:bob");
+        assert_eq!(perms.create_group.unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_has_permission() {
+        let mut perms = BucketPermissions::default();
+        perms.grant(BucketPermission::Write, Principal::Authenticated);
+        assert!(perms.has(BucketPermission::Write, Principal::Authenticated));
+        assert!(!perms.has(BucketPermission::Read, Principal::Authenticated));
+        assert!(!perms.has(BucketPermission::Write, Principal::Everyone));
+    }
+}
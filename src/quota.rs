@@ -0,0 +1,143 @@
+use serde_json;
+
+use KintoClient;
+use error::KintoError;
+use request::{KintoRequest, GetRecord};
+
+
+/// Usage of a bucket or collection, from the quota info records of the
+/// kinto-quotas plugin.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct QuotaInfo {
+    #[serde(default)]
+    pub record_count: u64,
+    /// Size of the stored objects, in bytes.
+    #[serde(default)]
+    pub storage_size: u64,
+    /// Number of collections, for buckets only.
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub collection_count: Option<u64>,
+}
+
+
+/// Get a quota info record, if the plugin is enabled on the server.
+pub fn fetch_quota(client: &KintoClient, path: String) -> Result<QuotaInfo, KintoError> {
+    if try!(client.capabilities())["quotas"].is_null() {
+        return Err(KintoError::UnavailableEndpointError);
+    }
+    let response = try!(GetRecord::new(client.clone(), path).send());
+    Ok(try!(serde_json::from_value(response.body["data"].to_owned())))
+}
+
+
+#[cfg(test)]
+mod test_quota {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use serde_json;
+    use KintoClient;
+    use error::KintoError;
+    use resource::Resource;
+    use utils::tests::setup_bucket;
+    use super::QuotaInfo;
+
+    #[test]
+    fn test_quota_info() {
+        let bucket: QuotaInfo = serde_json::from_value(json!({
+            "id": "bucket_info",
+            "record_count": 12,
+            "storage_size": 2048,
+            "collection_count": 2,
+        }))
+            .unwrap();
+        assert_eq!(bucket.collection_count, Some(2));
+        assert_eq!(bucket.storage_size, 2048);
+
+        let data = json!({"record_count": 3, "storage_size": 512});
+        let collection: QuotaInfo = serde_json::from_value(data).unwrap();
+        assert_eq!(collection.collection_count, None);
+        assert_eq!(serde_json::to_value(&collection).unwrap(),
+                   json!({"record_count": 3, "storage_size": 512}));
+    }
+
+    #[test]
+    fn test_bucket_quota() {
+        let mut bucket = setup_bucket();
+        bucket.set().unwrap();
+        let mut collection = bucket.new_collection();
+        collection.set().unwrap();
+        collection.new_record().set().unwrap();
+
+        let quota = bucket.quota().unwrap();
+        assert_eq!(quota.collection_count, Some(1));
+        assert_eq!(quota.record_count, 1);
+        assert!(quota.storage_size > 0);
+
+        let quota = collection.quota().unwrap();
+        assert_eq!(quota.collection_count, None);
+        assert_eq!(quota.record_count, 1);
+        assert!(quota.storage_size > 0);
+    }
+
+    #[test]
+    fn test_missing_bucket_quota() {
+        let bucket = setup_bucket();
+        match bucket.get_client().bucket("missing").quota() {
+            Err(KintoError::HttpError(_, _)) => (),
+            result => panic!("{:?}", result),
+        };
+    }
+
+    #[test]
+    fn test_quota_capability() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request).unwrap();
+            stream.write_all(b"HTTP/1.1 200 OK\r\n\
+                               Content-Type: application/json\r\n\
+                               Content-Length: 19\r\n\
+                               Connection: close\r\n\r\n\
+                               {\"capabilities\":{}}")
+                .unwrap();
+        });
+
+        // The capabilities are only fetched once
+        let client = KintoClient::new(url, None).unwrap();
+        for _ in 0..2 {
+            match client.bucket("food").quota() {
+                Err(KintoError::UnavailableEndpointError) => (),
+                result => panic!("{:?}", result),
+            };
+        }
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_quota_exceeded() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 4096];
+            let _ = stream.read(&mut request).unwrap();
+            stream.write_all(b"HTTP/1.1 507 Insufficient Storage\r\n\
+                               Content-Type: application/json\r\n\
+                               Content-Length: 2\r\n\
+                               Connection: close\r\n\r\n{}")
+                .unwrap();
+        });
+
+        let client = KintoClient::new(url, None).unwrap();
+        let mut record = client.bucket("food").collection("meat").record("entrecote");
+        record.data = json!({"title": "entrecote"}).into();
+        match record.set() {
+            Err(KintoError::QuotaExceeded) => (),
+            result => panic!("{:?}", result),
+        };
+        server.join().unwrap();
+    }
+}
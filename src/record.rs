@@ -1,11 +1,21 @@
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::Path;
+
 use serde_json;
 use serde_json::Value;
 
 use KintoClient;
 use error::KintoError;
+use paths::Paths;
+use request::{KintoRequest, DeleteRecord};
 use response::ResponseWrapper;
 use resource::Resource;
+use transformer::TransformerPipeline;
 use collection::Collection;
+use attachment;
+use attachment::{AttachmentInfo, UploadAttachment};
+use permissions::{Permissions, RecordPermission};
 
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
@@ -17,12 +27,33 @@ pub struct RecordPermissions {
 }
 
 
+impl Permissions for RecordPermissions {
+    type Permission = RecordPermission;
+
+    fn principals(&self, perm: RecordPermission) -> &Option<Vec<String>> {
+        match perm {
+            RecordPermission::Read => &self.read,
+            RecordPermission::Write => &self.write,
+        }
+    }
+
+    fn principals_mut(&mut self, perm: RecordPermission) -> &mut Option<Vec<String>> {
+        match perm {
+            RecordPermission::Read => &mut self.read,
+            RecordPermission::Write => &mut self.write,
+        }
+    }
+}
+
+
 #[derive(Debug, Clone, Default)]
 pub struct Record {
     pub data: Option<Value>,
     pub permissions: RecordPermissions,
     pub collection: Collection,
     pub id: Option<String>,
+    /// Id generated by a creation that got no response yet.
+    generated_id: Option<String>,
 }
 
 
@@ -34,6 +65,7 @@ impl Record {
             data: None,
             permissions: RecordPermissions::default(),
             id: None,
+            generated_id: None,
         }
     }
 
@@ -44,8 +76,95 @@ impl Record {
             data: None,
             permissions: RecordPermissions::default(),
             id: Some(id.to_owned()),
+            generated_id: None,
         }
     }
+
+    /// Get the record attachment path (kinto-attachment plugin).
+    pub fn attachment_path(&self) -> Result<String, KintoError> {
+        let bucket_id = try!(self.collection
+                                 .bucket
+                                 .get_id()
+                                 .ok_or(KintoError::UndefinedIdError));
+        let collection_id = try!(self.collection
+                                     .get_id()
+                                     .ok_or(KintoError::UndefinedIdError));
+        let id = try!(self.get_id().ok_or(KintoError::UndefinedIdError));
+        Ok(Paths::Attachment(bucket_id, collection_id, id).into())
+    }
+
+    /// Get the record attachment metadata if any.
+    pub fn attachment(&self) -> Option<AttachmentInfo> {
+        match self.data.as_ref() {
+            Some(data) => serde_json::from_value(data["attachment"].to_owned()).ok(),
+            None => None,
+        }
+    }
+
+    /// Upload a file as the record attachment, creating the record if needed.
+    ///
+    /// Attachments are stored as given: records of a collection with
+    /// transformers cannot have one.
+    pub fn attach<R>(&mut self,
+                     mut reader: R,
+                     mimetype: &str,
+                     filename: &str)
+                     -> Result<(), KintoError>
+        where R: Read
+    {
+        if !self.transformers().is_empty() {
+            return Err(KintoError::TransformError);
+        }
+        let mut content = vec![];
+        try!(reader.read_to_end(&mut content));
+
+        // The attachment field is managed by the server
+        let data = match self.get_data() {
+            Some(mut data) => {
+                match data.as_object_mut() {
+                    Some(data) => {
+                        data.remove("id");
+                        data.remove("last_modified");
+                        data.remove("attachment");
+                    }
+                    None => (),
+                };
+                Some(data)
+            }
+            None => None,
+        };
+
+        try!(UploadAttachment::new(self.get_client(), try!(self.attachment_path()))
+                 .form(content.as_slice(), mimetype, filename, data)
+                 .send());
+        self.load()
+    }
+
+    /// Upload a local file as the record attachment.
+    pub fn attach_file<P>(&mut self, path: P, mimetype: &str) -> Result<(), KintoError>
+        where P: AsRef<Path>
+    {
+        let filename = match path.as_ref().file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => return Err(KintoError::UndefinedAttachmentError),
+        };
+        let file = try!(File::open(path.as_ref()));
+        self.attach(file, mimetype, filename.as_str())
+    }
+
+    /// Remove the record attachment.
+    pub fn detach(&mut self) -> Result<(), KintoError> {
+        try!(DeleteRecord::new(self.get_client(), try!(self.attachment_path())).send());
+        self.load()
+    }
+
+    /// Download the record attachment, checking its size and hash.
+    pub fn download_attachment<W>(&self, writer: &mut W) -> Result<(), KintoError>
+        where W: Write
+    {
+        let info = try!(self.attachment().ok_or(KintoError::UndefinedAttachmentError));
+        attachment::download(&self.get_client(), &info, writer)
+    }
 }
 
 
@@ -54,11 +173,46 @@ impl Resource for Record {
         Ok(format!("{}/records", try!(self.collection.record_path())))
     }
 
-    fn unwrap_response(&mut self, wrapper: ResponseWrapper) {
-        self.data = Some(wrapper.body["data"].to_owned());
-        self.permissions = serde_json::from_value(wrapper.body["permissions"].to_owned())
-            .unwrap();
+    fn unwrap_response(&mut self, wrapper: ResponseWrapper) -> Result<(), KintoError> {
         self.id = Some(wrapper.body["data"]["id"].as_str().unwrap().to_owned());
+        self.generated_id = None;
+        let data = try!(self.transformers()
+                            .decode(&try!(self.uri()), wrapper.body["data"].to_owned()));
+        self.data = Some(data);
+        self.permissions = serde_json::from_value(wrapper.body["permissions"].to_owned())
+            .unwrap_or_default();
+        Ok(())
+    }
+
+    fn transformers(&self) -> TransformerPipeline {
+        self.collection.transformers.clone()
+    }
+
+    fn generate_id(&self) -> Option<String> {
+        Some(self.collection.generate_id())
+    }
+
+    fn set_generated_id(&mut self, id: &str) {
+        self.generated_id = Some(id.to_owned());
+    }
+
+    fn has_generated_id(&self) -> bool {
+        match self.generated_id {
+            Some(ref id) => self.get_id() == Some(id.as_str()),
+            None => false,
+        }
+    }
+
+    fn validates(&self) -> bool {
+        self.collection.record_validator.is_some()
+    }
+
+    fn validate(&self) -> Result<(), KintoError> {
+        let data = self.data.clone().unwrap_or(json!({}));
+        match self.collection.record_validator.as_ref() {
+            Some(validator) => validator.validate(&data),
+            None => Ok(()),
+        }
     }
 
     fn get_client(&self) -> KintoClient {
@@ -95,7 +249,7 @@ impl Resource for Record {
     fn get_timestamp(&self) -> Option<u64> {
         match self.get_data() {
             Some(data) => {
-                match data["lat_modified"].as_u64() {
+                match data["last_modified"].as_u64() {
                     Some(ts) => ts.into(),
                     None => None,
                 }
@@ -108,7 +262,11 @@ impl Resource for Record {
 
 #[cfg(test)]
 mod test_record {
+    use error::KintoError;
     use resource::Resource;
+    use conflict::{Manual, Resolution, ThreeWayMerge};
+    use ids::IdGenerator;
+    use schema::Validator;
     use utils::tests::{setup_record, setup_collection};
 
     #[test]
@@ -174,4 +332,128 @@ mod test_record {
         let mut record = client.record("food");
         record.update().unwrap_err();
     }
+
+    #[derive(Debug)]
+    struct PrefixGenerator;
+
+    impl IdGenerator for PrefixGenerator {
+        fn generate(&self) -> String {
+            "meat-1".to_owned()
+        }
+    }
+
+    #[test]
+    fn test_create_record_generates_id() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+
+        let mut record = collection.new_record();
+        record.create().unwrap();
+        assert_eq!(record.get_id().unwrap().len(), 36);
+
+        collection.set_id_generator(PrefixGenerator);
+        let mut record = collection.new_record();
+        record.set().unwrap();
+        assert_eq!(record.get_id().unwrap(), "meat-1");
+    }
+
+    #[test]
+    fn test_retried_create_does_not_duplicate() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+
+        let mut record = collection.new_record();
+        record.data = json!({"title": "beef"}).into();
+        record.create().unwrap();
+        match record.create() {
+            Err(KintoError::PreconditionError) => (),
+            result => panic!("Unexpected result {:?}", result),
+        };
+        assert_eq!(collection.list_records().unwrap().len(), 1);
+
+        // The response of the first attempt was lost
+        let id = record.id.clone().unwrap();
+        let mut retried = collection.new_record();
+        retried.data = json!({"id": id, "title": "beef"}).into();
+        retried.set_generated_id(&id);
+        retried.create().unwrap();
+        assert_eq!(retried.id, record.id);
+        assert!(!retried.has_generated_id());
+        assert_eq!(collection.list_records().unwrap().len(), 1);
+
+        let mut changed = collection.new_record();
+        changed.data = json!({"id": id, "title": "lamb"}).into();
+        changed.set_generated_id(&id);
+        match changed.create() {
+            Err(KintoError::PreconditionError) => (),
+            result => panic!("Unexpected result {:?}", result),
+        };
+
+        // The id was given by the caller, the record is another one
+        let mut given = collection.new_record();
+        given.data = json!({"id": id, "title": "beef"}).into();
+        match given.create() {
+            Err(KintoError::PreconditionError) => (),
+            result => panic!("Unexpected result {:?}", result),
+        };
+    }
+
+    #[test]
+    fn test_create_validates_generated_id() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        collection.record_validator = Some(Validator::new(json!({
+            "type": "object",
+            "required": ["id", "title"],
+        })));
+
+        let mut record = collection.new_record();
+        record.data = json!({"title": "beef"}).into();
+        record.create().unwrap();
+        assert!(record.id.is_some());
+    }
+
+    #[test]
+    fn test_update_record_with_merge() {
+        let mut record = setup_record();
+        record.data = json!({"title": "beef", "cut": "rib"}).into();
+        record.create().unwrap();
+        let base = record.data.clone();
+
+        let mut other = record.clone();
+        other.data.as_mut().unwrap()["cut"] = "loin".into();
+        other.update().unwrap();
+
+        record.data.as_mut().unwrap()["title"] = "lamb".into();
+        let conflicts = record.update_with(base.clone(), &ThreeWayMerge).unwrap();
+        assert!(conflicts.is_empty());
+        let data = record.data.clone().unwrap();
+        assert_eq!(data["title"], "lamb");
+        assert_eq!(data["cut"], "loin");
+
+        // Same field changed on both sides
+        other.data.as_mut().unwrap()["cut"] = "flank".into();
+        other.data.as_mut().unwrap()["last_modified"] = data["last_modified"].clone();
+        other.update().unwrap();
+        record.data.as_mut().unwrap()["cut"] = "shank".into();
+        let conflicts = record.update_with(Some(data), &ThreeWayMerge).unwrap();
+        assert_eq!(conflicts[0].path, "/cut");
+    }
+
+    #[test]
+    fn test_update_record_with_deletion() {
+        let mut record = setup_record();
+        record.data = json!({"title": "beef"}).into();
+        record.create().unwrap();
+
+        let mut other = record.clone();
+        other.data.as_mut().unwrap()["title"] = "lamb".into();
+        other.update().unwrap();
+
+        record.data.as_mut().unwrap()["title"] = "pork".into();
+        let delete = Manual::new(|_: &_| Resolution::Resolved(json!(null)));
+        let conflicts = record.update_with(None, &delete).unwrap();
+        assert!(conflicts.is_empty());
+        other.load().unwrap_err();
+    }
 }
@@ -0,0 +1,420 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+
+use serde_json;
+use serde_json::Value;
+use hyper::header::IfNoneMatch;
+
+use error::KintoError;
+use batch::send_batches;
+use request::{KintoRequest, PayloadedEndpoint, PluralEndpoint};
+use resource::Resource;
+use bucket::Bucket;
+use collection::Collection;
+use conflict::without_timestamp;
+use sync::SyncError;
+use utils::{etag_to_timestamp, uuid4};
+
+
+/// Storage of the replication checkpoints (timestamp of the last change
+/// replicated), by source and target.
+pub trait Checkpoints {
+    fn get(&self, key: &str) -> Result<Option<u64>, KintoError>;
+
+    fn set(&mut self, key: &str, timestamp: u64) -> Result<(), KintoError>;
+}
+
+
+/// Checkpoints kept in memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryCheckpoints {
+    checkpoints: HashMap<String, u64>,
+}
+
+
+impl MemoryCheckpoints {
+    pub fn new() -> MemoryCheckpoints {
+        MemoryCheckpoints::default()
+    }
+}
+
+
+impl Checkpoints for MemoryCheckpoints {
+    fn get(&self, key: &str) -> Result<Option<u64>, KintoError> {
+        Ok(self.checkpoints.get(key).cloned())
+    }
+
+    fn set(&mut self, key: &str, timestamp: u64) -> Result<(), KintoError> {
+        self.checkpoints.insert(key.to_owned(), timestamp);
+        Ok(())
+    }
+}
+
+
+/// Checkpoints saved in a JSON file, written after each change.
+#[derive(Debug, Clone)]
+pub struct FileCheckpoints {
+    path: PathBuf,
+    checkpoints: HashMap<String, u64>,
+}
+
+
+impl FileCheckpoints {
+    /// Open a checkpoints file, created on the first checkpoint.
+    pub fn open<P>(path: P) -> Result<FileCheckpoints, KintoError>
+        where P: AsRef<Path>
+    {
+        let path = path.as_ref().to_path_buf();
+        let checkpoints = if path.exists() {
+            try!(serde_json::from_reader(try!(File::open(&path))))
+        } else {
+            HashMap::new()
+        };
+        Ok(FileCheckpoints {
+               path: path,
+               checkpoints: checkpoints,
+           })
+    }
+
+    /// Write the checkpoints to a temporary file, then move it to the path.
+    fn write(&self, temp: &Path) -> Result<(), KintoError> {
+        let mut file = try!(File::create(temp));
+        try!(serde_json::to_writer(&mut file, &self.checkpoints));
+        try!(file.sync_all());
+        try!(fs::rename(temp, &self.path));
+        Ok(())
+    }
+}
+
+
+impl Checkpoints for FileCheckpoints {
+    fn get(&self, key: &str) -> Result<Option<u64>, KintoError> {
+        Ok(self.checkpoints.get(key).cloned())
+    }
+
+    /// Save the checkpoints in a temporary file replacing the previous one,
+    /// so that an interrupted write cannot lose them.
+    fn set(&mut self, key: &str, timestamp: u64) -> Result<(), KintoError> {
+        self.checkpoints.insert(key.to_owned(), timestamp);
+
+        let name = self.path.file_name().and_then(|name| name.to_str()).unwrap_or("");
+        let temp = self.path.with_file_name(format!(".{}.{}.tmp", name, uuid4()));
+        let written = self.write(&temp);
+        if written.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        written
+    }
+}
+
+
+/// Options of a replication.
+#[derive(Debug, Clone, Default)]
+pub struct ReplicationOptions {
+    /// Replicate the records permissions too.
+    pub permissions: bool,
+}
+
+
+/// Report of a replication.
+#[derive(Debug, Clone, Default)]
+pub struct ReplicationResult {
+    /// Records created or updated on the target.
+    pub updated: Vec<Value>,
+    /// Records deleted on the target.
+    pub deleted: Vec<Value>,
+    pub errors: Vec<SyncError>,
+}
+
+
+impl ReplicationResult {
+    /// Whether every change was replicated.
+    pub fn ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
+
+
+/// Apply the changes of the source collection to the target collection.
+///
+/// Only the changes since the last replication are fetched, and the
+/// checkpoint is only moved forward once all of them were applied.
+pub fn replicate<C>(source: &Collection,
+                    target: &Collection,
+                    options: &ReplicationOptions,
+                    checkpoints: &mut C)
+                    -> Result<ReplicationResult, KintoError>
+    where C: Checkpoints
+{
+    let key = try!(checkpoint_key(source, target));
+
+    let mut request = try!(source.new_record().list_request());
+    match try!(checkpoints.get(&key)) {
+        Some(timestamp) => {
+            request.since(timestamp);
+        }
+        None => (),
+    };
+    let response = try!(request.follow_subrequests());
+    let timestamp = etag_to_timestamp(&response.headers);
+    let changes = response.body["data"].as_array().cloned().unwrap_or_default();
+
+    let permissions = if options.permissions {
+        try!(records_permissions(source, &changes))
+    } else {
+        HashMap::new()
+    };
+
+    let mut requests = vec![];
+    let mut deletions = vec![];
+    for change in &changes {
+        let id = try!(change["id"].as_str().ok_or(KintoError::UndefinedIdError));
+        let record = target.record(id);
+        if change["deleted"].as_bool().unwrap_or(false) {
+            requests.push(try!(record.delete_request()).preparer().clone());
+            deletions.push(true);
+        } else {
+            let mut body = json!({"data": without_timestamp(change)});
+            match permissions.get(id) {
+                Some(permissions) => body["permissions"] = permissions.clone(),
+                None => (),
+            };
+            let mut request = try!(record.update_request());
+            request.body(Some(body));
+            requests.push(request.preparer().clone());
+            deletions.push(false);
+        }
+    }
+
+    let mut result = ReplicationResult::default();
+    let responses = try!(send_batches(&target.get_client(), requests));
+    let applied = changes.into_iter().zip(deletions).zip(responses);
+    for ((change, deleted), response) in applied {
+        let status = response.status.to_u16();
+        match (deleted, response.status.is_success(), status) {
+            (true, true, _) | (true, false, 404) => result.deleted.push(change),
+            (false, true, _) => result.updated.push(change),
+            _ => {
+                result.errors.push(SyncError {
+                                       id: change["id"].as_str().unwrap_or("").to_owned(),
+                                       status: status,
+                                       details: response.body,
+                                   })
+            }
+        };
+    }
+
+    match timestamp {
+        Some(timestamp) if result.ok() => try!(checkpoints.set(&key, timestamp)),
+        _ => (),
+    };
+    Ok(result)
+}
+
+
+/// Replicate all the collections of the source bucket.
+///
+/// The target bucket and collections are created if missing, with the
+/// source metadata.
+pub fn replicate_bucket<C>(source: &Bucket,
+                           target: &Bucket,
+                           options: &ReplicationOptions,
+                           checkpoints: &mut C)
+                           -> Result<ReplicationResult, KintoError>
+    where C: Checkpoints
+{
+    let mut result = ReplicationResult::default();
+    let mut source = source.clone();
+    try!(source.load());
+    try!(create_missing(target, source.get_data()));
+
+    for collection in try!(source.list_collections()) {
+        let id = try!(collection.get_id().ok_or(KintoError::UndefinedIdError));
+        let target_collection = target.clone().collection(id);
+        try!(create_missing(&target_collection, collection.get_data()));
+
+        let replicated =
+            try!(replicate(&collection, &target_collection, options, checkpoints));
+        result.updated.extend(replicated.updated);
+        result.deleted.extend(replicated.deleted);
+        result.errors.extend(replicated.errors);
+    }
+    Ok(result)
+}
+
+
+/// Identify a replication by the URLs of its source and target.
+fn checkpoint_key(source: &Collection,
+                  target: &Collection)
+                  -> Result<String, KintoError> {
+    Ok(format!("{} -> {}",
+               source.get_client().url(&try!(source.uri())),
+               target.get_client().url(&try!(target.uri()))))
+}
+
+
+/// Get the permissions of the changed records, in batches.
+fn records_permissions(source: &Collection,
+                       changes: &[Value])
+                       -> Result<HashMap<String, Value>, KintoError> {
+    let mut ids = vec![];
+    let mut requests = vec![];
+    for change in changes {
+        match change["id"].as_str() {
+            Some(id) if !change["deleted"].as_bool().unwrap_or(false) => {
+                requests.push(try!(source.record(id).load_request()));
+                ids.push(id.to_owned());
+            }
+            _ => (),
+        };
+    }
+
+    let mut permissions = HashMap::new();
+    let responses = try!(send_batches(&source.get_client(), requests));
+    for (id, response) in ids.into_iter().zip(responses) {
+        if response.status.is_success() {
+            permissions.insert(id, response.body["permissions"].to_owned());
+        }
+    }
+    Ok(permissions)
+}
+
+
+/// Create an object with the given data, unless it exists.
+fn create_missing<T>(resource: &T, data: Option<Value>) -> Result<(), KintoError>
+    where T: Resource
+{
+    let mut data = without_timestamp(&data.unwrap_or(json!({})));
+    data["id"] = try!(resource.get_id().ok_or(KintoError::UndefinedIdError)).into();
+
+    let mut request = try!(resource.update_request());
+    request.body(Some(json!({"data": data})));
+    request.if_none_match(IfNoneMatch::Any);
+    match request.send() {
+        Ok(_) | Err(KintoError::PreconditionError) => Ok(()),
+        Err(error) => Err(error),
+    }
+}
+
+
+#[cfg(test)]
+mod test_replication {
+    use std::env;
+    use std::fs;
+
+    use resource::Resource;
+    use bucket::Bucket;
+    use collection::Collection;
+    use permissions::{Permissions, RecordPermission};
+    use utils::uuid4;
+    use utils::tests::setup_client;
+    use super::{Checkpoints, FileCheckpoints, MemoryCheckpoints, ReplicationOptions,
+                replicate, replicate_bucket};
+
+    fn setup_source() -> (Bucket, Collection) {
+        let client = setup_client();
+        let mut bucket = client.bucket("central");
+        bucket.set().unwrap();
+        let mut collection = bucket.clone().collection("meat");
+        collection.set().unwrap();
+        for id in &["entrecote", "onglet"] {
+            let mut record = collection.record(id);
+            record.data = json!({"title": id}).into();
+            record.permissions.grant(RecordPermission::Read, "account:alice");
+            record.set().unwrap();
+        }
+        (bucket, collection)
+    }
+
+    fn setup_target(source: &Bucket) -> Collection {
+        let mut bucket = source.get_client().bucket("edge");
+        bucket.set().unwrap();
+        let mut collection = bucket.collection("meat");
+        collection.set().unwrap();
+        collection
+    }
+
+    #[test]
+    fn test_replicate_changes_since_checkpoint() {
+        let (bucket, source) = setup_source();
+        let target = setup_target(&bucket);
+        let mut checkpoints = MemoryCheckpoints::new();
+        let options = ReplicationOptions::default();
+
+        let result = replicate(&source, &target, &options, &mut checkpoints).unwrap();
+        assert!(result.ok());
+        assert_eq!(result.updated.len(), 2);
+        let mut record = target.record("onglet");
+        record.load().unwrap();
+        assert_eq!(record.data.clone().unwrap()["title"], "onglet");
+        assert!(!record.permissions.has(RecordPermission::Read, "account:alice"));
+
+        // Only the new changes are replicated
+        let mut changed = source.record("onglet");
+        changed.data = json!({"title": "changed"}).into();
+        changed.set().unwrap();
+        source.record("entrecote").delete().unwrap();
+
+        let result = replicate(&source, &target, &options, &mut checkpoints).unwrap();
+        assert_eq!(result.updated.len(), 1);
+        assert_eq!(result.deleted.len(), 1);
+        record.load().unwrap();
+        assert_eq!(record.data.unwrap()["title"], "changed");
+        target.record("entrecote").load().unwrap_err();
+
+        let result = replicate(&source, &target, &options, &mut checkpoints).unwrap();
+        assert!(result.updated.is_empty() && result.deleted.is_empty());
+    }
+
+    #[test]
+    fn test_replicate_permissions() {
+        let (bucket, source) = setup_source();
+        let target = setup_target(&bucket);
+        let options = ReplicationOptions { permissions: true };
+        replicate(&source, &target, &options, &mut MemoryCheckpoints::new()).unwrap();
+
+        let mut record = target.record("entrecote");
+        record.load().unwrap();
+        assert!(record.permissions.has(RecordPermission::Read, "account:alice"));
+    }
+
+    #[test]
+    fn test_replicate_bucket() {
+        let (source, _) = setup_source();
+        let target = source.get_client().bucket("edge");
+        let mut checkpoints = MemoryCheckpoints::new();
+        let options = ReplicationOptions::default();
+
+        let result = replicate_bucket(&source, &target, &options, &mut checkpoints)
+            .unwrap();
+        assert_eq!(result.updated.len(), 2);
+        let collections = target.list_collections().unwrap();
+        assert_eq!(collections[0].get_id().unwrap(), "meat");
+
+        let result = replicate_bucket(&source, &target, &options, &mut checkpoints)
+            .unwrap();
+        assert!(result.updated.is_empty());
+    }
+
+    #[test]
+    fn test_file_checkpoints() {
+        let dir = env::temp_dir().join(format!("kinto-{}", uuid4()));
+        fs::create_dir(&dir).unwrap();
+        let path = dir.join("checkpoints.json");
+
+        let mut checkpoints = FileCheckpoints::open(&path).unwrap();
+        assert_eq!(checkpoints.get("a -> b").unwrap(), None);
+        checkpoints.set("a -> b", 42).unwrap();
+        checkpoints.set("a -> c", 43).unwrap();
+
+        let checkpoints = FileCheckpoints::open(&path).unwrap();
+        assert_eq!(checkpoints.get("a -> b").unwrap(), Some(42));
+        assert_eq!(checkpoints.get("a -> c").unwrap(), Some(43));
+
+        // Only the checkpoints file is left
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
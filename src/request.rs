@@ -9,6 +9,7 @@ use hyper::status::StatusCode;
 
 use KintoClient;
 use error::KintoError;
+use paths::encode_segment;
 use response::ResponseWrapper;
 
 
@@ -21,6 +22,8 @@ pub struct RequestPreparer {
     pub headers: Headers,
     pub query: String,
     pub body: Option<Value>,
+    /// Raw payload, sent instead of the JSON body (e.g. multipart uploads).
+    pub payload: Option<Vec<u8>>,
 }
 
 
@@ -34,11 +37,20 @@ impl RequestPreparer {
             headers: Headers::new(),
             query: String::new(),
             body: None,
+            payload: None,
         }
     }
 }
 
 
+/// Send a prepared request as is (e.g. mixed requests of a batch).
+impl KintoRequest for RequestPreparer {
+    fn preparer(&mut self) -> &mut RequestPreparer {
+        self
+    }
+}
+
+
 /// Base trait with options shared with all kinto requests
 pub trait KintoRequest: Clone {
     fn preparer(&mut self) -> &mut RequestPreparer;
@@ -57,13 +69,16 @@ pub trait KintoRequest: Clone {
 
     /// Send the request.
     fn send(&mut self) -> Result<ResponseWrapper, KintoError> {
+        check_status(try!(self.send_unchecked()))
+    }
+
+    /// Send the request, returning the response whatever its status.
+    fn send_unchecked(&mut self) -> Result<ResponseWrapper, KintoError> {
 
         // Borrow preparer mutable
         let preparer = self.preparer();
 
-        let mut full_path = format!("{}{}",
-                                preparer.client.server_url,
-                                preparer.path);
+        let mut full_path = preparer.client.url(&preparer.path);
 
         if preparer.query.len() > 0 {
             full_path = format!("{}?{}", full_path, preparer.query);
@@ -72,22 +87,23 @@ pub trait KintoRequest: Clone {
         let mut headers = preparer.headers.to_owned();
 
         // Set authentication headers
-        match preparer.client.auth.to_owned() {
+        match preparer.client.auth().cloned() {
             Some(method) => headers.set(method),
             None => (),
         };
 
-        let payload = match preparer.body.clone() {
-            Some(body) => serde_json::to_string(&body).unwrap(),
-            None => "".to_owned(),
+        let payload = match (preparer.payload.clone(), preparer.body.clone()) {
+            (Some(payload), _) => payload,
+            (None, Some(body)) => serde_json::to_vec(&body).unwrap(),
+            (None, None) => vec![],
         };
 
         // Send prepared request
         let response = preparer.client
-            .http_client
+            .http_client()
             .request(preparer.method.to_owned(), &full_path)
             .headers(headers)
-            .body(payload.as_str())
+            .body(payload.as_slice())
             .send();
 
         let mut response = match response {
@@ -95,23 +111,19 @@ pub trait KintoRequest: Clone {
             Err(_) => return Err(KintoError::HyperError),
         };
 
-        // Handle sync errors
-        if response.status == StatusCode::NotModified {
-            return Err(KintoError::NotModified);
-        }
-
-        if response.status == StatusCode::PreconditionFailed {
-            return Err(KintoError::PreconditionError);
-        }
-
-        // Raise on unexpected errors
-        if !response.status.is_success() {
-            return Err(KintoError::HyperError);
-        }
-
-        let mut serialized = String::new();
-        try!(response.read_to_string(&mut serialized));
-        let body = serde_json::from_str(&serialized).unwrap();
+        let body = if response.status.is_success() {
+            let mut serialized = String::new();
+            try!(response.read_to_string(&mut serialized));
+            // Some endpoints answer without content (e.g. 204 No Content)
+            match serialized.len() {
+                0 => Value::Null,
+                _ => serde_json::from_str(&serialized).unwrap(),
+            }
+        } else {
+            let mut content = String::new();
+            let _ = response.read_to_string(&mut content);
+            serde_json::from_str(&content).unwrap_or(Value::Null)
+        };
 
         let response = ResponseWrapper {
             client: preparer.client.to_owned(),
@@ -125,9 +137,15 @@ pub trait KintoRequest: Clone {
     }
 
     fn follow_subrequests(&mut self) -> Result<ResponseWrapper, KintoError> {
+        let response = try!(self.send());
+        self.follow_pages(response)
+    }
 
-        // Send first request
-        let mut base_response = try!(self.send());
+    /// Append the next pages of a plural endpoint to its first response.
+    fn follow_pages(&mut self,
+                    response: ResponseWrapper)
+                    -> Result<ResponseWrapper, KintoError> {
+        let mut base_response = response;
         let mut current_response = base_response.clone();
 
         loop {
@@ -144,8 +162,7 @@ pub trait KintoRequest: Clone {
 
             // Remove client prefix
             temp_request.preparer().path =
-                next_page_url.as_str().replace(base_response.client.server_url.as_str(),
-                                               "");
+                base_response.client.endpoint_path(next_page_url.as_str());
             temp_request.preparer().query = "".to_owned();
 
             current_response = try!(temp_request.send());
@@ -159,6 +176,29 @@ pub trait KintoRequest: Clone {
 }
 
 
+/// Turn the error statuses of a response into errors.
+pub fn check_status(response: ResponseWrapper) -> Result<ResponseWrapper, KintoError> {
+    // Handle sync errors
+    if response.status == StatusCode::NotModified {
+        return Err(KintoError::NotModified);
+    }
+
+    if response.status == StatusCode::PreconditionFailed {
+        return Err(KintoError::PreconditionError);
+    }
+
+    if response.status == StatusCode::InsufficientStorage {
+        return Err(KintoError::QuotaExceeded);
+    }
+
+    // Raise on unexpected errors
+    if !response.status.is_success() {
+        return Err(KintoError::HttpError(response.status.to_u16(), response.body));
+    }
+    Ok(response)
+}
+
+
 /// Implement methods used on payloded requests (POST, PUT, PATCH).
 pub trait PayloadedEndpoint: KintoRequest {
     fn body(&mut self, body: Option<Value>) -> &mut Self {
@@ -174,6 +214,41 @@ pub trait PluralEndpoint: KintoRequest {
         self.preparer().query = format!("{}&_limit={}", self.preparer().query, limit);
         self
     }
+
+    /// Filter objects on a field value (e.g. `filter("action", "create")`).
+    fn filter(&mut self, field: &str, value: &str) -> &mut Self {
+        self.preparer().query = format!("{}&{}={}",
+                                        self.preparer().query,
+                                        encode_segment(field),
+                                        encode_segment(value));
+        self
+    }
+
+    /// Only get objects modified after the given timestamp.
+    fn since(&mut self, timestamp: u64) -> &mut Self {
+        self.preparer().query = format!("{}&_since={}", self.preparer().query, timestamp);
+        self
+    }
+
+    /// Only get objects modified before the given timestamp.
+    fn before(&mut self, timestamp: u64) -> &mut Self {
+        self.preparer().query = format!("{}&_before={}",
+                                        self.preparer().query,
+                                        timestamp);
+        self
+    }
+
+    /// Sort objects on the given fields (e.g. `sort("-last_modified")`).
+    fn sort(&mut self, fields: &str) -> &mut Self {
+        self.preparer().query = format!("{}&_sort={}", self.preparer().query, fields);
+        self
+    }
+
+    /// Only return the given fields of the objects (e.g. `fields("id,title")`).
+    fn fields(&mut self, fields: &str) -> &mut Self {
+        self.preparer().query = format!("{}&_fields={}", self.preparer().query, fields);
+        self
+    }
 }
 
 /// Get request on plural endpoints.
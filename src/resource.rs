@@ -2,10 +2,19 @@ use serde_json::Value;
 use hyper::header::{IfMatch, IfNoneMatch};
 
 use client::KintoClient;
+use bucket::Bucket;
+use collection::Collection;
+use group::Group;
+use record::Record;
 use error::KintoError;
-use request::{GetRecord, CreateRecord, UpdateRecord, DeleteRecord, GetCollection,
-              DeleteCollection, KintoRequest, PayloadedEndpoint};
+use conflict::{Conflict, ConflictStrategy, FieldConflict, Resolution, without_timestamp};
+use request::{GetRecord, CreateRecord, UpdateRecord, PatchRecord, DeleteRecord,
+              GetCollection, DeleteCollection, KintoRequest, PayloadedEndpoint,
+              PluralEndpoint};
+use ids::validate_id;
+use paths::encode_segment;
 use response::ResponseWrapper;
+use transformer::TransformerPipeline;
 use utils::timestamp_to_etag;
 
 
@@ -17,13 +26,21 @@ pub trait Resource: Clone {
     /// Get the record path for the resource.
     fn record_path(&self) -> Result<String, KintoError> {
         match self.get_id() {
-            Some(id) => Ok(format!("{}/{}", try!(self.resource_path()), id)),
+            Some(id) => {
+                Ok(format!("{}/{}", try!(self.resource_path()), encode_segment(id)))
+            }
             None => Err(KintoError::UndefinedIdError),
         }
     }
 
+    /// Get the URI of the object, as found in history entries, permissions
+    /// listings or batch responses (see `KintoClient::resolve()`).
+    fn uri(&self) -> Result<String, KintoError> {
+        self.record_path()
+    }
+
     /// Unwrap a request response and update the current object.
-    fn unwrap_response(&mut self, wrapper: ResponseWrapper);
+    fn unwrap_response(&mut self, wrapper: ResponseWrapper) -> Result<(), KintoError>;
 
     /// Get the client for the given resource.
     fn get_client(&self) -> KintoClient;
@@ -42,7 +59,36 @@ pub trait Resource: Clone {
     /// Return the object version timestamp.
     fn get_timestamp(&self) -> Option<u64>;
 
-    fn get_body(&self) -> Value {
+    /// Check the object data before sending it.
+    fn validate(&self) -> Result<(), KintoError> {
+        Ok(())
+    }
+
+    /// Whether `validate()` checks the data, which is then only saved whole.
+    fn validates(&self) -> bool {
+        false
+    }
+
+    /// Generate an id for a new object, if done on the client side.
+    fn generate_id(&self) -> Option<String> {
+        None
+    }
+
+    /// Remember that `create()` generated the id of this object.
+    fn set_generated_id(&mut self, _id: &str) {}
+
+    /// Whether the id of this object was generated by a creation attempt
+    /// that got no response.
+    fn has_generated_id(&self) -> bool {
+        false
+    }
+
+    /// Transformers applied to the object data (e.g. encryption).
+    fn transformers(&self) -> TransformerPipeline {
+        TransformerPipeline::default()
+    }
+
+    fn get_body(&self) -> Result<Value, KintoError> {
         let mut body = json!({});
 
         // If id is defined, replace body id with the provided id
@@ -77,7 +123,13 @@ pub trait Resource: Clone {
             None => (),
         };
 
-        return body;
+        let transformers = self.transformers();
+        if !body["data"].is_null() && !transformers.is_empty() {
+            let uri = try!(self.uri());
+            body["data"] = try!(transformers.encode(&uri, body["data"].clone()));
+        }
+
+        return Ok(body);
     }
 
     /// create a custom load (GET) request for the endpoint.
@@ -95,6 +147,11 @@ pub trait Resource: Clone {
         Ok(UpdateRecord::new(self.get_client(), try!(self.record_path())))
     }
 
+    /// Create a custom patch (PATCH) request for the endpoint.
+    fn patch_request(&self) -> Result<PatchRecord, KintoError> {
+        Ok(PatchRecord::new(self.get_client(), try!(self.record_path())))
+    }
+
     /// Create a custom delete request for the endpoint.
     fn delete_request(&self) -> Result<DeleteRecord, KintoError> {
         Ok(DeleteRecord::new(self.get_client(), try!(self.record_path())))
@@ -116,7 +173,7 @@ pub trait Resource: Clone {
             Ok(wrapper) => wrapper,
             Err(value) => return Err(value),
         };
-        self.unwrap_response(wrapper);
+        try!(self.unwrap_response(wrapper));
         Ok(())
     }
 
@@ -125,31 +182,82 @@ pub trait Resource: Clone {
         if self.get_id() == None {
             return self.create();
         }
+        try!(self.validate());
 
         let wrapper =
-            match try!(self.update_request()).body(self.get_body().into()).send() {
+            match try!(self.update_request()).body(try!(self.get_body()).into()).send() {
                 Ok(wrapper) => wrapper,
                 Err(value) => return Err(value),
             };
-        self.unwrap_response(wrapper);
+        try!(self.unwrap_response(wrapper));
         Ok(())
     }
 
     /// Create if not exists the current object.
+    ///
+    /// Objects with a known (or generated) id are created with `PUT`, so that
+    /// retrying a creation cannot duplicate them. A retry with a generated id
+    /// succeeds if the object was created with the same data. The id must
+    /// follow the Kinto ids rules, see `validate_id()`.
     fn create(&mut self) -> Result<(), KintoError> {
-        let wrapper = match try!(self.create_request())
-                  .body(self.get_body().into())
-                  .if_none_match(IfNoneMatch::Any)
-                  .send() {
-            Ok(wrapper) => wrapper,
-            Err(value) => return Err(value),
+        let retried = self.has_generated_id();
+        if self.get_id().is_none() {
+            match self.generate_id() {
+                Some(id) => {
+                    self.set_generated_id(&id);
+                    let mut data = self.get_data().unwrap_or(json!({}));
+                    data["id"] = id.into();
+                    self.set_data(data);
+                }
+                None => (),
+            };
+        }
+        // Ids of existing objects are only checked by the server
+        match self.get_id() {
+            Some(id) => try!(validate_id(id)),
+            None => (),
         };
-        self.unwrap_response(wrapper);
-        Ok(())
+        try!(self.validate());
+
+        let body = try!(self.get_body());
+        let response = match self.get_id() {
+            Some(_) => {
+                try!(self.update_request())
+                    .body(body.into())
+                    .if_none_match(IfNoneMatch::Any)
+                    .send()
+            }
+            None => {
+                try!(self.create_request())
+                    .body(body.into())
+                    .if_none_match(IfNoneMatch::Any)
+                    .send()
+            }
+        };
+
+        match response {
+            Err(KintoError::PreconditionError) if retried => {
+                // The previous attempt created it, unless the data differs
+                let mut existing = self.clone();
+                try!(existing.load());
+                let data = self.get_data().unwrap_or_default();
+                let stored = existing.get_data().unwrap_or_default();
+                if without_timestamp(&data) != without_timestamp(&stored) {
+                    return Err(KintoError::PreconditionError);
+                }
+                *self = existing;
+                Ok(())
+            }
+            response => {
+                try!(self.unwrap_response(try!(response)));
+                Ok(())
+            }
+        }
     }
 
     /// Update an existing object if exists with the current object.
     fn update(&mut self) -> Result<(), KintoError> {
+        try!(self.validate());
         let stamp = self.get_timestamp();
 
         let if_match = match stamp {
@@ -159,13 +267,126 @@ pub trait Resource: Clone {
 
         let wrapper = match self.update_request()
                   .unwrap()
-                  .body(self.get_body().into())
+                  .body(try!(self.get_body()).into())
                   .if_match(if_match)
                   .send() {
             Ok(wrapper) => wrapper,
             Err(value) => return Err(value),
         };
-        self.unwrap_response(wrapper);
+        try!(self.unwrap_response(wrapper));
+        Ok(())
+    }
+
+    /// Update the object, resolving a concurrent change with `strategy`.
+    ///
+    /// `base` is the version the local changes were made on. Returns the
+    /// unresolved field conflicts, in which case nothing is saved.
+    fn update_with<S>(&mut self,
+                      base: Option<Value>,
+                      strategy: &S)
+                      -> Result<Vec<FieldConflict>, KintoError>
+        where S: ConflictStrategy
+    {
+        match self.update() {
+            Err(KintoError::PreconditionError) => (),
+            other => return other.map(|_| vec![]),
+        };
+
+        // Fetch the current server version, if it still exists
+        let id = try!(self.get_id().ok_or(KintoError::UndefinedIdError)).to_owned();
+        let response = try!(try!(self.list_request()).filter("id", id.as_str()).send());
+        let remote = match response.body["data"][0] {
+            Value::Null => Value::Null,
+            ref remote => {
+                try!(self.transformers().decode(&try!(self.uri()), remote.clone()))
+            }
+        };
+
+        let conflict = Conflict {
+            base: base,
+            local: self.get_data().unwrap_or_default(),
+            remote: remote.clone(),
+        };
+
+        let mut data = match strategy.resolve(&conflict) {
+            Resolution::Unresolved(fields) => return Ok(fields),
+            Resolution::Resolved(Value::Null) => {
+                // Keep the object deleted, unless it was changed meanwhile
+                if !remote.is_null() {
+                    let stamp = try!(remote["last_modified"]
+                                         .as_u64()
+                                         .ok_or(KintoError::PreconditionError));
+                    let if_match = IfMatch::Items(timestamp_to_etag(stamp));
+                    let response = try!(try!(self.delete_request())
+                                            .if_match(if_match)
+                                            .send());
+                    try!(self.unwrap_response(response));
+                }
+                return Ok(vec![]);
+            }
+            Resolution::Resolved(data) => data,
+        };
+
+        if without_timestamp(&data) == without_timestamp(&remote) {
+            self.set_data(remote);
+            return Ok(vec![]);
+        }
+
+        if remote.is_null() {
+            self.set_data(without_timestamp(&data));
+            try!(self.set());
+        } else {
+            data["last_modified"] = remote["last_modified"].clone();
+            self.set_data(data);
+            try!(self.update());
+        }
+        Ok(vec![])
+    }
+
+    /// Merge the given fields into the data of an existing object.
+    fn patch(&mut self, data: Value) -> Result<(), KintoError> {
+        // Transformed or validated data can only be replaced as a whole
+        if !self.transformers().is_empty() || self.validates() {
+            try!(self.load());
+            let mut merged = self.get_data().unwrap_or(json!({}));
+            match data.as_object() {
+                Some(fields) => {
+                    for (field, value) in fields {
+                        merged[field.as_str()] = value.clone();
+                    }
+                }
+                None => (),
+            };
+            self.set_data(merged);
+            return self.update();
+        }
+
+        let body = json!({ "data": data });
+
+        let wrapper = match try!(self.patch_request()).body(body.into()).send() {
+            Ok(wrapper) => wrapper,
+            Err(value) => return Err(value),
+        };
+        try!(self.unwrap_response(wrapper));
+        Ok(())
+    }
+
+    /// Update only the permissions of an existing object.
+    ///
+    /// Like with `set()`, objects with invalid data are not saved.
+    fn set_permissions(&mut self) -> Result<(), KintoError> {
+        if self.get_data().is_some() {
+            try!(self.validate());
+        }
+        let body = json!({
+            "permissions": self.get_permissions().unwrap_or(json!({}))
+        });
+
+        let wrapper = match try!(self.patch_request()).body(body.into()).send() {
+            Ok(wrapper) => wrapper,
+            Err(value) => return Err(value),
+        };
+        try!(self.unwrap_response(wrapper));
         Ok(())
     }
 
@@ -175,7 +396,40 @@ pub trait Resource: Clone {
             Ok(wrapper) => wrapper,
             Err(value) => return Err(value),
         };
-        self.unwrap_response(wrapper);
+        try!(self.unwrap_response(wrapper));
         Ok(())
     }
 }
+
+
+/// Any object of the Kinto API, as resolved from its URI.
+#[derive(Debug, Clone)]
+pub enum AnyResource {
+    Bucket(Bucket),
+    Collection(Collection),
+    Group(Group),
+    Record(Record),
+}
+
+
+impl AnyResource {
+    /// Get the URI of the object.
+    pub fn uri(&self) -> Result<String, KintoError> {
+        match *self {
+            AnyResource::Bucket(ref bucket) => bucket.uri(),
+            AnyResource::Collection(ref collection) => collection.uri(),
+            AnyResource::Group(ref group) => group.uri(),
+            AnyResource::Record(ref record) => record.uri(),
+        }
+    }
+
+    /// Get the name of the object type (e.g. `record`), as in history entries.
+    pub fn resource_name(&self) -> &str {
+        match *self {
+            AnyResource::Bucket(_) => "bucket",
+            AnyResource::Collection(_) => "collection",
+            AnyResource::Group(_) => "group",
+            AnyResource::Record(_) => "record",
+        }
+    }
+}
@@ -0,0 +1,764 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+
+use serde_json::Value;
+use regex::Regex;
+
+use error::KintoError;
+
+
+/// Fields managed by the server, never validated (like Kinto does).
+const IGNORED_FIELDS: [&str; 3] = ["id", "last_modified", "schema"];
+
+
+/// Patterns of the string formats checked with a regular expression.
+const FORMATS: [(&str, &str); 7] = [("date-time",
+                     concat!(r"^\d{4}-\d{2}-\d{2}[Tt ]\d{2}:\d{2}:\d{2}(\.\d+)?",
+                             r"([Zz]|[+-]\d{2}:\d{2})$")),
+                    ("date", r"^\d{4}-(0[1-9]|1[0-2])-(0[1-9]|[12]\d|3[01])$"),
+                    ("time", r"^\d{2}:\d{2}:\d{2}(\.\d+)?([Zz]|[+-]\d{2}:\d{2})?$"),
+                    ("email", r"^[^@\s]+@[^@\s]+$"),
+                    ("uri", r"^[a-zA-Z][a-zA-Z0-9+.-]*:[^\s]*$"),
+                    ("uuid", r"^[0-9a-fA-F]{8}-([0-9a-fA-F]{4}-){3}[0-9a-fA-F]{12}$"),
+                    ("hostname",
+                     concat!(r"^[a-zA-Z0-9]([a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?",
+                             r"(\.[a-zA-Z0-9]([a-zA-Z0-9-]{0,61}[a-zA-Z0-9])?)*$"))];
+
+
+/// Validation failure of a value.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaError {
+    /// JSON pointer of the invalid value (e.g. `/author/name`).
+    pub path: String,
+    /// Schema keyword that failed (e.g. `required`).
+    pub keyword: String,
+    pub message: String,
+}
+
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = if self.path.is_empty() { "/" } else { self.path.as_str() };
+        write!(f, "{}: {}", path, self.message)
+    }
+}
+
+
+/// Validate records data against a collection JSON schema.
+///
+/// Patterns are compiled with the `regex` crate: the `pattern` and
+/// `patternProperties` it cannot compile (e.g. ECMA 262 lookarounds or
+/// backreferences) are not checked.
+#[derive(Debug, Clone, Default)]
+pub struct Validator {
+    /// Schema of the records, `Null` if any data is accepted.
+    schema: Value,
+    /// Compiled patterns of the string formats, shared by the clones.
+    formats: Arc<HashMap<&'static str, Regex>>,
+    /// Compiled patterns of the schema, without the unsupported ones.
+    patterns: Arc<HashMap<String, Regex>>,
+}
+
+
+impl PartialEq for Validator {
+    fn eq(&self, other: &Validator) -> bool {
+        self.schema == other.schema
+    }
+}
+
+
+impl Validator {
+    pub fn new(schema: Value) -> Validator {
+        let formats = FORMATS.iter()
+            .map(|&(format, pattern)| (format, Regex::new(pattern).unwrap()))
+            .collect();
+        let mut patterns = HashMap::new();
+        compile_patterns(&schema, &mut patterns);
+        Validator {
+            schema: schema,
+            formats: Arc::new(formats),
+            patterns: Arc::new(patterns),
+        }
+    }
+
+    /// Schema of the records, `Null` if any data is accepted.
+    pub fn schema(&self) -> &Value {
+        &self.schema
+    }
+
+    /// Check a record data, failing with `KintoError::ValidationError`.
+    pub fn validate(&self, data: &Value) -> Result<(), KintoError> {
+        let errors = self.errors(data);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(KintoError::ValidationError(errors))
+        }
+    }
+
+    /// List the validation errors of a record data.
+    ///
+    /// The ignored fields are removed from the data and from the required
+    /// fields of the schema.
+    pub fn errors(&self, data: &Value) -> Vec<SchemaError> {
+        if self.schema.is_null() {
+            return vec![];
+        }
+
+        let mut schema = self.schema.clone();
+        match schema.get_mut("required").and_then(Value::as_array_mut) {
+            Some(required) => {
+                required.retain(|field| match field.as_str() {
+                                    Some(field) => !IGNORED_FIELDS.contains(&field),
+                                    None => true,
+                                })
+            }
+            None => (),
+        };
+
+        let mut data = data.clone();
+        match data.as_object_mut() {
+            Some(map) => {
+                for field in IGNORED_FIELDS.iter() {
+                    map.remove(*field);
+                }
+            }
+            None => (),
+        };
+
+        let mut errors = vec![];
+        Context {
+                validator: self,
+                root: &schema,
+                errors: &mut errors,
+                refs: vec![],
+            }
+            .validate(&schema, &data, String::new());
+        errors
+    }
+}
+
+
+struct Context<'a> {
+    validator: &'a Validator,
+    root: &'a Value,
+    errors: &'a mut Vec<SchemaError>,
+    /// References being followed, with the value they are validating.
+    refs: Vec<(String, *const Value)>,
+}
+
+
+impl<'a> Context<'a> {
+    fn error(&mut self, path: &str, keyword: &str, message: String) {
+        self.errors.push(SchemaError {
+                             path: path.to_owned(),
+                             keyword: keyword.to_owned(),
+                             message: message,
+                         });
+    }
+
+    /// Whether the value is valid, without reporting the errors.
+    fn is_valid(&self, schema: &Value, value: &Value) -> bool {
+        let mut errors = vec![];
+        Context {
+                validator: self.validator,
+                root: self.root,
+                errors: &mut errors,
+                refs: self.refs.clone(),
+            }
+            .validate(schema, value, String::new());
+        errors.is_empty()
+    }
+
+    fn validate(&mut self, schema: &Value, value: &Value, path: String) {
+        let schema = match *schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => {
+                return self.error(&path, "false", "no value is allowed".to_owned())
+            }
+            Value::Object(ref schema) => schema,
+            _ => return,
+        };
+
+        match schema.get("$ref").and_then(|r| r.as_str()) {
+            Some(reference) => {
+                // Following a reference again for the same value never ends
+                let followed = (reference.to_owned(), value as *const Value);
+                if self.refs.contains(&followed) {
+                    return self.error(&path,
+                                      "$ref",
+                                      format!("circular reference {}", reference));
+                }
+                match resolve(self.root, reference) {
+                    Some(target) => {
+                        self.refs.push(followed);
+                        self.validate(target, value, path);
+                        self.refs.pop();
+                        return;
+                    }
+                    None => {
+                        return self.error(&path,
+                                          "$ref",
+                                          format!("unknown reference {}", reference))
+                    }
+                }
+            }
+            None => (),
+        };
+
+        match schema.get("type") {
+            Some(expected) => {
+                if !type_matches(expected, value) {
+                    return self.error(&path,
+                                      "type",
+                                      format!("expected type {}", expected));
+                }
+            }
+            None => (),
+        };
+
+        match schema.get("enum").and_then(|e| e.as_array()) {
+            Some(values) => {
+                if !values.contains(value) {
+                    self.error(&path, "enum", "value is not allowed".to_owned());
+                }
+            }
+            None => (),
+        };
+
+        match schema.get("const") {
+            Some(expected) => {
+                if expected != value {
+                    let message = format!("expected {}", expected);
+                    self.error(&path, "const", message);
+                }
+            }
+            None => (),
+        };
+
+        self.validate_combinations(schema, value, &path);
+
+        match *value {
+            Value::Object(_) => self.validate_object(schema, value, &path),
+            Value::Array(ref items) => self.validate_array(schema, items, &path),
+            Value::String(ref s) => self.validate_string(schema, s, &path),
+            Value::Number(ref n) => {
+                self.validate_number(schema, n.as_f64().unwrap_or_default(), &path)
+            }
+            _ => (),
+        };
+    }
+
+    fn validate_combinations(&mut self,
+                             schema: &::serde_json::Map<String, Value>,
+                             value: &Value,
+                             path: &str) {
+        match schema.get("allOf").and_then(|s| s.as_array()) {
+            Some(schemas) => {
+                for sub in schemas {
+                    self.validate(sub, value, path.to_owned());
+                }
+            }
+            None => (),
+        };
+
+        match schema.get("anyOf").and_then(|s| s.as_array()) {
+            Some(schemas) => {
+                if !schemas.iter().any(|sub| self.is_valid(sub, value)) {
+                    self.error(path, "anyOf", "no schema matches".to_owned());
+                }
+            }
+            None => (),
+        };
+
+        match schema.get("oneOf").and_then(|s| s.as_array()) {
+            Some(schemas) => {
+                let count = schemas.iter()
+                    .filter(|sub| self.is_valid(sub, value))
+                    .count();
+                if count != 1 {
+                    self.error(path,
+                               "oneOf",
+                               format!("{} schemas match instead of one", count));
+                }
+            }
+            None => (),
+        };
+
+        match schema.get("not") {
+            Some(sub) => {
+                if self.is_valid(sub, value) {
+                    self.error(path, "not", "value must not match the schema".to_owned());
+                }
+            }
+            None => (),
+        };
+    }
+
+    fn validate_object(&mut self,
+                       schema: &::serde_json::Map<String, Value>,
+                       value: &Value,
+                       path: &str) {
+        let object = value.as_object().unwrap();
+
+        match schema.get("required").and_then(|r| r.as_array()) {
+            Some(required) => {
+                for field in required.iter().filter_map(|f| f.as_str()) {
+                    if !object.contains_key(field) {
+                        self.error(&field_path(path, field),
+                                   "required",
+                                   "field is required".to_owned());
+                    }
+                }
+            }
+            None => (),
+        };
+
+        let count = object.len() as u64;
+        match schema.get("minProperties").and_then(|m| m.as_u64()) {
+            Some(min) if count < min => {
+                self.error(path,
+                           "minProperties",
+                           format!("expected at least {} fields", min))
+            }
+            _ => (),
+        };
+        match schema.get("maxProperties").and_then(|m| m.as_u64()) {
+            Some(max) if count > max => {
+                self.error(path,
+                           "maxProperties",
+                           format!("expected at most {} fields", max))
+            }
+            _ => (),
+        };
+
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        let mut patterns: Vec<(&Regex, &Value)> = vec![];
+        let mut unsupported = false;
+        match schema.get("patternProperties").and_then(|p| p.as_object()) {
+            Some(subs) => {
+                for (pattern, sub) in subs {
+                    match self.validator.patterns.get(pattern) {
+                        Some(regex) => patterns.push((regex, sub)),
+                        None => unsupported = true,
+                    };
+                }
+            }
+            None => (),
+        };
+        // The fields of unsupported patterns cannot be told apart
+        let additional = if unsupported {
+            None
+        } else {
+            schema.get("additionalProperties")
+        };
+
+        for (field, field_value) in object {
+            let sub_path = field_path(path, field);
+            let mut matched = false;
+
+            match properties.and_then(|p| p.get(field)) {
+                Some(sub) => {
+                    matched = true;
+                    self.validate(sub, field_value, sub_path.clone());
+                }
+                None => (),
+            };
+
+            for &(regex, sub) in &patterns {
+                if regex.is_match(field) {
+                    matched = true;
+                    self.validate(sub, field_value, sub_path.clone());
+                }
+            }
+
+            if !matched {
+                match additional {
+                    Some(&Value::Bool(false)) => {
+                        self.error(&sub_path,
+                                   "additionalProperties",
+                                   "field is not allowed".to_owned())
+                    }
+                    Some(sub) => self.validate(sub, field_value, sub_path),
+                    None => (),
+                };
+            }
+        }
+    }
+
+    fn validate_array(&mut self,
+                      schema: &::serde_json::Map<String, Value>,
+                      items: &[Value],
+                      path: &str) {
+        let count = items.len() as u64;
+        match schema.get("minItems").and_then(|m| m.as_u64()) {
+            Some(min) if count < min => {
+                self.error(path, "minItems", format!("expected at least {} items", min))
+            }
+            _ => (),
+        };
+        match schema.get("maxItems").and_then(|m| m.as_u64()) {
+            Some(max) if count > max => {
+                self.error(path, "maxItems", format!("expected at most {} items", max))
+            }
+            _ => (),
+        };
+
+        if schema.get("uniqueItems").and_then(|u| u.as_bool()).unwrap_or(false) {
+            let unique: HashSet<String> = items.iter()
+                .map(|item| item.to_string())
+                .collect();
+            if unique.len() != items.len() {
+                self.error(path, "uniqueItems", "items must be unique".to_owned());
+            }
+        }
+
+        match schema.get("items") {
+            Some(&Value::Array(ref schemas)) => {
+                for (index, (item, sub)) in items.iter().zip(schemas).enumerate() {
+                    self.validate(sub, item, format!("{}/{}", path, index));
+                }
+            }
+            Some(sub) => {
+                for (index, item) in items.iter().enumerate() {
+                    self.validate(sub, item, format!("{}/{}", path, index));
+                }
+            }
+            None => (),
+        };
+    }
+
+    fn validate_string(&mut self,
+                       schema: &::serde_json::Map<String, Value>,
+                       value: &str,
+                       path: &str) {
+        let length = value.chars().count() as u64;
+        match schema.get("minLength").and_then(|m| m.as_u64()) {
+            Some(min) if length < min => {
+                self.error(path,
+                           "minLength",
+                           format!("expected at least {} characters", min))
+            }
+            _ => (),
+        };
+        match schema.get("maxLength").and_then(|m| m.as_u64()) {
+            Some(max) if length > max => {
+                self.error(path,
+                           "maxLength",
+                           format!("expected at most {} characters", max))
+            }
+            _ => (),
+        };
+
+        match schema.get("pattern").and_then(|p| p.as_str()) {
+            Some(pattern) => {
+                match self.validator.patterns.get(pattern) {
+                    Some(regex) if !regex.is_match(value) => {
+                        self.error(path, "pattern", format!("must match {}", pattern))
+                    }
+                    // Unsupported patterns are not checked
+                    _ => (),
+                }
+            }
+            None => (),
+        };
+
+        match schema.get("format").and_then(|f| f.as_str()) {
+            Some(format) => {
+                if !self.format_matches(format, value) {
+                    self.error(path, "format", format!("invalid {}", format));
+                }
+            }
+            None => (),
+        };
+    }
+
+    fn format_matches(&self, format: &str, value: &str) -> bool {
+        match format {
+            "ipv4" => value.parse::<Ipv4Addr>().is_ok(),
+            "ipv6" => value.parse::<Ipv6Addr>().is_ok(),
+            // Unknown formats are not checked
+            _ => {
+                self.validator
+                    .formats
+                    .get(format)
+                    .map_or(true, |regex| regex.is_match(value))
+            }
+        }
+    }
+
+    fn validate_number(&mut self,
+                       schema: &::serde_json::Map<String, Value>,
+                       value: f64,
+                       path: &str) {
+        // Draft 4 uses booleans for the exclusive bounds, later drafts numbers
+        let exclusive = |keyword: &str| {
+            schema.get(keyword).and_then(|e| e.as_bool()).unwrap_or(false)
+        };
+
+        let (min_excluded, max_excluded) = (exclusive("exclusiveMinimum"),
+                                            exclusive("exclusiveMaximum"));
+
+        match schema.get("minimum").and_then(|m| m.as_f64()) {
+            Some(min) if value < min || (min_excluded && value == min) => {
+                self.error(path, "minimum", format!("must be at least {}", min))
+            }
+            _ => (),
+        };
+        match schema.get("maximum").and_then(|m| m.as_f64()) {
+            Some(max) if value > max || (max_excluded && value == max) => {
+                self.error(path, "maximum", format!("must be at most {}", max))
+            }
+            _ => (),
+        };
+        match schema.get("exclusiveMinimum").and_then(|m| m.as_f64()) {
+            Some(min) if value <= min => {
+                self.error(path, "exclusiveMinimum", format!("must be above {}", min))
+            }
+            _ => (),
+        };
+        match schema.get("exclusiveMaximum").and_then(|m| m.as_f64()) {
+            Some(max) if value >= max => {
+                self.error(path, "exclusiveMaximum", format!("must be below {}", max))
+            }
+            _ => (),
+        };
+        match schema.get("multipleOf").and_then(|m| m.as_f64()) {
+            Some(factor) if factor > 0.0 && (value / factor).fract() != 0.0 => {
+                let message = format!("must be a multiple of {}", factor);
+                self.error(path, "multipleOf", message)
+            }
+            _ => (),
+        };
+    }
+}
+
+
+/// Resolve a local reference (e.g. `#/definitions/author`).
+fn resolve<'a>(root: &'a Value, reference: &str) -> Option<&'a Value> {
+    if !reference.starts_with('#') {
+        return None;
+    }
+    let pointer = reference[1..].replace("~1", "/").replace("~0", "~");
+    root.pointer(pointer.as_str())
+}
+
+
+fn field_path(path: &str, field: &str) -> String {
+    format!("{}/{}", path, field.replace("~", "~0").replace("/", "~1"))
+}
+
+
+fn type_matches(expected: &Value, value: &Value) -> bool {
+    match *expected {
+        Value::String(ref name) => {
+            match (name.as_str(), value) {
+                ("null", &Value::Null) => true,
+                ("boolean", &Value::Bool(_)) => true,
+                ("object", &Value::Object(_)) => true,
+                ("array", &Value::Array(_)) => true,
+                ("string", &Value::String(_)) => true,
+                ("number", &Value::Number(_)) => true,
+                ("integer", &Value::Number(ref n)) => {
+                    n.is_i64() || n.is_u64() ||
+                    n.as_f64().map_or(false, |f| f.fract() == 0.0)
+                }
+                _ => false,
+            }
+        }
+        Value::Array(ref names) => names.iter().any(|name| type_matches(name, value)),
+        _ => true,
+    }
+}
+
+
+/// Compile the `pattern` and `patternProperties` of a schema and of its
+/// subschemas.
+fn compile_patterns(schema: &Value, patterns: &mut HashMap<String, Regex>) {
+    let mut sources = vec![];
+    let subs: Vec<&Value> = match *schema {
+        Value::Object(ref map) => {
+            match map.get("pattern").and_then(|p| p.as_str()) {
+                Some(pattern) => sources.push(pattern),
+                None => (),
+            };
+            match map.get("patternProperties").and_then(|p| p.as_object()) {
+                Some(subs) => sources.extend(subs.keys().map(String::as_str)),
+                None => (),
+            };
+            map.values().collect()
+        }
+        Value::Array(ref items) => items.iter().collect(),
+        _ => vec![],
+    };
+    for pattern in sources {
+        match Regex::new(pattern) {
+            Ok(regex) => {
+                patterns.insert(pattern.to_owned(), regex);
+            }
+            // Unsupported patterns are not checked
+            Err(_) => (),
+        };
+    }
+    for sub in subs {
+        compile_patterns(sub, patterns);
+    }
+}
+
+
+#[cfg(test)]
+mod test_schema {
+    use error::KintoError;
+    use resource::Resource;
+    use utils::tests::setup_collection;
+    use super::Validator;
+
+    fn validator() -> Validator {
+        Validator::new(json!({
+            "type": "object",
+            "required": ["title"],
+            "additionalProperties": false,
+            "properties": {
+                "title": {"type": "string", "minLength": 1},
+                "email": {"type": "string", "format": "email"},
+                "author": {
+                    "type": "object",
+                    "properties": {"name": {"$ref": "#/definitions/name"}},
+                    "required": ["name"]
+                },
+                "tags": {"type": "array", "items": {"enum": ["beef", "lamb"]}}
+            },
+            "definitions": {"name": {"type": "string", "pattern": "^[A-Z]"}}
+        }))
+    }
+
+    #[test]
+    fn test_valid_data() {
+        let data = json!({"id": "a", "last_modified": 1, "title": "Entrecote",
+                          "author": {"name": "Marie"}, "tags": ["beef"],
+                          "email": "marie@example.com"});
+        validator().validate(&data).unwrap();
+        Validator::default().validate(&json!({"any": "thing"})).unwrap();
+    }
+
+    #[test]
+    fn test_errors_are_addressed_by_path() {
+        let data = json!({"author": {"name": "marie"}, "tags": ["beef", "pork"],
+                          "email": "marie", "extra": true});
+        let errors = validator().errors(&data);
+        let paths: Vec<(&str, &str)> = errors.iter()
+            .map(|e| (e.path.as_str(), e.keyword.as_str()))
+            .collect();
+        assert_eq!(paths,
+                   vec![("/title", "required"),
+                        ("/author/name", "pattern"),
+                        ("/email", "format"),
+                        ("/extra", "additionalProperties"),
+                        ("/tags/1", "enum")]);
+    }
+
+    #[test]
+    fn test_record_validated_before_create() {
+        let mut collection = setup_collection();
+        collection.data = json!({"schema": {"type": "object", "required": ["title"]}})
+            .into();
+        collection.create().unwrap();
+        collection.validator().unwrap();
+
+        let mut record = collection.record("entrecote");
+        match record.create() {
+            Err(KintoError::ValidationError(errors)) => {
+                assert_eq!(errors[0].path, "/title")
+            }
+            result => panic!("Unexpected result {:?}", result),
+        };
+        assert_eq!(collection.list_records().unwrap().len(), 0);
+
+        record.data = json!({"title": "Entrecote"}).into();
+        record.create().unwrap();
+    }
+
+    #[test]
+    fn test_record_validated_before_patch() {
+        let mut collection = setup_collection();
+        collection.data = json!({"schema": {
+            "type": "object",
+            "properties": {"title": {"type": "string"}}
+        }})
+            .into();
+        collection.create().unwrap();
+        collection.validator().unwrap();
+
+        let mut record = collection.record("entrecote");
+        record.data = json!({"title": "Entrecote"}).into();
+        record.create().unwrap();
+        match record.patch(json!({"title": 42})) {
+            Err(KintoError::ValidationError(errors)) => {
+                assert_eq!(errors[0].path, "/title")
+            }
+            result => panic!("Unexpected result {:?}", result),
+        };
+        record.patch(json!({"cut": "rib"})).unwrap();
+        assert_eq!(record.data.clone().unwrap()["title"], "Entrecote");
+
+        record.data = json!({"title": 42}).into();
+        record.set_permissions().unwrap_err();
+    }
+
+    #[test]
+    fn test_circular_references() {
+        let schema = json!({"definitions": {"a": {"$ref": "#/definitions/a"}}});
+        Validator::new(schema).validate(&json!({"title": "a"})).unwrap();
+
+        let schema = json!({
+            "$ref": "#/definitions/a",
+            "definitions": {
+                "a": {"allOf": [{"$ref": "#/definitions/b"}]},
+                "b": {"anyOf": [{"$ref": "#/definitions/a"}]}
+            }
+        });
+        let errors = Validator::new(schema).errors(&json!({"title": "a"}));
+        assert_eq!(errors[0].keyword, "anyOf");
+
+        // Recursive schemas still validate nested values
+        let schema = json!({
+            "$ref": "#/definitions/node",
+            "definitions": {"node": {
+                "type": "object",
+                "properties": {"child": {"$ref": "#/definitions/node"}}
+            }}
+        });
+        let validator = Validator::new(schema);
+        validator.validate(&json!({"child": {"child": {}}})).unwrap();
+        let errors = validator.errors(&json!({"child": {"child": 1}}));
+        assert_eq!(errors[0].path, "/child/child");
+    }
+
+    #[test]
+    fn test_unsupported_patterns_are_not_checked() {
+        let validator = Validator::new(json!({
+            "type": "object",
+            "additionalProperties": false,
+            "properties": {"title": {"type": "string", "pattern": "^(?!draft)"}},
+            "patternProperties": {"^(?=x-)": {"type": "string"}}
+        }));
+        validator.validate(&json!({"title": "draft", "x-tag": "a"})).unwrap();
+        assert!(validator.patterns.is_empty());
+    }
+
+    #[test]
+    fn test_patterns_are_compiled_once() {
+        let validator = Validator::new(json!({
+            "type": "object",
+            "properties": {"tags": {"type": "array", "items": {"pattern": "^[a-z]+$"}}},
+            "patternProperties": {"^x-": {"type": "string"}}
+        }));
+        let mut patterns: Vec<&String> = validator.patterns.keys().collect();
+        patterns.sort();
+        assert_eq!(patterns, vec!["^[a-z]+$", "^x-"]);
+        validator.validate(&json!({"tags": ["meat"], "x-tag": "a"})).unwrap();
+        validator.validate(&json!({"tags": ["Meat"]})).unwrap_err();
+    }
+}
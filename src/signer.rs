@@ -0,0 +1,384 @@
+use serde_json;
+use serde_json::Value;
+
+use error::KintoError;
+use resource::Resource;
+use collection::Collection;
+
+
+/// Review status of a signed collection (kinto-signer plugin).
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignerStatus {
+    WorkInProgress,
+    ToReview,
+    ToSign,
+    ToResign,
+    ToRollback,
+    Signed,
+    Unknown(String),
+}
+
+
+impl<'a> From<&'a str> for SignerStatus {
+    fn from(status: &'a str) -> Self {
+        match status {
+            "work-in-progress" => SignerStatus::WorkInProgress,
+            "to-review" => SignerStatus::ToReview,
+            "to-sign" => SignerStatus::ToSign,
+            "to-resign" => SignerStatus::ToResign,
+            "to-rollback" => SignerStatus::ToRollback,
+            "signed" => SignerStatus::Signed,
+            other => SignerStatus::Unknown(other.to_owned()),
+        }
+    }
+}
+
+
+impl Into<String> for SignerStatus {
+    fn into(self) -> String {
+        match self {
+            SignerStatus::WorkInProgress => "work-in-progress".to_owned(),
+            SignerStatus::ToReview => "to-review".to_owned(),
+            SignerStatus::ToSign => "to-sign".to_owned(),
+            SignerStatus::ToResign => "to-resign".to_owned(),
+            SignerStatus::ToRollback => "to-rollback".to_owned(),
+            SignerStatus::Signed => "signed".to_owned(),
+            SignerStatus::Unknown(status) => status,
+        }
+    }
+}
+
+
+/// Signature metadata stored on destination collections.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Signature {
+    pub x5u: String,
+    pub signature: String,
+    pub mode: String,
+    #[serde(default, rename="ref", skip_serializing_if="Option::is_none")]
+    pub reference: Option<String>,
+}
+
+
+/// Bucket (and optional collection) referenced by a signer mapping.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SignerResource {
+    pub bucket: String,
+    #[serde(default)]
+    pub collection: Option<String>,
+}
+
+
+/// Source, preview and destination of a signed resource.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SignerMapping {
+    pub source: SignerResource,
+    #[serde(default)]
+    pub preview: Option<SignerResource>,
+    pub destination: SignerResource,
+}
+
+
+impl SignerMapping {
+    /// Find the mapping of a source collection in the server capabilities.
+    pub fn find(capabilities: &Value,
+                bucket_id: &str,
+                collection_id: &str)
+                -> Option<SignerMapping> {
+        let resources = match capabilities["signer"]["resources"].as_array() {
+            Some(resources) => resources.clone(),
+            None => return None,
+        };
+
+        let mut bucket_mapping = None;
+
+        for resource in resources {
+            let mapping: SignerMapping = match serde_json::from_value(resource) {
+                Ok(mapping) => mapping,
+                Err(_) => continue,
+            };
+
+            if mapping.source.bucket != bucket_id {
+                continue;
+            }
+
+            match mapping.source.collection.clone() {
+                // Exact collection mapping
+                Some(ref id) if id == collection_id => return Some(mapping),
+                Some(_) => (),
+                // Whole bucket mapping, same collection id on each side
+                None => bucket_mapping = Some(mapping.for_collection(collection_id)),
+            };
+        }
+
+        bucket_mapping
+    }
+
+    /// Resolve a bucket level mapping for a specific collection.
+    fn for_collection(mut self, collection_id: &str) -> SignerMapping {
+        self.source.collection = Some(collection_id.to_owned());
+        match self.preview.as_mut() {
+            Some(preview) => preview.collection = Some(collection_id.to_owned()),
+            None => (),
+        };
+        self.destination.collection = Some(collection_id.to_owned());
+        self
+    }
+}
+
+
+/// Review workflow of a signed collection.
+#[derive(Debug, Clone)]
+pub struct Signer {
+    pub source: Collection,
+    pub mapping: SignerMapping,
+}
+
+
+impl Signer {
+    /// Discover the signer mapping of a source collection.
+    pub fn new(source: Collection) -> Result<Signer, KintoError> {
+        let info = try!(source.get_client().server_info());
+        let bucket_id = try!(source.bucket.get_id().ok_or(KintoError::UndefinedIdError))
+            .to_owned();
+        let collection_id = try!(source.get_id().ok_or(KintoError::UndefinedIdError))
+            .to_owned();
+
+        match SignerMapping::find(&info["capabilities"],
+                                  bucket_id.as_str(),
+                                  collection_id.as_str()) {
+            Some(mapping) => {
+                Ok(Signer {
+                       source: source,
+                       mapping: mapping,
+                   })
+            }
+            None => Err(KintoError::UnavailableEndpointError),
+        }
+    }
+
+    /// Get the preview collection, if the review workflow is enabled.
+    pub fn preview(&self) -> Option<Collection> {
+        self.mapping.preview.as_ref().map(|preview| self.collection_for(preview))
+    }
+
+    /// Get the destination collection.
+    pub fn destination(&self) -> Collection {
+        self.collection_for(&self.mapping.destination)
+    }
+
+    /// Load the current review status of the source collection.
+    pub fn status(&mut self) -> Result<SignerStatus, KintoError> {
+        try!(self.source.load());
+        let data = self.source.data.clone().unwrap_or_default();
+        Ok(match data["status"].as_str() {
+               Some(status) => status.into(),
+               None => SignerStatus::Unknown("".to_owned()),
+           })
+    }
+
+    /// Ask for a review of the source changes.
+    pub fn request_review(&mut self) -> Result<(), KintoError> {
+        self.set_status(SignerStatus::ToReview)
+    }
+
+    /// Approve the changes under review and sign the destination.
+    pub fn approve(&mut self) -> Result<(), KintoError> {
+        self.set_status(SignerStatus::ToSign)
+    }
+
+    /// Decline the changes under review.
+    pub fn decline(&mut self) -> Result<(), KintoError> {
+        self.set_status(SignerStatus::WorkInProgress)
+    }
+
+    /// Reset the source to the destination content.
+    pub fn rollback(&mut self) -> Result<(), KintoError> {
+        self.set_status(SignerStatus::ToRollback)
+    }
+
+    /// Get the signature of the destination collection if signed.
+    pub fn signature(&self) -> Result<Option<Signature>, KintoError> {
+        let mut destination = self.destination();
+        try!(destination.load());
+        let data = destination.data.unwrap_or_default();
+        match data["signature"] {
+            Value::Null => Ok(None),
+            ref signature => {
+                serde_json::from_value(signature.to_owned())
+                    .map(Some)
+                    .map_err(|_| KintoError::SignatureError)
+            }
+        }
+    }
+
+    fn set_status(&mut self, status: SignerStatus) -> Result<(), KintoError> {
+        let status: String = status.into();
+        self.source.patch(json!({"status": status}))
+    }
+
+    fn collection_for(&self, resource: &SignerResource) -> Collection {
+        let client = self.source.get_client();
+        let collection_id = match resource.collection.as_ref() {
+            Some(id) => id.as_str(),
+            None => self.source.get_id().unwrap_or_default(),
+        };
+        client.bucket(resource.bucket.as_str()).collection(collection_id)
+    }
+}
+
+
+#[cfg(test)]
+mod test_signer {
+    use hyper::header::{Authorization, Basic};
+
+    use KintoClient;
+    use error::KintoError;
+    use resource::Resource;
+    use collection::Collection;
+    use permissions::{Permissions, CollectionPermission};
+    use utils::tests::setup_client;
+    use super::{Signer, SignerMapping, SignerResource, SignerStatus};
+
+    /// Source collection of the signer mapping, with a record, and the same
+    /// collection seen by another user allowed to review it.
+    fn setup_source() -> (Collection, Collection) {
+        let client = setup_client();
+        let auth = Authorization(Basic {
+                                     username: "b".to_owned(),
+                                     password: Some("b".to_owned()),
+                                 });
+        let reviewer = KintoClient::new(client.server_url().to_string(), auth.into())
+            .unwrap();
+        let reviewer_id = reviewer.server_info().unwrap()["user"]["id"]
+            .as_str()
+            .unwrap()
+            .to_owned();
+
+        let mut bucket = client.bucket("food");
+        bucket.set().unwrap();
+        let mut collection = bucket.collection("meat");
+        collection.permissions.grant(CollectionPermission::Write, reviewer_id.as_str());
+        collection.set().unwrap();
+        collection.record("entrecote").set().unwrap();
+        (collection, reviewer.bucket("food").collection("meat"))
+    }
+
+    fn count_records(collection: &Collection) -> usize {
+        collection.list_records().unwrap().len()
+    }
+
+    fn capabilities() -> ::serde_json::Value {
+        json!({
+            "signer": {
+                "resources": [{
+                    "source": {"bucket": "main-workspace", "collection": null},
+                    "preview": {"bucket": "main-preview", "collection": null},
+                    "destination": {"bucket": "main", "collection": null}
+                }, {
+                    "source": {"bucket": "security-workspace", "collection": "onecrl"},
+                    "destination": {"bucket": "security", "collection": "certs"}
+                }]
+            }
+        })
+    }
+
+    #[test]
+    fn test_find_bucket_mapping() {
+        let mapping = SignerMapping::find(&capabilities(), "main-workspace", "cfr")
+            .unwrap();
+        assert_eq!(mapping.destination,
+                   SignerResource {
+                       bucket: "main".to_owned(),
+                       collection: Some("cfr".to_owned()),
+                   });
+        assert_eq!(mapping.preview.unwrap().bucket, "main-preview");
+    }
+
+    #[test]
+    fn test_find_collection_mapping() {
+        let mapping = SignerMapping::find(&capabilities(), "security-workspace", "onecrl")
+            .unwrap();
+        assert_eq!(mapping.destination.collection.unwrap(), "certs");
+        assert!(mapping.preview.is_none());
+    }
+
+    #[test]
+    fn test_find_unknown_mapping() {
+        assert!(SignerMapping::find(&capabilities(), "security-workspace", "other")
+                    .is_none());
+        assert!(SignerMapping::find(&json!({}), "main-workspace", "cfr").is_none());
+    }
+
+    #[test]
+    fn test_status_from_str() {
+        assert_eq!(SignerStatus::from("to-review"), SignerStatus::ToReview);
+        assert_eq!(SignerStatus::from("signed"), SignerStatus::Signed);
+        assert_eq!(SignerStatus::from("other"),
+                   SignerStatus::Unknown("other".to_owned()));
+    }
+
+    #[test]
+    fn test_signer_mapping() {
+        let (source, _) = setup_source();
+        let signer = Signer::new(source.clone()).unwrap();
+        assert_eq!(signer.preview().unwrap().bucket.get_id(), Some("food-preview"));
+        assert_eq!(signer.destination().bucket.get_id(), Some("food-signed"));
+        assert_eq!(signer.destination().get_id(), Some("meat"));
+
+        let other = source.get_client().bucket("drinks").collection("wine");
+        match Signer::new(other) {
+            Err(KintoError::UnavailableEndpointError) => (),
+            result => panic!("{:?}", result),
+        };
+    }
+
+    #[test]
+    fn test_review_and_approve() {
+        let (source, reviewed) = setup_source();
+        let mut editor = Signer::new(source).unwrap();
+        editor.request_review().unwrap();
+        assert_eq!(editor.status().unwrap(), SignerStatus::ToReview);
+        assert_eq!(count_records(&editor.preview().unwrap()), 1);
+
+        // Editors cannot review their own changes
+        editor.approve().unwrap_err();
+
+        let mut reviewer = Signer::new(reviewed).unwrap();
+        reviewer.approve().unwrap();
+        assert_eq!(editor.status().unwrap(), SignerStatus::Signed);
+        assert_eq!(count_records(&editor.destination()), 1);
+        assert_eq!(editor.signature().unwrap().unwrap().mode, "p384ecdsa");
+    }
+
+    #[test]
+    fn test_decline_and_rollback() {
+        let (source, reviewed) = setup_source();
+        let mut editor = Signer::new(source.clone()).unwrap();
+        let mut reviewer = Signer::new(reviewed).unwrap();
+        editor.request_review().unwrap();
+        reviewer.decline().unwrap();
+        assert_eq!(editor.status().unwrap(), SignerStatus::WorkInProgress);
+
+        editor.request_review().unwrap();
+        reviewer.approve().unwrap();
+        source.record("onglet").set().unwrap();
+        assert_eq!(count_records(&source), 2);
+        editor.rollback().unwrap();
+        assert_eq!(count_records(&source), 1);
+    }
+
+    #[test]
+    fn test_malformed_signature() {
+        let (source, _) = setup_source();
+        let signer = Signer::new(source).unwrap();
+        let mut destination = signer.destination();
+        destination.bucket.set().unwrap();
+        destination.data = json!({"signature": {"x5u": 42}}).into();
+        destination.set().unwrap();
+        match signer.signature() {
+            Err(KintoError::SignatureError) => (),
+            result => panic!("{:?}", result),
+        };
+    }
+}
@@ -0,0 +1,374 @@
+use std::path::Path;
+
+use serde_json;
+use serde_json::Value;
+use hyper::header::EntityTag;
+use rusqlite::{Connection, OptionalExtension, Row};
+use rusqlite::types::Value as SqlValue;
+
+use error::KintoError;
+use sync::{LocalRecord, LocalStore, matches};
+use utils::timestamp_to_etag;
+
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS records (
+        collection TEXT NOT NULL,
+        id TEXT NOT NULL,
+        data TEXT NOT NULL,
+        status TEXT NOT NULL,
+        base TEXT,
+        PRIMARY KEY (collection, id)
+    );
+    CREATE TABLE IF NOT EXISTS timestamps (
+        collection TEXT PRIMARY KEY,
+        timestamp INTEGER NOT NULL
+    );";
+
+
+/// Local store persisted in a SQLite database.
+///
+/// A database file can hold several collections, each store only sees the
+/// records of its own.
+#[derive(Debug)]
+pub struct SqliteStore {
+    connection: Connection,
+    collection: String,
+}
+
+
+impl SqliteStore {
+    /// Open (or create) the database at `path` for the given collection name
+    /// (e.g. `food/meat`).
+    pub fn open<P>(path: P, collection: &str) -> Result<SqliteStore, KintoError>
+        where P: AsRef<Path>
+    {
+        let connection = try!(Connection::open(path).map_err(|_| KintoError::StoreError));
+        SqliteStore::init(connection, collection)
+    }
+
+    /// Create a store in memory, for tests or short lived processes.
+    pub fn open_in_memory(collection: &str) -> Result<SqliteStore, KintoError> {
+        let connection = try!(Connection::open_in_memory()
+                                  .map_err(|_| KintoError::StoreError));
+        SqliteStore::init(connection, collection)
+    }
+
+    fn init(connection: Connection, collection: &str) -> Result<SqliteStore, KintoError> {
+        try!(connection.execute_batch(SCHEMA).map_err(|_| KintoError::StoreError));
+        Ok(SqliteStore {
+               connection: connection,
+               collection: collection.to_owned(),
+           })
+    }
+
+    /// ETag of the collection at the last synchronization.
+    pub fn etag(&self) -> Result<Option<Vec<EntityTag>>, KintoError> {
+        Ok(try!(self.timestamp()).map(timestamp_to_etag))
+    }
+
+    /// Remove the records and timestamp of the collection.
+    pub fn clear(&mut self) -> Result<(), KintoError> {
+        try!(self.execute("DELETE FROM records WHERE collection = ?1", vec![]));
+        try!(self.execute("DELETE FROM timestamps WHERE collection = ?1", vec![]));
+        Ok(())
+    }
+
+    /// Run a statement, with the collection name as first parameter.
+    fn execute(&self, sql: &str, params: Vec<SqlValue>) -> Result<(), KintoError> {
+        let mut values = vec![SqlValue::Text(self.collection.clone())];
+        values.extend(params);
+        try!(self.connection.execute(sql, values).map_err(|_| KintoError::StoreError));
+        Ok(())
+    }
+
+    /// Query records, with the collection name as first parameter.
+    fn query(&self,
+             sql: &str,
+             params: Vec<SqlValue>)
+             -> Result<Vec<LocalRecord>, KintoError> {
+        let mut values = vec![SqlValue::Text(self.collection.clone())];
+        values.extend(params);
+
+        let mut statement = try!(self.connection
+                                     .prepare(sql)
+                                     .map_err(|_| KintoError::StoreError));
+        let rows = try!(statement.query_map(values, read_row)
+                            .map_err(|_| KintoError::StoreError));
+
+        let mut records = vec![];
+        for row in rows {
+            let row = try!(row.map_err(|_| KintoError::StoreError));
+            records.push(try!(parse_row(row)));
+        }
+        Ok(records)
+    }
+}
+
+
+type RawRow = (String, String, Option<String>);
+
+
+const SELECT_RECORDS: &str = "SELECT data, status, base FROM records
+                              WHERE collection = ?1";
+
+
+fn read_row(row: &Row) -> Result<RawRow, ::rusqlite::Error> {
+    Ok((try!(row.get(0)), try!(row.get(1)), try!(row.get(2))))
+}
+
+
+fn parse_row(row: RawRow) -> Result<LocalRecord, KintoError> {
+    let (data, status, base) = row;
+    let base = match base {
+        Some(base) => Some(try!(serde_json::from_str(base.as_str()))),
+        None => None,
+    };
+    Ok(LocalRecord {
+           data: try!(serde_json::from_str(data.as_str())),
+           status: try!(serde_json::from_value(Value::String(status))),
+           base: base,
+       })
+}
+
+
+/// `json_extract` path of a (dotted) field, with its keys quoted.
+///
+/// SQLite cannot escape quotes in keys: such fields are not filtered in SQL.
+fn json_path(field: &str) -> Option<String> {
+    if field.contains('"') {
+        return None;
+    }
+    let keys: Vec<String> = field.split('.').map(|key| format!(".\"{}\"", key)).collect();
+    Some(format!("${}", keys.concat()))
+}
+
+
+/// SQL value compared with `json_extract`, for scalar JSON values only.
+fn sql_value(value: &Value) -> Option<SqlValue> {
+    match *value {
+        Value::Bool(b) => Some(SqlValue::Integer(b as i64)),
+        Value::Number(ref n) => {
+            match n.as_i64() {
+                Some(i) => Some(SqlValue::Integer(i)),
+                None => n.as_f64().map(SqlValue::Real),
+            }
+        }
+        Value::String(ref s) => Some(SqlValue::Text(s.clone())),
+        _ => None,
+    }
+}
+
+
+impl LocalStore for SqliteStore {
+    fn get(&self, id: &str) -> Result<Option<LocalRecord>, KintoError> {
+        let sql = format!("{} AND id = ?2", SELECT_RECORDS);
+        let values = vec![SqlValue::Text(self.collection.clone()),
+                          SqlValue::Text(id.to_owned())];
+        let row = try!(self.connection
+                           .query_row(sql.as_str(), values, read_row)
+                           .optional()
+                           .map_err(|_| KintoError::StoreError));
+        match row {
+            Some(row) => parse_row(row).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn list(&self) -> Result<Vec<LocalRecord>, KintoError> {
+        self.query(SELECT_RECORDS, vec![])
+    }
+
+    fn put(&mut self, record: LocalRecord) -> Result<(), KintoError> {
+        let id = try!(record.id().ok_or(KintoError::UndefinedIdError)).to_owned();
+        let status = match try!(serde_json::to_value(record.status)) {
+            Value::String(status) => status,
+            _ => return Err(KintoError::StoreError),
+        };
+        let base = match record.base {
+            Some(ref base) => SqlValue::Text(try!(serde_json::to_string(base))),
+            None => SqlValue::Null,
+        };
+        self.execute("INSERT OR REPLACE INTO records (collection, id, data, status, base)
+                      VALUES (?1, ?2, ?3, ?4, ?5)",
+                     vec![SqlValue::Text(id),
+                          SqlValue::Text(try!(serde_json::to_string(&record.data))),
+                          SqlValue::Text(status),
+                          base])
+    }
+
+    fn remove(&mut self, id: &str) -> Result<(), KintoError> {
+        self.execute("DELETE FROM records WHERE collection = ?1 AND id = ?2",
+                     vec![SqlValue::Text(id.to_owned())])
+    }
+
+    fn timestamp(&self) -> Result<Option<u64>, KintoError> {
+        let timestamp: Option<i64> =
+            try!(self.connection
+                     .query_row("SELECT timestamp FROM timestamps WHERE collection = ?1",
+                                vec![SqlValue::Text(self.collection.clone())],
+                                |row| row.get(0))
+                     .optional()
+                     .map_err(|_| KintoError::StoreError));
+        Ok(timestamp.map(|timestamp| timestamp as u64))
+    }
+
+    fn set_timestamp(&mut self, timestamp: u64) -> Result<(), KintoError> {
+        self.execute("INSERT OR REPLACE INTO timestamps (collection, timestamp)
+                      VALUES (?1, ?2)",
+                     vec![SqlValue::Integer(timestamp as i64)])
+    }
+
+    fn begin(&mut self) -> Result<(), KintoError> {
+        self.connection.execute_batch("BEGIN").map_err(|_| KintoError::StoreError)
+    }
+
+    fn commit(&mut self) -> Result<(), KintoError> {
+        self.connection.execute_batch("COMMIT").map_err(|_| KintoError::StoreError)
+    }
+
+    fn rollback(&mut self) -> Result<(), KintoError> {
+        self.connection.execute_batch("ROLLBACK").map_err(|_| KintoError::StoreError)
+    }
+
+    /// Filter the scalar fields in SQL, the others are compared on the
+    /// loaded records.
+    fn filter(&self, filters: &[(&str, Value)]) -> Result<Vec<LocalRecord>, KintoError> {
+        let mut sql = format!("{} AND status != 'deleted'", SELECT_RECORDS);
+        let mut values = vec![];
+
+        for &(field, ref value) in filters {
+            match (json_path(field), sql_value(value)) {
+                (Some(path), Some(value)) => {
+                    sql.push_str(&format!(" AND json_extract(data, ?{}) = ?{}",
+                                          values.len() + 2,
+                                          values.len() + 3));
+                    values.push(SqlValue::Text(path));
+                    values.push(value);
+                }
+                _ => (),
+            };
+        }
+
+        Ok(try!(self.query(sql.as_str(), values))
+               .into_iter()
+               .filter(|record| matches(record, filters))
+               .collect())
+    }
+}
+
+
+#[cfg(test)]
+mod test_sqlite {
+    use std::env;
+    use std::fs;
+
+    use resource::Resource;
+    use sync::{LocalStore, SyncStatus};
+    use utils::uuid4;
+    use utils::tests::setup_collection;
+    use super::SqliteStore;
+
+    #[test]
+    fn test_store_persists_records() {
+        let path = env::temp_dir().join(format!("kinto-{}.sqlite", uuid4()));
+        {
+            let mut store = SqliteStore::open(&path, "food/meat").unwrap();
+            store.create(json!({"id": "entrecote", "title": "beef"})).unwrap();
+            store.set_timestamp(42).unwrap();
+        }
+
+        let store = SqliteStore::open(&path, "food/meat").unwrap();
+        let record = store.get("entrecote").unwrap().unwrap();
+        assert_eq!(record.status, SyncStatus::Created);
+        assert_eq!(record.data["title"], "beef");
+        assert_eq!(store.timestamp().unwrap(), Some(42));
+
+        // Collections are isolated
+        let other = SqliteStore::open(&path, "food/fish").unwrap();
+        assert_eq!(other.list().unwrap().len(), 0);
+        assert_eq!(other.timestamp().unwrap(), None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_store_filter() {
+        let mut store = SqliteStore::open_in_memory("food/meat").unwrap();
+        store.create(json!({"id": "a", "kind": "beef", "origin": {"country": "fr"}}))
+            .unwrap();
+        store.create(json!({"id": "b", "kind": "beef", "origin": {"country": "ar"}}))
+            .unwrap();
+        store.create(json!({"id": "c", "kind": "lamb", "tags": ["grill"]})).unwrap();
+
+        assert_eq!(store.filter(&[("kind", json!("beef"))]).unwrap().len(), 2);
+        let records = store.filter(&[("kind", json!("beef")),
+                                     ("origin.country", json!("fr"))])
+            .unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].data["id"], "a");
+        assert_eq!(store.filter(&[("tags", json!(["grill"]))]).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_store_filter_quotes_fields() {
+        let mut store = SqliteStore::open_in_memory("food/meat").unwrap();
+        store.create(json!({"id": "a", "grade[1]": "A", "say \"moo\"": true}))
+            .unwrap();
+        store.create(json!({"id": "b", "grade": ["B", "B"]})).unwrap();
+
+        let records = store.filter(&[("grade[1]", json!("A"))]).unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].data["id"], "a");
+        assert_eq!(store.filter(&[("grade[1]", json!("B"))]).unwrap().len(), 0);
+        assert_eq!(store.filter(&[("say \"moo\"", json!(true))]).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_store_transactions() {
+        let mut store = SqliteStore::open_in_memory("food/meat").unwrap();
+        store.begin().unwrap();
+        store.create(json!({"id": "a"})).unwrap();
+        store.rollback().unwrap();
+        assert_eq!(store.list().unwrap().len(), 0);
+
+        store.begin().unwrap();
+        store.create(json!({"id": "a"})).unwrap();
+        store.set_timestamp(42).unwrap();
+        store.commit().unwrap();
+        assert_eq!(store.list().unwrap().len(), 1);
+        assert_eq!(store.timestamp().unwrap(), Some(42));
+    }
+
+    #[test]
+    fn test_sync_resumes_from_stored_timestamp() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        collection.record("entrecote").create().unwrap();
+
+        collection.record("onglet").create().unwrap();
+
+        let mut store = SqliteStore::open_in_memory("food/meat").unwrap();
+        let result = collection.sync(&mut store).unwrap();
+        assert_eq!(result.created.len(), 2);
+        assert!(store.etag().unwrap().is_some());
+
+        // Change the local copy behind the sync's back: only the records
+        // changed since the stored timestamp are downloaded again
+        let mut local = store.get("entrecote").unwrap().unwrap();
+        local.data["title"] = "stale".into();
+        store.put(local).unwrap();
+        let mut record = collection.record("onglet");
+        record.data = json!({"title": "beef"}).into();
+        record.set().unwrap();
+
+        let result = collection.sync(&mut store).unwrap();
+        assert_eq!(result.created.len(), 0);
+        assert_eq!(result.updated.len(), 1);
+        assert_eq!(result.updated[0]["id"], "onglet");
+        assert_eq!(store.get("entrecote").unwrap().unwrap().data["title"], "stale");
+
+        // No transaction is left open
+        store.begin().unwrap();
+        store.commit().unwrap();
+    }
+}
@@ -0,0 +1,803 @@
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+use serde_json::Value;
+use hyper::header::{IfMatch, IfNoneMatch};
+
+use error::KintoError;
+use batch::{BatchRequest, BatchResponseWrapper, BATCH_MAX_REQUESTS};
+use request::{KintoRequest, PayloadedEndpoint, PluralEndpoint};
+use resource::Resource;
+use collection::Collection;
+use conflict::{Conflict, ConflictStrategy, FieldConflict, Resolution, without_timestamp};
+use utils::{timestamp_to_etag, etag_to_timestamp, uuid4};
+
+
+/// Synchronization state of a local record.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum SyncStatus {
+    #[serde(rename="created")]
+    Created,
+    #[serde(rename="updated")]
+    Updated,
+    #[serde(rename="deleted")]
+    Deleted,
+    #[serde(rename="synced")]
+    Synced,
+}
+
+
+/// Record stored locally, with its synchronization state.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocalRecord {
+    pub data: Value,
+    #[serde(rename="_status")]
+    pub status: SyncStatus,
+    /// Last synchronized version of a locally changed record.
+    #[serde(default, skip_serializing_if="Option::is_none")]
+    pub base: Option<Value>,
+}
+
+
+impl LocalRecord {
+    pub fn new(data: Value, status: SyncStatus) -> LocalRecord {
+        LocalRecord {
+            data: data,
+            status: status,
+            base: None,
+        }
+    }
+
+    /// Version the local changes of this record were made on.
+    pub fn synced_version(&self) -> Option<Value> {
+        match self.status {
+            SyncStatus::Synced => Some(self.data.clone()),
+            SyncStatus::Created => None,
+            _ => self.base.clone(),
+        }
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.data["id"].as_str()
+    }
+
+    /// Timestamp of the last known server version.
+    pub fn last_modified(&self) -> Option<u64> {
+        self.data["last_modified"].as_u64()
+    }
+}
+
+
+/// Storage of the local copy of a collection.
+///
+/// Only the storage primitives have to be implemented, local changes are
+/// tracked by the provided methods.
+pub trait LocalStore {
+    fn get(&self, id: &str) -> Result<Option<LocalRecord>, KintoError>;
+
+    fn list(&self) -> Result<Vec<LocalRecord>, KintoError>;
+
+    fn put(&mut self, record: LocalRecord) -> Result<(), KintoError>;
+
+    fn remove(&mut self, id: &str) -> Result<(), KintoError>;
+
+    /// Collection timestamp of the last synchronization.
+    fn timestamp(&self) -> Result<Option<u64>, KintoError>;
+
+    fn set_timestamp(&mut self, timestamp: u64) -> Result<(), KintoError>;
+
+    /// Group the next writes until `commit` or `rollback` (e.g. in a database
+    /// transaction). Stores without transactions write right away.
+    fn begin(&mut self) -> Result<(), KintoError> {
+        Ok(())
+    }
+
+    /// Keep the writes since `begin`.
+    fn commit(&mut self) -> Result<(), KintoError> {
+        Ok(())
+    }
+
+    /// Cancel the writes since `begin`, if supported.
+    fn rollback(&mut self) -> Result<(), KintoError> {
+        Ok(())
+    }
+
+    /// Create a record locally, generating its id if missing.
+    fn create(&mut self, data: Value) -> Result<Value, KintoError> {
+        let mut data = data;
+        if data["id"].as_str().is_none() {
+            data["id"] = uuid4().into();
+        }
+        try!(self.put(LocalRecord::new(data.clone(), SyncStatus::Created)));
+        Ok(data)
+    }
+
+    /// Update a record locally.
+    fn update(&mut self, data: Value) -> Result<Value, KintoError> {
+        let mut data = data;
+        let id = try!(data["id"].as_str().ok_or(KintoError::UndefinedIdError)).to_owned();
+
+        let record = match try!(self.get(id.as_str())) {
+            Some(existing) => {
+                // Keep the server timestamp for the push precondition
+                if data["last_modified"].is_null() {
+                    data["last_modified"] = existing.data["last_modified"].clone();
+                }
+                let status = match existing.status {
+                    SyncStatus::Created => SyncStatus::Created,
+                    _ => SyncStatus::Updated,
+                };
+                LocalRecord {
+                    data: data.clone(),
+                    status: status,
+                    base: existing.synced_version(),
+                }
+            }
+            None => LocalRecord::new(data.clone(), SyncStatus::Updated),
+        };
+
+        try!(self.put(record));
+        Ok(data)
+    }
+
+    /// Delete a record locally.
+    ///
+    /// Records that were never published are removed right away, the others
+    /// are kept until the deletion is synchronized.
+    fn delete(&mut self, id: &str) -> Result<(), KintoError> {
+        match try!(self.get(id)) {
+            Some(ref record) if record.status == SyncStatus::Created => self.remove(id),
+            Some(record) => {
+                self.put(LocalRecord {
+                             base: record.synced_version(),
+                             data: record.data,
+                             status: SyncStatus::Deleted,
+                         })
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// List the records with local changes.
+    fn pending(&self) -> Result<Vec<LocalRecord>, KintoError> {
+        Ok(try!(self.list())
+               .into_iter()
+               .filter(|record| record.status != SyncStatus::Synced)
+               .collect())
+    }
+
+    /// List the records whose fields have the given values, ignoring the
+    /// local deletions.
+    ///
+    /// Nested fields are separated with dots (e.g. `author.name`).
+    fn filter(&self, filters: &[(&str, Value)]) -> Result<Vec<LocalRecord>, KintoError> {
+        Ok(try!(self.list())
+               .into_iter()
+               .filter(|record| matches(record, filters))
+               .collect())
+    }
+}
+
+
+/// Whether a local record is not deleted and matches the field filters.
+pub fn matches(record: &LocalRecord, filters: &[(&str, Value)]) -> bool {
+    record.status != SyncStatus::Deleted &&
+    filters.iter().all(|&(field, ref value)| {
+                           let pointer = format!("/{}", field.replace(".", "/"));
+                           record.data.pointer(pointer.as_str()) == Some(value)
+                       })
+}
+
+
+/// Local store kept in memory.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    records: HashMap<String, LocalRecord>,
+    timestamp: Option<u64>,
+}
+
+
+impl MemoryStore {
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+}
+
+
+impl LocalStore for MemoryStore {
+    fn get(&self, id: &str) -> Result<Option<LocalRecord>, KintoError> {
+        Ok(self.records.get(id).cloned())
+    }
+
+    fn list(&self) -> Result<Vec<LocalRecord>, KintoError> {
+        Ok(self.records.values().cloned().collect())
+    }
+
+    fn put(&mut self, record: LocalRecord) -> Result<(), KintoError> {
+        let id = try!(record.id().ok_or(KintoError::UndefinedIdError)).to_owned();
+        self.records.insert(id, record);
+        Ok(())
+    }
+
+    fn remove(&mut self, id: &str) -> Result<(), KintoError> {
+        self.records.remove(id);
+        Ok(())
+    }
+
+    fn timestamp(&self) -> Result<Option<u64>, KintoError> {
+        Ok(self.timestamp)
+    }
+
+    fn set_timestamp(&mut self, timestamp: u64) -> Result<(), KintoError> {
+        self.timestamp = Some(timestamp);
+        Ok(())
+    }
+}
+
+
+/// Local change that could not be applied because the server version changed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncConflict {
+    pub local: LocalRecord,
+    /// Server version of the record, `Null` if it does not exist anymore.
+    pub remote: Value,
+    /// Fields left unresolved by the conflict strategy.
+    pub fields: Vec<FieldConflict>,
+}
+
+
+/// Local change rejected by the server.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyncError {
+    pub id: String,
+    pub status: u16,
+    pub details: Value,
+}
+
+
+/// Report of a synchronization.
+#[derive(Debug, Clone, Default)]
+pub struct SyncResult {
+    /// Remote records created locally.
+    pub created: Vec<Value>,
+    /// Remote changes applied locally.
+    pub updated: Vec<Value>,
+    /// Remote deletions applied locally.
+    pub deleted: Vec<Value>,
+    /// Local changes published on the server.
+    pub published: Vec<Value>,
+    pub conflicts: Vec<SyncConflict>,
+    pub errors: Vec<SyncError>,
+}
+
+
+impl SyncResult {
+    /// Whether every change was synchronized.
+    pub fn ok(&self) -> bool {
+        self.conflicts.is_empty() && self.errors.is_empty()
+    }
+}
+
+
+/// Synchronize a local store with a collection.
+///
+/// Remote changes since the last synchronization are pulled first, then the
+/// local changes are pushed with concurrency control.
+pub fn sync<S>(collection: &Collection, store: &mut S) -> Result<SyncResult, KintoError>
+    where S: LocalStore
+{
+    let mut result = SyncResult::default();
+    try!(pull(collection, store, &mut result));
+    try!(push(collection, store, &mut result));
+
+    // Move the local timestamp after our own changes, reporting the remote
+    // changes made meanwhile
+    if !result.published.is_empty() {
+        try!(pull(collection, store, &mut result));
+    }
+    Ok(result)
+}
+
+
+/// Synchronize a local store with a collection, resolving the conflicts with
+/// `strategy`.
+///
+/// Resolved records are published in the same pass, the other conflicts are
+/// reported with their unresolved fields.
+pub fn sync_with<S, C>(collection: &Collection,
+                       store: &mut S,
+                       strategy: &C)
+                       -> Result<SyncResult, KintoError>
+    where S: LocalStore,
+          C: ConflictStrategy
+{
+    let mut result = try!(sync(collection, store));
+    if result.conflicts.is_empty() {
+        return Ok(result);
+    }
+
+    for conflict in mem::take(&mut result.conflicts) {
+        match try!(resolve(store, conflict, strategy)) {
+            Some(unresolved) => result.conflicts.push(unresolved),
+            None => (),
+        };
+    }
+
+    let published = result.published.len();
+    try!(push(collection, store, &mut result));
+    if result.published.len() > published {
+        try!(pull(collection, store, &mut result));
+    }
+    Ok(result)
+}
+
+
+/// Apply the resolution of a conflict to the local store.
+fn resolve<S, C>(store: &mut S,
+                 conflict: SyncConflict,
+                 strategy: &C)
+                 -> Result<Option<SyncConflict>, KintoError>
+    where S: LocalStore,
+          C: ConflictStrategy
+{
+    let id = try!(conflict.local.id().ok_or(KintoError::UndefinedIdError)).to_owned();
+    let local = match conflict.local.status {
+        SyncStatus::Deleted => Value::Null,
+        _ => conflict.local.data.clone(),
+    };
+
+    let resolution = strategy.resolve(&Conflict {
+                                          base: conflict.local.synced_version(),
+                                          local: local,
+                                          remote: conflict.remote.clone(),
+                                      });
+
+    let data = match resolution {
+        Resolution::Resolved(data) => data,
+        Resolution::Unresolved(fields) => {
+            return Ok(Some(SyncConflict {
+                               fields: fields,
+                               ..conflict
+                           }))
+        }
+    };
+
+    let remote = conflict.remote;
+    let record = if data.is_null() {
+        if remote.is_null() {
+            return store.remove(id.as_str()).map(|_| None);
+        }
+        LocalRecord {
+            data: remote.clone(),
+            status: SyncStatus::Deleted,
+            base: Some(remote),
+        }
+    } else if same_content(&data, &remote) {
+        LocalRecord::new(remote, SyncStatus::Synced)
+    } else if remote.is_null() {
+        LocalRecord::new(without_timestamp(&data), SyncStatus::Created)
+    } else {
+        // Apply the resolution on top of the server version
+        let mut data = data;
+        data["last_modified"] = remote["last_modified"].clone();
+        LocalRecord {
+            data: data,
+            status: SyncStatus::Updated,
+            base: Some(remote),
+        }
+    };
+
+    try!(store.put(record));
+    Ok(None)
+}
+
+
+/// Apply the remote changes to the local store.
+fn pull<S>(collection: &Collection,
+           store: &mut S,
+           result: &mut SyncResult)
+           -> Result<(), KintoError>
+    where S: LocalStore
+{
+    let resource = collection.new_record();
+    let mut request = try!(resource.list_request());
+    match try!(store.timestamp()) {
+        Some(timestamp) => {
+            request.since(timestamp);
+        }
+        None => (),
+    };
+
+    let response = try!(request.follow_subrequests());
+
+    // Apply the changes with the new timestamp at once
+    transaction(store, |store| {
+        for remote in response.body["data"].as_array().cloned().unwrap_or_default() {
+            let id = match remote["id"].as_str() {
+                Some(id) => id.to_owned(),
+                None => continue,
+            };
+            let deleted = remote["deleted"].as_bool().unwrap_or(false);
+
+            match try!(store.get(id.as_str())) {
+                None => {
+                    if !deleted {
+                        let record = LocalRecord::new(remote.clone(), SyncStatus::Synced);
+                        try!(store.put(record));
+                        result.created.push(remote);
+                    }
+                }
+                Some(local) => {
+                    match local.status {
+                        SyncStatus::Synced if deleted => {
+                            try!(store.remove(id.as_str()));
+                            result.deleted.push(remote);
+                        }
+                        SyncStatus::Synced => {
+                            if local.data != remote {
+                                try!(store.put(LocalRecord::new(remote.clone(),
+                                                                SyncStatus::Synced)));
+                                result.updated.push(remote);
+                            }
+                        }
+                        // Deleted on both sides
+                        SyncStatus::Deleted if deleted => try!(store.remove(id.as_str())),
+                        _ => {
+                            if !deleted && same_content(&local.data, &remote) {
+                                let record = LocalRecord::new(remote, SyncStatus::Synced);
+                                try!(store.put(record));
+                            } else {
+                                let remote = if deleted { Value::Null } else { remote };
+                                result.conflicts.push(SyncConflict {
+                                                          local: local,
+                                                          remote: remote,
+                                                          fields: vec![],
+                                                      });
+                            }
+                        }
+                    }
+                }
+            };
+        }
+
+        match etag_to_timestamp(&response.headers) {
+            Some(timestamp) => try!(store.set_timestamp(timestamp)),
+            None => (),
+        };
+        Ok(())
+    })
+}
+
+
+/// Publish the local changes, in batches.
+fn push<S>(collection: &Collection,
+           store: &mut S,
+           result: &mut SyncResult)
+           -> Result<(), KintoError>
+    where S: LocalStore
+{
+    let conflicting: HashSet<String> = result.conflicts
+        .iter()
+        .filter_map(|conflict| conflict.local.id().map(|id| id.to_owned()))
+        .collect();
+
+    let pending: Vec<LocalRecord> = try!(store.pending())
+        .into_iter()
+        .filter(|record| match record.id() {
+                    Some(id) => !conflicting.contains(id),
+                    None => false,
+                })
+        .collect();
+
+    for chunk in pending.chunks(BATCH_MAX_REQUESTS) {
+        let mut batch = BatchRequest::new(collection.get_client());
+
+        for local in chunk {
+            let record = collection.record(local.id().unwrap_or_default());
+            let etag = local.last_modified().map(timestamp_to_etag);
+
+            match local.status {
+                SyncStatus::Deleted => {
+                    let mut request = try!(record.delete_request());
+                    match etag {
+                        Some(etag) => {
+                            request.if_match(IfMatch::Items(etag));
+                        }
+                        None => (),
+                    };
+                    batch.add_request(request);
+                }
+                status => {
+                    let mut request = try!(record.update_request());
+                    request.body(Some(json!({"data": without_timestamp(&local.data)})));
+                    match (status, etag) {
+                        (SyncStatus::Created, _) => {
+                            request.if_none_match(IfNoneMatch::Any);
+                        }
+                        (_, Some(etag)) => {
+                            request.if_match(IfMatch::Items(etag));
+                        }
+                        _ => (),
+                    };
+                    batch.add_request(request);
+                }
+            };
+        }
+
+        let responses: BatchResponseWrapper = try!(batch.send()).into();
+
+        // Record the results of the batch at once
+        try!(transaction(store, |store| {
+            for (local, response) in chunk.iter().zip(responses.responses) {
+                let id = local.id().unwrap_or_default().to_owned();
+                let status = response.status.to_u16();
+
+                // A record already deleted on the server is gone as expected
+                if local.status == SyncStatus::Deleted && status == 404 {
+                    try!(store.remove(id.as_str()));
+                    result.published.push(json!({"id": id, "deleted": true}));
+                } else if response.status.is_success() {
+                    let data = response.body["data"].to_owned();
+                    if local.status == SyncStatus::Deleted {
+                        try!(store.remove(id.as_str()));
+                    } else {
+                        let record = LocalRecord::new(data.clone(), SyncStatus::Synced);
+                        try!(store.put(record));
+                    }
+                    result.published.push(data);
+                } else if status == 412 {
+                    result.conflicts.push(SyncConflict {
+                                              local: local.clone(),
+                                              remote: response.body["details"]["existing"]
+                                                  .to_owned(),
+                                              fields: vec![],
+                                          });
+                } else {
+                    result.errors.push(SyncError {
+                                           id: id,
+                                           status: status,
+                                           details: response.body,
+                                       });
+                }
+            }
+            Ok(())
+        }));
+    }
+    Ok(())
+}
+
+
+/// Write the changes of `apply` in a single store transaction.
+fn transaction<S, F>(store: &mut S, apply: F) -> Result<(), KintoError>
+    where S: LocalStore,
+          F: FnOnce(&mut S) -> Result<(), KintoError>
+{
+    try!(store.begin());
+    match apply(store) {
+        Ok(()) => store.commit(),
+        Err(error) => {
+            let _ = store.rollback();
+            Err(error)
+        }
+    }
+}
+
+
+/// Whether a local and a remote record have the same content.
+fn same_content(local: &Value, remote: &Value) -> bool {
+    without_timestamp(local) == without_timestamp(remote)
+}
+
+
+#[cfg(test)]
+mod test_sync {
+    use KintoError;
+    use resource::Resource;
+    use collection::Collection;
+    use utils::tests::setup_collection;
+    use conflict::{ServerWins, ThreeWayMerge};
+    use super::{LocalRecord, LocalStore, MemoryStore, SyncStatus};
+
+    /// Store changing the server once the first local record is published.
+    struct ConcurrentStore {
+        store: MemoryStore,
+        collection: Option<Collection>,
+    }
+
+    impl LocalStore for ConcurrentStore {
+        fn get(&self, id: &str) -> Result<Option<LocalRecord>, KintoError> {
+            self.store.get(id)
+        }
+
+        fn list(&self) -> Result<Vec<LocalRecord>, KintoError> {
+            self.store.list()
+        }
+
+        fn put(&mut self, record: LocalRecord) -> Result<(), KintoError> {
+            if record.status == SyncStatus::Synced {
+                match self.collection.take() {
+                    Some(collection) => try!(collection.record("onglet").create()),
+                    None => (),
+                };
+            }
+            self.store.put(record)
+        }
+
+        fn remove(&mut self, id: &str) -> Result<(), KintoError> {
+            self.store.remove(id)
+        }
+
+        fn timestamp(&self) -> Result<Option<u64>, KintoError> {
+            self.store.timestamp()
+        }
+
+        fn set_timestamp(&mut self, timestamp: u64) -> Result<(), KintoError> {
+            self.store.set_timestamp(timestamp)
+        }
+    }
+
+    #[test]
+    fn test_local_changes() {
+        let mut store = MemoryStore::new();
+        let data = store.create(json!({"title": "entrecote"})).unwrap();
+        let id = data["id"].as_str().unwrap().to_owned();
+        assert_eq!(store.get(&id).unwrap().unwrap().status, SyncStatus::Created);
+
+        store.update(json!({"id": id, "title": "onglet"})).unwrap();
+        assert_eq!(store.get(&id).unwrap().unwrap().status, SyncStatus::Created);
+
+        store.delete(&id).unwrap();
+        assert!(store.get(&id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_sync_pulls_remote_records() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        collection.record("entrecote").create().unwrap();
+
+        let mut store = MemoryStore::new();
+        let result = collection.sync(&mut store).unwrap();
+        assert_eq!(result.created.len(), 1);
+        assert_eq!(store.get("entrecote").unwrap().unwrap().status, SyncStatus::Synced);
+        assert!(store.timestamp().unwrap().is_some());
+
+        collection.record("entrecote").delete().unwrap();
+        let result = collection.sync(&mut store).unwrap();
+        assert_eq!(result.deleted.len(), 1);
+        assert!(store.get("entrecote").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_sync_pushes_local_changes() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+
+        let mut store = MemoryStore::new();
+        store.create(json!({"id": "entrecote", "title": "beef"})).unwrap();
+        let result = collection.sync(&mut store).unwrap();
+        assert!(result.ok());
+        assert_eq!(result.published.len(), 1);
+
+        let mut record = collection.record("entrecote");
+        record.load().unwrap();
+        assert_eq!(record.data.unwrap()["title"], "beef");
+
+        store.update(json!({"id": "entrecote", "title": "lamb"})).unwrap();
+        collection.sync(&mut store).unwrap();
+        let mut record = collection.record("entrecote");
+        record.load().unwrap();
+        assert_eq!(record.data.unwrap()["title"], "lamb");
+
+        store.delete("entrecote").unwrap();
+        collection.sync(&mut store).unwrap();
+        assert_eq!(collection.list_records().unwrap().len(), 0);
+        assert!(store.pending().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sync_deletes_records_already_gone() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+
+        let mut store = MemoryStore::new();
+        store.put(LocalRecord::new(json!({"id": "entrecote"}), SyncStatus::Deleted))
+            .unwrap();
+        let result = collection.sync(&mut store).unwrap();
+        assert!(result.ok());
+        assert_eq!(result.published[0]["deleted"].as_bool(), Some(true));
+        assert!(store.get("entrecote").unwrap().is_none());
+        assert!(store.pending().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_sync_reports_changes_pulled_after_push() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+
+        let mut store = ConcurrentStore {
+            store: MemoryStore::new(),
+            collection: Some(collection.clone()),
+        };
+        store.create(json!({"id": "entrecote"})).unwrap();
+        let result = collection.sync(&mut store).unwrap();
+        assert_eq!(result.published.len(), 1);
+        assert_eq!(result.created.len(), 1);
+        assert_eq!(result.created[0]["id"], "onglet");
+        assert!(store.get("onglet").unwrap().is_some());
+    }
+
+    #[test]
+    fn test_sync_reports_conflicts() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        collection.record("entrecote").create().unwrap();
+
+        let mut store = MemoryStore::new();
+        collection.sync(&mut store).unwrap();
+        store.update(json!({"id": "entrecote", "title": "local"})).unwrap();
+
+        let mut record = collection.record("entrecote");
+        record.data = json!({"title": "remote"}).into();
+        record.update().unwrap();
+
+        let result = collection.sync(&mut store).unwrap();
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].remote["title"], "remote");
+        assert_eq!(store.get("entrecote").unwrap().unwrap().status,
+                   SyncStatus::Updated);
+    }
+
+    #[test]
+    fn test_sync_with_server_wins() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        collection.record("entrecote").create().unwrap();
+
+        let mut store = MemoryStore::new();
+        collection.sync(&mut store).unwrap();
+        store.delete("entrecote").unwrap();
+
+        let mut record = collection.record("entrecote");
+        record.data = json!({"title": "remote"}).into();
+        record.update().unwrap();
+
+        let result = collection.sync_with(&mut store, &ServerWins).unwrap();
+        assert!(result.ok());
+        let local = store.get("entrecote").unwrap().unwrap();
+        assert_eq!(local.status, SyncStatus::Synced);
+        assert_eq!(local.data["title"], "remote");
+    }
+
+    #[test]
+    fn test_sync_with_three_way_merge() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        let mut record = collection.record("entrecote");
+        record.data = json!({"title": "beef", "cut": "rib"}).into();
+        record.create().unwrap();
+
+        let mut store = MemoryStore::new();
+        collection.sync(&mut store).unwrap();
+        store.update(json!({"id": "entrecote", "title": "lamb", "cut": "rib"})).unwrap();
+
+        record.data = json!({"title": "beef", "cut": "loin"}).into();
+        record.update().unwrap();
+
+        let result = collection.sync_with(&mut store, &ThreeWayMerge).unwrap();
+        assert!(result.ok());
+        record.load().unwrap();
+        let data = record.data.unwrap();
+        assert_eq!(data["title"], "lamb");
+        assert_eq!(data["cut"], "loin");
+
+        // Same field changed on both sides
+        store.update(json!({"id": "entrecote", "title": "pork", "cut": "loin"})).unwrap();
+        record.data = json!({"title": "veal", "cut": "loin"}).into();
+        record.update().unwrap();
+
+        let result = collection.sync_with(&mut store, &ThreeWayMerge).unwrap();
+        assert_eq!(result.conflicts.len(), 1);
+        assert_eq!(result.conflicts[0].fields[0].path, "/title");
+    }
+}
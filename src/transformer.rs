@@ -0,0 +1,416 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use serde_json;
+use serde_json::Value;
+use openssl::base64;
+use openssl::rand::rand_bytes;
+use openssl::symm::{Cipher, decrypt_aead, encrypt_aead};
+
+use error::KintoError;
+
+
+/// Fields that stay readable by the server.
+const CLEAR_FIELDS: [&str; 3] = ["id", "last_modified", "deleted"];
+
+
+/// Transform the record data before sending and after receiving it.
+///
+/// `uri` is the URI of the record the data belongs to.
+pub trait RecordTransformer: fmt::Debug + Send + Sync {
+    /// Transform the data sent to the server.
+    fn encode(&self, uri: &str, data: Value) -> Result<Value, KintoError>;
+
+    /// Revert the transformation of the data received from the server.
+    fn decode(&self, uri: &str, data: Value) -> Result<Value, KintoError>;
+}
+
+
+/// Ordered list of transformers.
+///
+/// Data is encoded by each transformer in order, and decoded in reverse order.
+///
+/// Transformers only apply to the records saved and loaded through a
+/// `Collection` (`Record::load`, `set`, `create`, `update`, `patch` and
+/// `Collection::list_records`). The synchronization, watchers, batches and
+/// archives send and receive the records data as stored on the server, and
+/// the transformed records cannot have attachments.
+#[derive(Debug, Clone, Default)]
+pub struct TransformerPipeline {
+    transformers: Vec<Arc<dyn RecordTransformer>>,
+}
+
+
+impl TransformerPipeline {
+    pub fn new() -> TransformerPipeline {
+        TransformerPipeline::default()
+    }
+
+    /// Add a transformer at the end of the pipeline.
+    pub fn push<T>(&mut self, transformer: T) -> &mut Self
+        where T: RecordTransformer + 'static
+    {
+        self.transformers.push(Arc::new(transformer));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transformers.is_empty()
+    }
+
+    pub fn encode(&self, uri: &str, data: Value) -> Result<Value, KintoError> {
+        let mut data = data;
+        for transformer in &self.transformers {
+            data = try!(transformer.encode(uri, data));
+        }
+        Ok(data)
+    }
+
+    pub fn decode(&self, uri: &str, data: Value) -> Result<Value, KintoError> {
+        let mut data = data;
+        for transformer in self.transformers.iter().rev() {
+            data = try!(transformer.decode(uri, data));
+        }
+        Ok(data)
+    }
+}
+
+
+/// Encrypt the record data with AES-256-GCM.
+///
+/// Only the `id` and `last_modified` fields are left in clear, the other
+/// fields are replaced by `ciphertext`, `iv`, `tag` and the id of the key
+/// used (`key_id`).
+///
+/// The record URI is authenticated with the content: an encrypted payload
+/// copied to another record or collection cannot be decrypted.
+#[derive(Clone)]
+pub struct AesGcmTransformer {
+    key_id: String,
+    keys: HashMap<String, Vec<u8>>,
+}
+
+
+impl fmt::Debug for AesGcmTransformer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Never print the keys
+        f.debug_struct("AesGcmTransformer")
+            .field("key_id", &self.key_id)
+            .finish()
+    }
+}
+
+
+impl AesGcmTransformer {
+    /// Create a transformer encrypting with a 256 bits key.
+    pub fn new(key_id: &str, key: &[u8]) -> Result<AesGcmTransformer, KintoError> {
+        let mut transformer = AesGcmTransformer {
+            key_id: key_id.to_owned(),
+            keys: HashMap::new(),
+        };
+        try!(transformer.add_key(key_id, key));
+        Ok(transformer)
+    }
+
+    /// Generate a random 256 bits key.
+    pub fn generate_key() -> Result<Vec<u8>, KintoError> {
+        let mut key = vec![0; 32];
+        try!(rand_bytes(&mut key).map_err(|_| KintoError::TransformError));
+        Ok(key)
+    }
+
+    /// Id of the key used to encrypt.
+    pub fn key_id(&self) -> &str {
+        self.key_id.as_str()
+    }
+
+    /// Add a key only used to decrypt (e.g. a previous key).
+    pub fn add_key(&mut self, key_id: &str, key: &[u8]) -> Result<&mut Self, KintoError> {
+        if key.len() != 32 {
+            return Err(KintoError::TransformError);
+        }
+        self.keys.insert(key_id.to_owned(), key.to_vec());
+        Ok(self)
+    }
+
+    /// Encrypt with a new key, previous keys are kept to decrypt existing
+    /// records until they are encrypted again.
+    pub fn rotate(&mut self, key_id: &str, key: &[u8]) -> Result<&mut Self, KintoError> {
+        try!(self.add_key(key_id, key));
+        self.key_id = key_id.to_owned();
+        Ok(self)
+    }
+
+    /// Whether the data is encrypted with an older key.
+    pub fn needs_rotation(&self, data: &Value) -> bool {
+        match data["key_id"].as_str() {
+            Some(key_id) => key_id != self.key_id,
+            None => false,
+        }
+    }
+}
+
+
+impl RecordTransformer for AesGcmTransformer {
+    fn encode(&self, uri: &str, data: Value) -> Result<Value, KintoError> {
+        let mut content = match data {
+            Value::Object(map) => map,
+            _ => return Err(KintoError::TransformError),
+        };
+
+        let mut encoded = json!({});
+        for field in CLEAR_FIELDS.iter() {
+            match content.remove(*field) {
+                Some(value) => encoded[*field] = value,
+                None => (),
+            };
+        }
+
+        let plaintext = try!(serde_json::to_vec(&Value::Object(content)));
+        let key = try!(self.keys.get(&self.key_id).ok_or(KintoError::TransformError));
+
+        let mut iv = [0; 12];
+        let mut tag = [0; 16];
+        try!(rand_bytes(&mut iv).map_err(|_| KintoError::TransformError));
+        let ciphertext = try!(encrypt_aead(Cipher::aes_256_gcm(),
+                                           key,
+                                           Some(&iv),
+                                           uri.as_bytes(),
+                                           &plaintext,
+                                           &mut tag)
+                                  .map_err(|_| KintoError::TransformError));
+
+        encoded["key_id"] = self.key_id.clone().into();
+        encoded["iv"] = base64::encode_block(&iv).into();
+        encoded["tag"] = base64::encode_block(&tag).into();
+        encoded["ciphertext"] = base64::encode_block(&ciphertext).into();
+        Ok(encoded)
+    }
+
+    fn decode(&self, uri: &str, data: Value) -> Result<Value, KintoError> {
+        // Tombstones have no content
+        if data["deleted"].as_bool().unwrap_or(false) {
+            return Ok(data);
+        }
+
+        let key_id = try!(data["key_id"].as_str().ok_or(KintoError::TransformError));
+        let key = try!(self.keys.get(key_id).ok_or(KintoError::TransformError));
+        let iv = try!(decode_field(&data, "iv"));
+        let tag = try!(decode_field(&data, "tag"));
+        let ciphertext = try!(decode_field(&data, "ciphertext"));
+
+        let plaintext = try!(decrypt_aead(Cipher::aes_256_gcm(),
+                                          key,
+                                          Some(&iv),
+                                          uri.as_bytes(),
+                                          &ciphertext,
+                                          &tag)
+                                 .map_err(|_| KintoError::TransformError));
+
+        let mut decoded: Value = try!(serde_json::from_slice(&plaintext)
+                                          .map_err(|_| KintoError::TransformError));
+        for field in CLEAR_FIELDS.iter() {
+            if !data[*field].is_null() {
+                decoded[*field] = data[*field].clone();
+            }
+        }
+        Ok(decoded)
+    }
+}
+
+
+fn decode_field(data: &Value, field: &str) -> Result<Vec<u8>, KintoError> {
+    let encoded = try!(data[field].as_str().ok_or(KintoError::TransformError));
+    base64::decode_block(encoded).map_err(|_| KintoError::TransformError)
+}
+
+
+#[cfg(test)]
+mod test_transformer {
+    use std::io::Cursor;
+
+    use conflict::ServerWins;
+    use error::KintoError;
+    use resource::Resource;
+    use utils::tests::setup_collection;
+    use super::{AesGcmTransformer, RecordTransformer, TransformerPipeline};
+
+    const NOTE: &str = "/buckets/default/collections/notes/records/note";
+
+    fn transformer() -> AesGcmTransformer {
+        AesGcmTransformer::new("first", &[1; 32]).unwrap()
+    }
+
+    #[test]
+    fn test_encrypt_and_decrypt() {
+        let data = json!({"id": "note", "last_modified": 42, "title": "secret"});
+        let encoded = transformer().encode(NOTE, data.clone()).unwrap();
+
+        assert_eq!(encoded["id"], "note");
+        assert_eq!(encoded["last_modified"], 42);
+        assert!(encoded["title"].is_null());
+        assert!(!encoded["ciphertext"].as_str().unwrap().contains("secret"));
+        assert_eq!(transformer().decode(NOTE, encoded).unwrap(), data);
+    }
+
+    #[test]
+    fn test_decrypt_fails_on_wrong_key() {
+        let data = json!({"id": "a", "title": "secret"});
+        let encoded = transformer().encode(NOTE, data).unwrap();
+        let mut tampered = encoded.clone();
+        tampered["tag"] = "AAAAAAAAAAAAAAAAAAAAAA==".into();
+        transformer().decode(NOTE, tampered).unwrap_err();
+
+        let other = AesGcmTransformer::new("first", &[2; 32]).unwrap();
+        other.decode(NOTE, encoded).unwrap_err();
+    }
+
+    #[test]
+    fn test_decrypt_fails_on_other_record() {
+        let data = json!({"id": "note", "title": "secret"});
+        let encoded = transformer().encode(NOTE, data).unwrap();
+        let mut moved = encoded.clone();
+        moved["id"] = "other".into();
+        transformer()
+            .decode("/buckets/default/collections/notes/records/other", moved)
+            .unwrap_err();
+        transformer()
+            .decode("/buckets/default/collections/other/records/note", encoded)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_key_rotation() {
+        let mut transformer = transformer();
+        let encoded = transformer.encode(NOTE, json!({"id": "a", "title": "secret"}))
+            .unwrap();
+
+        transformer.rotate("second", &[2; 32]).unwrap();
+        assert!(transformer.needs_rotation(&encoded));
+        let decoded = transformer.decode(NOTE, encoded).unwrap();
+
+        let encoded = transformer.encode(NOTE, decoded).unwrap();
+        assert_eq!(encoded["key_id"], "second");
+        assert!(!transformer.needs_rotation(&encoded));
+    }
+
+    #[test]
+    fn test_pipeline_order() {
+        let mut pipeline = TransformerPipeline::new();
+        pipeline.push(transformer());
+        pipeline.push(AesGcmTransformer::new("outer", &[3; 32]).unwrap());
+
+        let data = json!({"id": "a", "title": "secret"});
+        let encoded = pipeline.encode(NOTE, data.clone()).unwrap();
+        assert_eq!(encoded["key_id"], "outer");
+        assert_eq!(pipeline.decode(NOTE, encoded).unwrap(), data);
+    }
+
+    #[test]
+    fn test_encrypted_records() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        collection.transformers.push(transformer());
+
+        let mut record = collection.record("note");
+        record.data = json!({"title": "secret"}).into();
+        record.create().unwrap();
+        assert_eq!(record.data.clone().unwrap()["title"], "secret");
+
+        // The server only stores the encrypted content
+        let mut raw = collection.clone();
+        raw.transformers = TransformerPipeline::new();
+        let mut stored = raw.record("note");
+        stored.load().unwrap();
+        assert!(stored.data.unwrap()["title"].is_null());
+
+        let mut loaded = collection.record("note");
+        loaded.load().unwrap();
+        assert_eq!(loaded.data.unwrap()["title"], "secret");
+        let records = collection.list_records().unwrap();
+        assert_eq!(records[0].data.clone().unwrap()["title"], "secret");
+
+        record.patch(json!({"tags": ["private"]})).unwrap();
+        let data = record.data.unwrap();
+        assert_eq!(data["title"], "secret");
+        assert_eq!(data["tags"][0], "private");
+    }
+
+    #[test]
+    fn test_encrypted_record_moved_to_other_id() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        collection.transformers.push(transformer());
+        let mut record = collection.record("note");
+        record.data = json!({"title": "secret"}).into();
+        record.create().unwrap();
+
+        let mut raw = collection.clone();
+        raw.transformers = TransformerPipeline::new();
+        let mut stored = raw.record("note");
+        stored.load().unwrap();
+        let mut moved = raw.record("other");
+        moved.data = stored.data;
+        moved.set().unwrap();
+
+        collection.record("other").load().unwrap_err();
+    }
+
+    #[test]
+    fn test_encrypted_record_conflict() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        collection.transformers.push(transformer());
+        let mut record = collection.record("note");
+        record.data = json!({"title": "secret"}).into();
+        record.create().unwrap();
+
+        let mut remote = record.clone();
+        remote.data = json!({"title": "remote secret"}).into();
+        remote.update().unwrap();
+
+        record.data = json!({"title": "local secret", "last_modified": 1}).into();
+        record.update_with(None, &ServerWins).unwrap();
+        assert_eq!(record.data.unwrap()["title"], "remote secret");
+    }
+
+    #[test]
+    fn test_encrypted_record_cannot_be_attached() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        collection.transformers.push(transformer());
+        let mut record = collection.record("note");
+        record.data = json!({"title": "secret"}).into();
+        record.create().unwrap();
+
+        match record.attach(Cursor::new(b"secret"), "text/plain", "secret.txt") {
+            Err(KintoError::TransformError) => (),
+            result => panic!("{:?}", result),
+        };
+        record.load().unwrap();
+        assert!(record.attachment().is_none());
+    }
+
+    #[test]
+    fn test_rewrite_records_after_rotation() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        collection.transformers.push(transformer());
+        let mut record = collection.record("note");
+        record.data = json!({"title": "secret"}).into();
+        record.create().unwrap();
+
+        let mut rotated = transformer();
+        rotated.rotate("second", &[2; 32]).unwrap();
+        collection.transformers = TransformerPipeline::new();
+        collection.transformers.push(rotated);
+        collection.rewrite_records().unwrap();
+
+        let mut raw = collection.clone();
+        raw.transformers = TransformerPipeline::new();
+        let mut stored = raw.record("note");
+        stored.load().unwrap();
+        assert_eq!(stored.data.unwrap()["key_id"], "second");
+    }
+}
@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 
-use hyper::header::EntityTag;
+use hyper::header::{EntityTag, ETag, Headers};
+use openssl::rand::rand_bytes;
 
 use response::ResponseWrapper;
 use resource::Resource;
@@ -26,18 +27,59 @@ pub fn timestamp_to_etag(timestamp: u64) -> Vec<EntityTag> {
 }
 
 
+/// Get the integer timestamp from an ETag response header.
+pub fn etag_to_timestamp(headers: &Headers) -> Option<u64> {
+    match headers.get::<ETag>() {
+        Some(etag) => etag.tag().parse().ok(),
+        None => None,
+    }
+}
+
+
+/// Generate a random (version 4) UUID.
+pub fn uuid4() -> String {
+    let mut bytes = [0; 16];
+    rand_bytes(&mut bytes).unwrap();
+
+    // Set version and variant bits
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}",
+            hex[0..4].join(""),
+            hex[4..6].join(""),
+            hex[6..8].join(""),
+            hex[8..10].join(""),
+            hex[10..16].join(""))
+}
+
+
+/// Whether a path segment is an API version (e.g. `v1`).
+pub fn is_api_version(segment: &str) -> bool {
+    segment.len() > 1 && segment.starts_with('v') &&
+    segment[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+
 /// Split a path (e.g. "/buckets/food/collections/foo") into a resource name HashMap.
 pub fn extract_ids_from_path(path: String) -> HashMap<String, Option<String>> {
 
-    // XXX: Remove version from path if exists. We shouldn't hardcode version
-    let path = path.replace("/v1", "").to_owned();
-
     // Split path into ["", "buckets", "bucket_id", ...]
-    let mut split = path.split("/");
+    let mut split = path.split("/").peekable();
 
     // Remove starting "/"
     split.next().unwrap();
 
+    // Remove version from path if exists (e.g. "/v1")
+    let versioned = match split.peek() {
+        Some(segment) => is_api_version(segment),
+        None => false,
+    };
+    if versioned {
+        split.next();
+    }
+
     let mut map = HashMap::new();
 
     while let Some(key) = split.next() {
@@ -72,7 +114,7 @@ pub mod tests {
                                      username: "a".to_owned(),
                                      password: Some("a".to_owned()),
                                  });
-        let client = KintoClient::new(server_url, auth.into());
+        let client = KintoClient::new(server_url, auth.into()).unwrap();
         client.flush().unwrap();
         return client;
     }
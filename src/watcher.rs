@@ -0,0 +1,364 @@
+use std::cmp;
+use std::collections::HashSet;
+use std::str;
+use std::sync::mpsc::{channel, Receiver, Sender, RecvTimeoutError};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use hyper::header::{Headers, IfNoneMatch};
+
+use error::KintoError;
+
+use request::{KintoRequest, PluralEndpoint, check_status};
+use resource::Resource;
+use collection::Collection;
+use record::Record;
+use utils::{timestamp_to_etag, etag_to_timestamp};
+
+
+/// Longest wait between two polls after consecutive errors.
+const MAX_ERROR_WAIT: u64 = 300;
+
+
+/// Change observed on a watched collection.
+#[derive(Debug, Clone)]
+pub enum ChangeEvent {
+    RecordCreated(Record),
+    RecordUpdated(Record),
+    RecordDeleted(Record),
+}
+
+
+/// Polling state of a watched collection.
+#[derive(Debug, Clone)]
+struct WatchedCollection {
+    collection: Collection,
+    timestamp: Option<u64>,
+    known: HashSet<String>,
+}
+
+
+/// Poll collections for changes, using `_since` and the last known ETag.
+#[derive(Debug, Clone)]
+pub struct Watcher {
+    pub interval: Duration,
+    /// Wait requested by the server with the `Backoff` or `Retry-After`
+    /// headers during the last poll.
+    pub backoff: Option<Duration>,
+    watched: Vec<WatchedCollection>,
+}
+
+
+impl Watcher {
+    /// Create a watcher polling the given collections every `interval`.
+    pub fn new(collections: Vec<Collection>, interval: Duration) -> Watcher {
+        let watched = collections.into_iter()
+            .map(|collection| {
+                     WatchedCollection {
+                         collection: collection,
+                         timestamp: None,
+                         known: HashSet::new(),
+                     }
+                 })
+            .collect();
+
+        Watcher {
+            interval: interval,
+            backoff: None,
+            watched: watched,
+        }
+    }
+
+    /// Poll every collection once and return the changes.
+    ///
+    /// The first poll of a collection only loads its current state and does
+    /// not produce any event. If a collection cannot be polled, no collection
+    /// is considered polled: their changes are returned by the next poll.
+    pub fn poll(&mut self) -> Result<Vec<ChangeEvent>, KintoError> {
+        let mut events = vec![];
+        let mut watched = self.watched.clone();
+        self.backoff = None;
+
+        for collection in &mut watched {
+            events.extend(try!(poll_collection(collection, &mut self.backoff)));
+        }
+        self.watched = watched;
+        Ok(events)
+    }
+
+    /// Poll in a background thread and pass the changes to `callback`.
+    ///
+    /// Polling errors are retried with an increasing delay.
+    pub fn watch<F>(mut self, mut callback: F) -> WatcherHandle
+        where F: FnMut(ChangeEvent) + Send + 'static
+    {
+        let (stop_sender, stop_receiver) = channel();
+
+        let thread = thread::spawn(move || {
+            let mut errors = 0;
+            loop {
+                let wait = match self.poll() {
+                    Ok(events) => {
+                        errors = 0;
+                        for event in events {
+                            callback(event);
+                        }
+                        cmp::max(self.interval, self.backoff.unwrap_or_default())
+                    }
+                    Err(_) => {
+                        errors += 1;
+                        let seconds = cmp::min(2u64.pow(cmp::min(errors, 16)),
+                                               MAX_ERROR_WAIT);
+                        let wait = cmp::max(self.interval, Duration::from_secs(seconds));
+                        cmp::max(wait, self.backoff.unwrap_or_default())
+                    }
+                };
+
+                match stop_receiver.recv_timeout(wait) {
+                    Err(RecvTimeoutError::Timeout) => (),
+                    _ => return,
+                };
+            }
+        });
+
+        WatcherHandle {
+            stop: stop_sender,
+            thread: thread,
+        }
+    }
+
+    /// Poll in a background thread and send the changes on a channel.
+    pub fn watch_channel(self) -> (WatcherHandle, Receiver<ChangeEvent>) {
+        let (sender, receiver) = channel();
+        let handle = self.watch(move |event| {
+                                    let _ = sender.send(event);
+                                });
+        (handle, receiver)
+    }
+}
+
+
+/// Get the changes of a collection since its last poll.
+fn poll_collection(watched: &mut WatchedCollection,
+                   backoff: &mut Option<Duration>)
+                   -> Result<Vec<ChangeEvent>, KintoError> {
+    let resource = watched.collection.new_record();
+    let mut request = try!(resource.list_request());
+
+    match watched.timestamp {
+        Some(timestamp) => {
+            request.since(timestamp)
+                .if_none_match(IfNoneMatch::Items(timestamp_to_etag(timestamp)));
+        }
+        None => (),
+    };
+
+    let response = try!(request.send_unchecked());
+
+    // Server backoff (in seconds), sent with any status
+    for header in &["backoff", "retry-after"] {
+        match seconds(&response.headers, header) {
+            Some(seconds) => {
+                let wait = Duration::from_secs(seconds);
+                *backoff = Some(cmp::max(backoff.unwrap_or(wait), wait));
+            }
+            None => (),
+        };
+    }
+    let response = match check_status(response) {
+        Ok(response) => try!(request.follow_pages(response)),
+        Err(KintoError::NotModified) => return Ok(vec![]),
+        Err(error) => return Err(error),
+    };
+
+    let first_poll = watched.timestamp.is_none();
+    let mut events = vec![];
+
+    for data in response.body["data"].as_array().cloned().unwrap_or_default() {
+        let id = match data["id"].as_str() {
+            Some(id) => id.to_owned(),
+            None => continue,
+        };
+        let deleted = data["deleted"].as_bool().unwrap_or(false);
+        let record = resource.clone().set_data(data);
+
+        if first_poll {
+            watched.known.insert(id);
+        } else if deleted {
+            watched.known.remove(&id);
+            events.push(ChangeEvent::RecordDeleted(record));
+        } else if watched.known.insert(id) {
+            events.push(ChangeEvent::RecordCreated(record));
+        } else {
+            events.push(ChangeEvent::RecordUpdated(record));
+        }
+    }
+
+    // Oldest changes first
+    events.reverse();
+
+    watched.timestamp = match etag_to_timestamp(&response.headers) {
+        Some(timestamp) => Some(timestamp),
+        None => watched.timestamp,
+    };
+    Ok(events)
+}
+
+
+/// Get a header value in seconds.
+fn seconds(headers: &Headers, name: &str) -> Option<u64> {
+    match headers.get_raw(name) {
+        Some(values) => {
+            str::from_utf8(values[0].as_slice())
+                .ok()
+                .and_then(|value| value.trim().parse().ok())
+        }
+        None => None,
+    }
+}
+
+
+/// Handle on a background watcher thread.
+#[derive(Debug)]
+pub struct WatcherHandle {
+    stop: Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+
+impl WatcherHandle {
+    /// Stop polling and wait for the current poll to finish.
+    pub fn stop(self) {
+        let _ = self.stop.send(());
+        let _ = self.thread.join();
+    }
+}
+
+
+#[cfg(test)]
+mod test_watcher {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    use KintoClient;
+    use resource::Resource;
+    use utils::tests::setup_collection;
+    use super::{Watcher, ChangeEvent};
+
+    #[test]
+    fn test_first_poll_has_no_events() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        collection.record("entrecote").create().unwrap();
+
+        let mut watcher = Watcher::new(vec![collection], Duration::from_secs(1));
+        assert_eq!(watcher.poll().unwrap().len(), 0);
+        assert_eq!(watcher.poll().unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_poll_changes() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+
+        let mut watcher = Watcher::new(vec![collection.clone()], Duration::from_secs(1));
+        watcher.poll().unwrap();
+
+        let mut record = collection.record("entrecote");
+        record.create().unwrap();
+        match watcher.poll().unwrap()[0] {
+            ChangeEvent::RecordCreated(ref record) => {
+                assert_eq!(record.get_id().unwrap(), "entrecote")
+            }
+            ref event => panic!("Unexpected event {:?}", event),
+        };
+
+        record.update().unwrap();
+        match watcher.poll().unwrap()[0] {
+            ChangeEvent::RecordUpdated(_) => (),
+            ref event => panic!("Unexpected event {:?}", event),
+        };
+
+        record.delete().unwrap();
+        match watcher.poll().unwrap()[0] {
+            ChangeEvent::RecordDeleted(_) => (),
+            ref event => panic!("Unexpected event {:?}", event),
+        };
+    }
+
+    #[test]
+    fn test_watch_channel() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+
+        let watcher = Watcher::new(vec![collection.clone()], Duration::from_millis(50));
+        let (handle, events) = watcher.watch_channel();
+
+        // Let the first poll load the collection state
+        ::std::thread::sleep(Duration::from_millis(200));
+        collection.record("entrecote").create().unwrap();
+
+        match events.recv_timeout(Duration::from_secs(5)).unwrap() {
+            ChangeEvent::RecordCreated(_) => (),
+            event => panic!("Unexpected event {:?}", event),
+        };
+        handle.stop();
+    }
+
+    #[test]
+    fn test_failed_poll_keeps_changes() {
+        let mut collection = setup_collection();
+        collection.create().unwrap();
+        let mut other = collection.bucket.clone().collection("fish");
+        other.create().unwrap();
+
+        let mut watcher = Watcher::new(vec![collection.clone(), other.clone()],
+                                       Duration::from_secs(1));
+        watcher.poll().unwrap();
+
+        collection.record("entrecote").create().unwrap();
+        other.delete().unwrap();
+        watcher.poll().unwrap_err();
+
+        other.create().unwrap();
+        match watcher.poll().unwrap()[0] {
+            ChangeEvent::RecordCreated(ref record) => {
+                assert_eq!(record.get_id().unwrap(), "entrecote")
+            }
+            ref event => panic!("Unexpected event {:?}", event),
+        };
+    }
+
+    #[test]
+    fn test_backoff_on_any_response() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1", listener.local_addr().unwrap());
+        let server = thread::spawn(move || {
+            let responses: [&[u8]; 2] = [b"HTTP/1.1 304 Not Modified\r\n\
+                                           Backoff: 30\r\n\
+                                           Connection: close\r\n\r\n",
+                                         b"HTTP/1.1 503 Service Unavailable\r\n\
+                                           Retry-After: 60\r\n\
+                                           Content-Length: 2\r\n\
+                                           Connection: close\r\n\r\n{}"];
+            for response in responses.iter() {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0; 4096];
+                let _ = stream.read(&mut request).unwrap();
+                stream.write_all(response).unwrap();
+            }
+        });
+
+        let client = KintoClient::new(url, None).unwrap();
+        let collection = client.bucket("food").collection("meat");
+        let mut watcher = Watcher::new(vec![collection], Duration::from_secs(1));
+        assert_eq!(watcher.poll().unwrap().len(), 0);
+        assert_eq!(watcher.backoff, Some(Duration::from_secs(30)));
+        watcher.poll().unwrap_err();
+        assert_eq!(watcher.backoff, Some(Duration::from_secs(60)));
+        server.join().unwrap();
+    }
+}